@@ -0,0 +1,118 @@
+use syn::{self, Attribute, LitInt, LitStr};
+
+
+// Attributes on the struct or enum itself
+#[derive(Default)]
+pub struct Container {
+    // Write every variant by its name instead of its index
+    pub named: bool,
+}
+
+// Attributes on an enum variant
+#[derive(Default)]
+pub struct Variant {
+    pub named: bool,
+    pub rename: Option<String>,
+    // Catch every variant that isn't known about
+    pub other: bool,
+}
+
+// Attributes on a field of a struct or variant
+#[derive(Default)]
+pub struct Field {
+    pub rename: Option<String>,
+    // The field's ID when structs are written with `SerializerOptions::field_ids`
+    pub id: Option<u32>,
+    // Fill the field in with `Default::default()` when it's missing
+    pub default: bool,
+    // Never write the field, and always fill it in with `Default::default()`
+    pub skip: bool,
+    // Write a `Vec` or boxed slice of numbers as a `LeSlice`
+    pub packed: bool,
+    pub intern: bool,
+    pub shrink_floats: bool,
+}
+
+// Calls `parse` for every item inside every `#[dbor(...)]` attribute. `#[serde(...)]` attributes
+//   are rejected, since they'd otherwise be silently ignored (they're only allowed at all when
+//   serde's derive is used for the other half).
+fn each_item<F>(attrs: &[Attribute], mut parse: F) -> syn::Result<()>
+where
+    F: FnMut(syn::meta::ParseNestedMeta) -> syn::Result<()>
+{
+    for attr in attrs {
+        if attr.path().is_ident("serde") {
+            return Err(syn::Error::new_spanned(attr, "serde attributes aren't supported by the \
+                dbor derives, use #[dbor(...)] attributes instead"));
+        }
+        if attr.path().is_ident("dbor") {
+            attr.parse_nested_meta(&mut parse)?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn container(attrs: &[Attribute], is_enum: bool) -> syn::Result<Container> {
+    let mut container = Container::default();
+
+    each_item(attrs, |meta| {
+        if meta.path.is_ident("named") && is_enum {
+            container.named = true;
+        } else {
+            return Err(meta.error("unknown dbor attribute"));
+        }
+
+        Ok(())
+    })?;
+
+    Ok(container)
+}
+
+pub fn variant(attrs: &[Attribute]) -> syn::Result<Variant> {
+    let mut variant = Variant::default();
+
+    each_item(attrs, |meta| {
+        if meta.path.is_ident("named") {
+            variant.named = true;
+        } else if meta.path.is_ident("rename") {
+            variant.rename = Some(meta.value()?.parse::<LitStr>()?.value());
+        } else if meta.path.is_ident("other") {
+            variant.other = true;
+        } else {
+            return Err(meta.error("unknown dbor attribute"));
+        }
+
+        Ok(())
+    })?;
+
+    Ok(variant)
+}
+
+pub fn field(attrs: &[Attribute]) -> syn::Result<Field> {
+    let mut field = Field::default();
+
+    each_item(attrs, |meta| {
+        if meta.path.is_ident("rename") {
+            field.rename = Some(meta.value()?.parse::<LitStr>()?.value());
+        } else if meta.path.is_ident("id") {
+            field.id = Some(meta.value()?.parse::<LitInt>()?.base10_parse()?);
+        } else if meta.path.is_ident("default") {
+            field.default = true;
+        } else if meta.path.is_ident("skip") {
+            field.skip = true;
+        } else if meta.path.is_ident("packed") {
+            field.packed = true;
+        } else if meta.path.is_ident("intern") {
+            field.intern = true;
+        } else if meta.path.is_ident("shrink_floats") {
+            field.shrink_floats = true;
+        } else {
+            return Err(meta.error("unknown dbor attribute"));
+        }
+
+        Ok(())
+    })?;
+
+    Ok(field)
+}
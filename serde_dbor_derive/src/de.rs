@@ -0,0 +1,664 @@
+use proc_macro2::{Span, TokenStream};
+use syn::{self, Data, DeriveInput, GenericParam, Ident, LitByteStr};
+
+use {attr, bound_params, fields, struct_name, variants, Field, Style, Variant};
+
+
+// The pieces of the type's generics that every generated visitor needs
+struct Context {
+    ident: Ident,
+    // The generics of the type itself
+    ty_generics: TokenStream,
+    // The generics of the impl and the visitors, which start with `'de`
+    impl_generics: TokenStream,
+    visitor_generics: TokenStream,
+    where_clause: TokenStream,
+}
+
+pub fn expand(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let body = match input.data {
+        Data::Struct(ref data) => {
+            attr::container(&input.attrs, false)?;
+
+            let (style, fields) = fields(&data.fields)?;
+            let context = context(input, &fields.iter().collect::<Vec<_>>());
+
+            deserialize_struct(&context, style, &fields)?
+        }
+        Data::Enum(ref data) => {
+            let variants = variants(input, data)?;
+            let all_fields: Vec<&Field> = variants.iter()
+                .flat_map(|variant| &variant.fields)
+                .collect();
+            let context = context(input, &all_fields);
+
+            deserialize_enum(&context, &variants)
+        }
+        Data::Union(_) => {
+            return Err(syn::Error::new_spanned(input, "unions can't be deserialized"));
+        }
+    };
+
+    Ok(body)
+}
+
+fn context(input: &DeriveInput, fields: &[&Field]) -> Context {
+    let mut generics = bound_params(&input.generics, quote!(_serde::Deserialize<'de>));
+
+    // Fields that can be left out need a default value
+    for field in fields {
+        if field.attrs.default || field.attrs.skip {
+            let ty = field.ty;
+
+            generics.make_where_clause().predicates
+                .push(syn::parse_quote!(#ty: _core::default::Default));
+        }
+    }
+
+    // Borrowed fields can only live as long as the input they're borrowed from
+    let lifetimes: Vec<_> = generics.lifetimes().map(|param| param.lifetime.clone()).collect();
+    let de: GenericParam = if lifetimes.is_empty() {
+        syn::parse_quote!('de)
+    } else {
+        syn::parse_quote!('de: #(#lifetimes)+*)
+    };
+
+    generics.params.insert(0, de);
+
+    let (_, ty_generics, _) = input.generics.split_for_impl();
+    let (impl_generics, visitor_generics, where_clause) = generics.split_for_impl();
+
+    Context {
+        ident: input.ident.clone(),
+        ty_generics: quote!(#ty_generics),
+        impl_generics: quote!(#impl_generics),
+        visitor_generics: quote!(#visitor_generics),
+        where_clause: quote!(#where_clause),
+    }
+}
+
+// Wraps the body of `deserialize` in the impl
+fn wrap(context: &Context, body: TokenStream) -> TokenStream {
+    let Context { ref ident, ref ty_generics, ref impl_generics, ref where_clause, .. } = *context;
+
+    quote! {
+        #[allow(non_upper_case_globals, unused_attributes, unused_qualifications)]
+        const _: () = {
+            extern crate core as _core;
+            extern crate serde as _serde;
+            extern crate serde_dbor as _serde_dbor;
+
+            impl #impl_generics _serde::Deserialize<'de> for #ident #ty_generics #where_clause {
+                fn deserialize<__D>(__deserializer: __D)
+                    -> _core::result::Result<Self, __D::Error>
+                where
+                    __D: _serde::Deserializer<'de>
+                {
+                    #body
+                }
+            }
+        };
+    }
+}
+
+// The type to deserialize a field as, and how to get the field's value out of it
+fn field_type(field: &Field) -> (TokenStream, TokenStream) {
+    let ty = field.ty;
+
+    if field.attrs.packed {
+        (quote!(_serde_dbor::private::DeserializePacked<#ty>), quote!(.0))
+    } else {
+        (quote!(#ty), quote!())
+    }
+}
+
+// What to do when a field is missing, in a seq or a map
+fn missing(field: &Field, error: TokenStream) -> TokenStream {
+    if field.attrs.default {
+        quote!(_core::default::Default::default())
+    } else {
+        quote!(return _core::result::Result::Err(#error))
+    }
+}
+
+// An enum of the fields of a struct, which is what a struct's keys get deserialized as
+fn field_identifier(fields: &[&Field]) -> TokenStream {
+    let bindings: Vec<&Ident> = fields.iter().map(|field| &field.binding).collect();
+    let names: Vec<&String> = fields.iter().map(|field| &field.name).collect();
+    let bytes: Vec<LitByteStr> = names.iter()
+        .map(|name| LitByteStr::new(name.as_bytes(), Span::call_site()))
+        .collect();
+    let ids: Vec<u64> = fields.iter().enumerate()
+        .map(|(position, field)| field.attrs.id.map_or(position as u64, u64::from))
+        .collect();
+
+    quote! {
+        #[allow(non_camel_case_types)]
+        enum __Field {
+            #(#bindings,)*
+            __ignore,
+        }
+
+        struct __FieldVisitor;
+
+        impl<'de> _serde::de::Visitor<'de> for __FieldVisitor {
+            type Value = __Field;
+
+            fn expecting(&self, __formatter: &mut _core::fmt::Formatter)
+                -> _core::fmt::Result
+            {
+                _core::fmt::Formatter::write_str(__formatter, "field identifier")
+            }
+
+            fn visit_u64<__E>(self, __value: u64) -> _core::result::Result<__Field, __E>
+            where
+                __E: _serde::de::Error
+            {
+                match __value {
+                    #(#ids => _core::result::Result::Ok(__Field::#bindings),)*
+                    _ => _core::result::Result::Ok(__Field::__ignore),
+                }
+            }
+
+            fn visit_str<__E>(self, __value: &str) -> _core::result::Result<__Field, __E>
+            where
+                __E: _serde::de::Error
+            {
+                match __value {
+                    #(#names => _core::result::Result::Ok(__Field::#bindings),)*
+                    _ => _core::result::Result::Ok(__Field::__ignore),
+                }
+            }
+
+            fn visit_bytes<__E>(self, __value: &[u8]) -> _core::result::Result<__Field, __E>
+            where
+                __E: _serde::de::Error
+            {
+                match __value {
+                    #(#bytes => _core::result::Result::Ok(__Field::#bindings),)*
+                    _ => _core::result::Result::Ok(__Field::__ignore),
+                }
+            }
+        }
+
+        impl<'de> _serde::Deserialize<'de> for __Field {
+            #[inline]
+            fn deserialize<__D>(__deserializer: __D) -> _core::result::Result<Self, __D::Error>
+            where
+                __D: _serde::Deserializer<'de>
+            {
+                _serde::Deserializer::deserialize_identifier(__deserializer, __FieldVisitor)
+            }
+        }
+    }
+}
+
+// A visitor named `visitor` that builds `path` out of `fields`
+fn visitor(context: &Context, visitor: &Ident, expecting: &str, path: TokenStream, style: Style,
+    fields: &[Field]) -> TokenStream
+{
+    let written: Vec<&Field> = fields.iter().filter(|field| field.is_written()).collect();
+    let members: Vec<_> = fields.iter().map(|field| &field.member).collect();
+    let bindings: Vec<_> = fields.iter().map(|field| &field.binding).collect();
+    let construct = match style {
+        Style::Unit => path.clone(),
+        _ => quote!(#path { #(#members: #bindings),* }),
+    };
+    let skipped: Vec<_> = fields.iter()
+        .filter(|field| !field.is_written())
+        .map(|field| {
+            let binding = &field.binding;
+
+            quote!(let #binding = _core::default::Default::default();)
+        })
+        .collect();
+
+    let seq_fields = written.iter().enumerate().map(|(position, field)| {
+        let binding = &field.binding;
+        let (ty, unwrap) = field_type(field);
+        let missing = missing(field, quote! {
+            _serde::de::Error::invalid_length(#position, &#expecting)
+        });
+
+        quote! {
+            let #binding = match _serde::de::SeqAccess::next_element::<#ty>(&mut __seq)? {
+                _core::option::Option::Some(__value) => __value #unwrap,
+                _core::option::Option::None => #missing,
+            };
+        }
+    });
+
+    let visit_seq = quote! {
+        fn visit_seq<__A>(self, mut __seq: __A) -> _core::result::Result<Self::Value, __A::Error>
+        where
+            __A: _serde::de::SeqAccess<'de>
+        {
+            #(#seq_fields)*
+            #(#skipped)*
+
+            _core::result::Result::Ok(#construct)
+        }
+    };
+
+    let visit_other = match style {
+        Style::Unit => quote! {
+            fn visit_unit<__E>(self) -> _core::result::Result<Self::Value, __E>
+            where
+                __E: _serde::de::Error
+            {
+                _core::result::Result::Ok(#construct)
+            }
+        },
+        Style::Newtype => {
+            let binding = &fields[0].binding;
+            let (ty, unwrap) = field_type(&fields[0]);
+
+            quote! {
+                fn visit_newtype_struct<__E>(self, __e: __E)
+                    -> _core::result::Result<Self::Value, __E::Error>
+                where
+                    __E: _serde::Deserializer<'de>
+                {
+                    let #binding = <#ty as _serde::Deserialize>::deserialize(__e)? #unwrap;
+
+                    _core::result::Result::Ok(#construct)
+                }
+            }
+        }
+        Style::Tuple => quote!(),
+        Style::Struct => {
+            let identifier = field_identifier(&written);
+            let declarations = written.iter().map(|field| {
+                let binding = &field.binding;
+                let ty = field.ty;
+
+                quote! {
+                    let mut #binding: _core::option::Option<#ty> = _core::option::Option::None;
+                }
+            });
+            let arms = written.iter().map(|field| {
+                let binding = &field.binding;
+                let name = &field.name;
+                let (ty, unwrap) = field_type(field);
+
+                quote! {
+                    __Field::#binding => {
+                        if _core::option::Option::is_some(&#binding) {
+                            return _core::result::Result::Err(
+                                <__A::Error as _serde::de::Error>::duplicate_field(#name)
+                            );
+                        }
+
+                        #binding = _core::option::Option::Some(
+                            _serde::de::MapAccess::next_value::<#ty>(&mut __map)? #unwrap
+                        );
+                    }
+                }
+            });
+            let unwraps = written.iter().map(|field| {
+                let binding = &field.binding;
+                let name = &field.name;
+                let missing = missing(field, quote! {
+                    <__A::Error as _serde::de::Error>::missing_field(#name)
+                });
+
+                quote! {
+                    let #binding = match #binding {
+                        _core::option::Option::Some(__value) => __value,
+                        _core::option::Option::None => #missing,
+                    };
+                }
+            });
+
+            quote! {
+                fn visit_map<__A>(self, mut __map: __A)
+                    -> _core::result::Result<Self::Value, __A::Error>
+                where
+                    __A: _serde::de::MapAccess<'de>
+                {
+                    #identifier
+
+                    #(#declarations)*
+
+                    while let _core::option::Option::Some(__key) =
+                        _serde::de::MapAccess::next_key::<__Field>(&mut __map)?
+                    {
+                        match __key {
+                            #(#arms)*
+                            __Field::__ignore => {
+                                let _ = _serde::de::MapAccess::next_value::<
+                                    _serde::de::IgnoredAny
+                                >(&mut __map)?;
+                            }
+                        }
+                    }
+
+                    #(#unwraps)*
+                    #(#skipped)*
+
+                    _core::result::Result::Ok(#construct)
+                }
+            }
+        }
+    };
+
+    visitor_impl(context, visitor, expecting, quote! {
+        #visit_seq
+        #visit_other
+    })
+}
+
+// Declares a visitor for the whole type with the given `visit_*` methods
+fn visitor_impl(context: &Context, visitor: &Ident, expecting: &str, methods: TokenStream)
+    -> TokenStream
+{
+    let Context {
+        ref ident, ref ty_generics, ref impl_generics, ref visitor_generics, ref where_clause, ..
+    } = *context;
+
+    quote! {
+        struct #visitor #impl_generics #where_clause {
+            marker: _core::marker::PhantomData<#ident #ty_generics>,
+            lifetime: _core::marker::PhantomData<&'de ()>,
+        }
+
+        impl #impl_generics _serde::de::Visitor<'de> for #visitor #visitor_generics #where_clause {
+            type Value = #ident #ty_generics;
+
+            fn expecting(&self, __formatter: &mut _core::fmt::Formatter)
+                -> _core::fmt::Result
+            {
+                _core::fmt::Formatter::write_str(__formatter, #expecting)
+            }
+
+            #methods
+        }
+    }
+}
+
+fn new_visitor(visitor: &Ident) -> TokenStream {
+    quote! {
+        #visitor {
+            marker: _core::marker::PhantomData,
+            lifetime: _core::marker::PhantomData,
+        }
+    }
+}
+
+fn deserialize_struct(context: &Context, style: Style, fields: &[Field])
+    -> syn::Result<TokenStream>
+{
+    let ident = &context.ident;
+    let name = struct_name(ident, fields)?;
+    let visitor_ident = Ident::new("__Visitor", Span::call_site());
+    let expecting = match style {
+        Style::Tuple => format!("tuple struct {}", ident),
+        _ => format!("struct {}", ident),
+    };
+    let visitor = visitor(context, &visitor_ident, &expecting, quote!(#ident), style, fields);
+    let new_visitor = new_visitor(&visitor_ident);
+
+    let written: Vec<&String> = fields.iter()
+        .filter(|field| field.is_written())
+        .map(|field| &field.name)
+        .collect();
+    let len = written.len();
+
+    let call = match style {
+        Style::Unit => quote! {
+            _serde::Deserializer::deserialize_unit_struct(__deserializer, #name, #new_visitor)
+        },
+        Style::Newtype => quote! {
+            _serde::Deserializer::deserialize_newtype_struct(__deserializer, #name, #new_visitor)
+        },
+        Style::Tuple => quote! {
+            _serde::Deserializer::deserialize_tuple_struct(__deserializer, #name, #len,
+                #new_visitor)
+        },
+        Style::Struct => quote! {
+            const __FIELDS: &'static [&'static str] = &[#(#written),*];
+
+            _serde::Deserializer::deserialize_struct(__deserializer, #name, __FIELDS,
+                #new_visitor)
+        },
+    };
+
+    Ok(wrap(context, quote! {
+        #visitor
+
+        #call
+    }))
+}
+
+fn deserialize_enum(context: &Context, variants: &[Variant]) -> TokenStream {
+    let ident = &context.ident;
+    let enum_name = ident.to_string();
+
+    let bindings: Vec<Ident> = (0..variants.len())
+        .map(|index| Ident::new(&format!("__variant{}", index), Span::call_site()))
+        .collect();
+    let names: Vec<&String> = variants.iter().map(|variant| &variant.name).collect();
+    let expecting_index = format!("variant index 0 <= i < {}", variants.len());
+
+    // A newtype variant that catches unknown variants holds their id instead of being one itself
+    let catches_id = |variant: &Variant| variant.other && variant.style == Style::Newtype;
+    let declarations = variants.iter().zip(&bindings).map(|(variant, binding)| {
+        if catches_id(variant) {
+            quote!(#binding(_serde_dbor::VariantId))
+        } else {
+            quote!(#binding)
+        }
+    });
+    let known: Vec<usize> = (0..variants.len()).filter(|&index| !catches_id(&variants[index]))
+        .collect();
+    let known_bindings: Vec<&Ident> = known.iter().map(|&index| &bindings[index]).collect();
+    let known_names: Vec<&String> = known.iter().map(|&index| names[index]).collect();
+    let known_bytes: Vec<LitByteStr> = known_names.iter()
+        .map(|name| LitByteStr::new(name.as_bytes(), Span::call_site()))
+        .collect();
+    let known_indices: Vec<u64> = known.iter().map(|&index| index as u64).collect();
+
+    // What to do with a variant that isn't known about
+    let other = variants.iter().zip(&bindings).find(|&(variant, _)| variant.other);
+    let (index_fallthrough, name_fallthrough) = match other {
+        Some((variant, binding)) if catches_id(variant) => (
+            quote! {
+                _serde_dbor::private::unknown_index(__value).map(__Variant::#binding)
+            },
+            quote! {
+                _core::result::Result::Ok(__Variant::#binding(
+                    _serde_dbor::private::unknown_name(__value)
+                ))
+            },
+        ),
+        Some((_, binding)) => (
+            quote!(_core::result::Result::Ok(__Variant::#binding)),
+            quote!(_core::result::Result::Ok(__Variant::#binding)),
+        ),
+        None => (
+            quote! {
+                _core::result::Result::Err(_serde::de::Error::invalid_value(
+                    _serde::de::Unexpected::Unsigned(__value),
+                    &#expecting_index,
+                ))
+            },
+            quote! {
+                _core::result::Result::Err(_serde::de::Error::unknown_variant(__value, __VARIANTS))
+            },
+        ),
+    };
+
+    let arms: Vec<TokenStream> = variants.iter().zip(&bindings).map(|(variant, binding)| {
+        let variant_ident = variant.ident;
+        let path = quote!(#ident::#variant_ident);
+        let visitor_ident = Ident::new("__VariantVisitor", Span::call_site());
+        let new_visitor = new_visitor(&visitor_ident);
+        let len = variant.fields.iter().filter(|field| field.is_written()).count();
+
+        let body = match variant.style {
+            _ if catches_id(variant) => quote! {
+                let __payload = _serde::de::VariantAccess::newtype_variant::<
+                    _serde_dbor::RawDbor
+                >(__variant)?;
+
+                _core::result::Result::Ok(#path(_serde_dbor::UnknownVariant {
+                    id: __id,
+                    payload: __payload,
+                }))
+            },
+            Style::Unit => quote! {
+                _serde::de::VariantAccess::unit_variant(__variant)?;
+
+                _core::result::Result::Ok(#path)
+            },
+            Style::Newtype => {
+                let (ty, unwrap) = field_type(&variant.fields[0]);
+
+                quote! {
+                    let __value = _serde::de::VariantAccess::newtype_variant::<#ty>(__variant)?;
+
+                    _core::result::Result::Ok(#path(__value #unwrap))
+                }
+            }
+            Style::Tuple => {
+                let expecting = format!("tuple variant {}::{}", ident, variant_ident);
+                let visitor = visitor(context, &visitor_ident, &expecting, path, Style::Tuple,
+                    &variant.fields);
+
+                quote! {
+                    #visitor
+
+                    _serde::de::VariantAccess::tuple_variant(__variant, #len, #new_visitor)
+                }
+            }
+            Style::Struct => {
+                let expecting = format!("struct variant {}::{}", ident, variant_ident);
+                let visitor = visitor(context, &visitor_ident, &expecting, path, Style::Struct,
+                    &variant.fields);
+                let written = variant.fields.iter()
+                    .filter(|field| field.is_written())
+                    .map(|field| &field.name);
+
+                quote! {
+                    #visitor
+
+                    const __FIELDS: &'static [&'static str] = &[#(#written),*];
+
+                    _serde::de::VariantAccess::struct_variant(__variant, __FIELDS, #new_visitor)
+                }
+            }
+        };
+
+        let pattern = if catches_id(variant) {
+            quote!(__Variant::#binding(__id))
+        } else {
+            quote!(__Variant::#binding)
+        };
+
+        quote! {
+            (#pattern, __variant) => {
+                #body
+            }
+        }
+    }).collect();
+
+    let visit_enum = if variants.is_empty() {
+        quote! {
+            let (__value, _) = _serde::de::EnumAccess::variant::<__Variant>(__data)?;
+
+            match __value {}
+        }
+    } else {
+        quote! {
+            match _serde::de::EnumAccess::variant::<__Variant>(__data)? {
+                #(#arms)*
+            }
+        }
+    };
+
+    let expecting = format!("enum {}", ident);
+    let visitor_ident = Ident::new("__Visitor", Span::call_site());
+    let visitor = visitor_impl(context, &visitor_ident, &expecting, quote! {
+        fn visit_enum<__A>(self, __data: __A) -> _core::result::Result<Self::Value, __A::Error>
+        where
+            __A: _serde::de::EnumAccess<'de>
+        {
+            #visit_enum
+        }
+    });
+    let new_visitor = new_visitor(&visitor_ident);
+
+    wrap(context, quote! {
+        #[allow(non_camel_case_types)]
+        enum __Variant {
+            #(#declarations,)*
+        }
+
+        const __VARIANTS: &'static [&'static str] = &[#(#names),*];
+
+        struct __VariantIdentVisitor;
+
+        impl<'de> _serde::de::Visitor<'de> for __VariantIdentVisitor {
+            type Value = __Variant;
+
+            fn expecting(&self, __formatter: &mut _core::fmt::Formatter)
+                -> _core::fmt::Result
+            {
+                _core::fmt::Formatter::write_str(__formatter, "variant identifier")
+            }
+
+            fn visit_u64<__E>(self, __value: u64) -> _core::result::Result<__Variant, __E>
+            where
+                __E: _serde::de::Error
+            {
+                match __value {
+                    #(#known_indices => _core::result::Result::Ok(__Variant::#known_bindings),)*
+                    _ => #index_fallthrough,
+                }
+            }
+
+            fn visit_str<__E>(self, __value: &str) -> _core::result::Result<__Variant, __E>
+            where
+                __E: _serde::de::Error
+            {
+                match __value {
+                    #(#known_names => _core::result::Result::Ok(__Variant::#known_bindings),)*
+                    _ => #name_fallthrough,
+                }
+            }
+
+            fn visit_bytes<__E>(self, __value: &[u8]) -> _core::result::Result<__Variant, __E>
+            where
+                __E: _serde::de::Error
+            {
+                match __value {
+                    #(#known_bytes => _core::result::Result::Ok(__Variant::#known_bindings),)*
+                    _ => match _core::str::from_utf8(__value) {
+                        _core::result::Result::Ok(__value) => #name_fallthrough,
+                        _core::result::Result::Err(_) => _core::result::Result::Err(
+                            _serde::de::Error::invalid_value(
+                                _serde::de::Unexpected::Bytes(__value),
+                                &self,
+                            )
+                        ),
+                    },
+                }
+            }
+        }
+
+        impl<'de> _serde::Deserialize<'de> for __Variant {
+            #[inline]
+            fn deserialize<__D>(__deserializer: __D) -> _core::result::Result<Self, __D::Error>
+            where
+                __D: _serde::Deserializer<'de>
+            {
+                _serde::Deserializer::deserialize_identifier(__deserializer, __VariantIdentVisitor)
+            }
+        }
+
+        #visitor
+
+        _serde::Deserializer::deserialize_enum(__deserializer, #enum_name, __VARIANTS,
+            #new_visitor)
+    })
+}
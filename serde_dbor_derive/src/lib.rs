@@ -0,0 +1,263 @@
+//! # DBOR Derive
+//!
+//! Derive macros that implement serde's `Serialize` and `Deserialize` the same way serde's own
+//! derives do, along with `#[dbor(...)]` attributes for encoding choices that only make sense for
+//! DBOR. Every field is still written and read through its own serde implementation, so any type
+//! that works with `serde_dbor` works inside these derives too.
+//!
+//! ```rust,ignore
+//! #[macro_use]
+//! extern crate serde_dbor_derive;
+//!
+//! #[derive(DborSerialize, DborDeserialize)]
+//! struct Mesh {
+//!     #[dbor(id = 0, intern)]
+//!     name: String,
+//!     #[dbor(id = 1, packed)]
+//!     vertices: Vec<f32>,
+//!     #[dbor(id = 2, default)]
+//!     scale: f64,
+//! }
+//! ```
+//!
+//! ### Container attributes
+//! * `#[dbor(named)]` - write every variant of an enum by its name instead of its index
+//!
+//! ### Variant attributes
+//! * `#[dbor(named)]` - write this variant by its name instead of its index
+//! * `#[dbor(rename = "...")]` - use a different name for this variant
+//! * `#[dbor(other)]` - read every variant that isn't known about as this one, which has to be
+//!   either a unit variant (like `#[serde(other)]`) or a newtype variant holding a
+//!   `serde_dbor::UnknownVariant`, which keeps the variant's id and payload so that it can be
+//!   written back out unchanged
+//!
+//! ### Field attributes
+//! * `#[dbor(id = 7)]` - the field's ID when structs are written with
+//!   `SerializerOptions::field_ids`, which has to be given to either every field or none of them
+//!   (fields without IDs are numbered in the order they're declared in, not counting skipped ones)
+//! * `#[dbor(default)]` - fill the field in with `Default::default()` when it's missing
+//! * `#[dbor(skip)]` - never write the field, and always fill it in with `Default::default()`
+//! * `#[dbor(rename = "...")]` - use a different name for this field
+//! * `#[dbor(packed)]` - write a `Vec` or boxed slice of numbers as a packed `LeSlice`
+//! * `#[dbor(intern)]` - intern the strings inside this field, like
+//!   `SerializerOptions::intern_strings` does for everything
+//! * `#[dbor(shrink_floats)]` - shrink the floats inside this field, like
+//!   `SerializerOptions::shrink_floats` does for everything
+//!
+//! ### Limitations
+//! Only `#[dbor(...)]` attributes are understood, so `#[serde(...)]` attributes on a type that
+//! uses either of these derives are a compile error instead of being ignored. A type that needs
+//! serde's attributes has to use serde's derives instead.
+//!
+//! Other serializers see structs with explicit field IDs and named variants under generated names
+//! (which show up wherever a format writes struct names, like in error messages), and interned and
+//! shrunk fields as newtype structs around their values.
+
+extern crate proc_macro;
+extern crate proc_macro2;
+#[macro_use]
+extern crate quote;
+extern crate syn;
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use syn::{DeriveInput, Fields, Generics, Ident, Member, Type};
+
+mod attr;
+mod ser;
+mod de;
+
+
+// Has to match `serde_dbor::private::FIELD_IDS_TOKEN`, since struct names with field IDs are
+//   built at compile time
+const FIELD_IDS_TOKEN: &str = "$serde_dbor::private::FieldIds:";
+
+/// Implements `serde::Serialize`, with support for `#[dbor(...)]` attributes
+#[proc_macro_derive(DborSerialize, attributes(dbor))]
+pub fn derive_serialize(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as DeriveInput);
+
+    ser::expand(&input)
+        .unwrap_or_else(compile_errors)
+        .into()
+}
+
+/// Implements `serde::Deserialize`, with support for `#[dbor(...)]` attributes
+#[proc_macro_derive(DborDeserialize, attributes(dbor))]
+pub fn derive_deserialize(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as DeriveInput);
+
+    de::expand(&input)
+        .unwrap_or_else(compile_errors)
+        .into()
+}
+
+// Turns errors into `compile_error!`s. syn's own version calls it through `::core`, which
+//   edition 2015 crates can't see without an `extern crate core`.
+fn compile_errors(error: syn::Error) -> TokenStream2 {
+    error.into_iter()
+        .map(|error| {
+            let message = error.to_string();
+
+            quote_spanned!(error.span()=> compile_error!(#message);)
+        })
+        .collect()
+}
+
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Style {
+    Unit,
+    Newtype,
+    Tuple,
+    Struct,
+}
+
+struct Field<'a> {
+    member: Member,
+    // The name of a local variable holding the field's value
+    binding: Ident,
+    ty: &'a Type,
+    // The name the field is written under
+    name: String,
+    attrs: attr::Field,
+}
+
+impl<'a> Field<'a> {
+    #[inline]
+    fn is_written(&self) -> bool {
+        !self.attrs.skip
+    }
+}
+
+struct Variant<'a> {
+    ident: &'a Ident,
+    name: String,
+    named: bool,
+    other: bool,
+    style: Style,
+    fields: Vec<Field<'a>>,
+}
+
+fn fields(fields: &Fields) -> syn::Result<(Style, Vec<Field<'_>>)> {
+    let mut result = Vec::new();
+
+    for (index, field) in fields.iter().enumerate() {
+        let attrs = attr::field(&field.attrs)?;
+        let member = match field.ident {
+            Some(ref ident) => Member::Named(ident.clone()),
+            None => Member::from(index),
+        };
+        let name = match (&attrs.rename, &field.ident) {
+            (Some(rename), _) => rename.clone(),
+            (None, Some(ident)) => ident.to_string(),
+            (None, None) => index.to_string(),
+        };
+
+        if attrs.id.is_some() && field.ident.is_none() {
+            return Err(syn::Error::new_spanned(field, "only named fields can have an id"));
+        }
+
+        result.push(Field {
+            member,
+            binding: Ident::new(&format!("__field{}", index), Span::call_site()),
+            ty: &field.ty,
+            name,
+            attrs,
+        });
+    }
+
+    let style = match *fields {
+        Fields::Named(_) => Style::Struct,
+        Fields::Unnamed(ref unnamed) if unnamed.unnamed.len() == 1 => {
+            if result[0].attrs.skip {
+                return Err(syn::Error::new_spanned(fields, "a newtype's only field can't be \
+                    skipped"));
+            }
+
+            Style::Newtype
+        }
+        Fields::Unnamed(_) => Style::Tuple,
+        Fields::Unit => Style::Unit,
+    };
+
+    Ok((style, result))
+}
+
+fn variants<'a>(input: &DeriveInput, variants: &'a syn::DataEnum)
+    -> syn::Result<Vec<Variant<'a>>>
+{
+    let container = attr::container(&input.attrs, true)?;
+    let mut result = Vec::new();
+
+    for variant in &variants.variants {
+        let attrs = attr::variant(&variant.attrs)?;
+        let (style, fields) = fields(&variant.fields)?;
+
+        if let Some(field) = fields.iter().find(|field| field.attrs.id.is_some()) {
+            return Err(syn::Error::new_spanned(field.ty, "fields of enum variants can't have an \
+                id"));
+        }
+        if attrs.other {
+            if style != Style::Unit && style != Style::Newtype {
+                return Err(syn::Error::new_spanned(variant, "only unit and newtype variants can \
+                    be other"));
+            }
+            if result.iter().any(|other: &Variant| other.other) {
+                return Err(syn::Error::new_spanned(variant, "only one variant can be other"));
+            }
+        }
+
+        result.push(Variant {
+            ident: &variant.ident,
+            name: attrs.rename.unwrap_or_else(|| variant.ident.to_string()),
+            named: container.named || attrs.named,
+            other: attrs.other,
+            style,
+            fields,
+        });
+    }
+
+    Ok(result)
+}
+
+// The name to give serde for a struct, which lists the field IDs if there are any
+fn struct_name(ident: &Ident, fields: &[Field]) -> syn::Result<String> {
+    let written: Vec<&Field> = fields.iter().filter(|field| field.is_written()).collect();
+
+    if written.iter().all(|field| field.attrs.id.is_none()) {
+        return Ok(ident.to_string());
+    }
+
+    let mut pairs = Vec::new();
+
+    for (position, field) in written.iter().enumerate() {
+        let id = match field.attrs.id {
+            Some(id) => id,
+            None => return Err(syn::Error::new_spanned(field.ty, "either every field or none of \
+                them can have an id")),
+        };
+
+        if written[..position].iter().any(|other| other.attrs.id == Some(id)) {
+            return Err(syn::Error::new_spanned(field.ty, format!("id {} is used more than once",
+                id)));
+        }
+
+        pairs.push(format!("{}={}", field.name, id));
+    }
+
+    Ok(format!("{}{}", FIELD_IDS_TOKEN, pairs.join(",")))
+}
+
+// Adds a bound to every type parameter
+fn bound_params(generics: &Generics, bound: TokenStream2) -> Generics {
+    let mut generics = generics.clone();
+    let params: Vec<Ident> = generics.type_params().map(|param| param.ident.clone()).collect();
+    let where_clause = generics.make_where_clause();
+
+    for param in params {
+        where_clause.predicates.push(syn::parse_quote!(#param: #bound));
+    }
+
+    generics
+}
@@ -0,0 +1,188 @@
+use proc_macro2::TokenStream;
+use syn::{self, Data, DeriveInput};
+
+use {attr, bound_params, fields, struct_name, variants, Field, Style, Variant};
+
+
+pub fn expand(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let ident = &input.ident;
+    let generics = bound_params(&input.generics, quote!(_serde::Serialize));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let body = match input.data {
+        Data::Struct(ref data) => {
+            attr::container(&input.attrs, false)?;
+
+            let (style, fields) = fields(&data.fields)?;
+
+            serialize_struct(input, style, &fields)?
+        }
+        Data::Enum(ref data) => {
+            let variants = variants(input, data)?;
+            let arms = variants.iter().enumerate()
+                .map(|(index, variant)| serialize_variant(input, index as u32, variant));
+
+            quote! {
+                match *self {
+                    #(#arms)*
+                }
+            }
+        }
+        Data::Union(_) => {
+            return Err(syn::Error::new_spanned(input, "unions can't be serialized"));
+        }
+    };
+
+    Ok(quote! {
+        #[allow(non_upper_case_globals, unused_attributes, unused_qualifications)]
+        const _: () = {
+            extern crate core as _core;
+            extern crate serde as _serde;
+            extern crate serde_dbor as _serde_dbor;
+
+            impl #impl_generics _serde::Serialize for #ident #ty_generics #where_clause {
+                fn serialize<__S>(&self, __serializer: __S)
+                    -> _core::result::Result<__S::Ok, __S::Error>
+                where
+                    __S: _serde::Serializer
+                {
+                    #body
+                }
+            }
+        };
+    })
+}
+
+// Wraps a reference to a field's value in whatever its attributes ask for
+fn value(field: &Field, value: TokenStream) -> TokenStream {
+    let mut value = value;
+
+    if field.attrs.packed {
+        value = quote!(&_serde_dbor::private::SerializePacked(#value));
+    }
+    if field.attrs.intern {
+        value = quote!(&_serde_dbor::private::Interned(#value));
+    }
+    if field.attrs.shrink_floats {
+        value = quote!(&_serde_dbor::private::ShrinkFloats(#value));
+    }
+
+    value
+}
+
+fn serialize_struct(input: &DeriveInput, style: Style, fields: &[Field])
+    -> syn::Result<TokenStream>
+{
+    let name = struct_name(&input.ident, fields)?;
+    let written: Vec<&Field> = fields.iter().filter(|field| field.is_written()).collect();
+    let len = written.len();
+
+    Ok(match style {
+        Style::Unit => quote! {
+            _serde::Serializer::serialize_unit_struct(__serializer, #name)
+        },
+        Style::Newtype => {
+            let value = value(&fields[0], quote!(&self.0));
+
+            quote! {
+                _serde::Serializer::serialize_newtype_struct(__serializer, #name, #value)
+            }
+        }
+        Style::Tuple => {
+            let values = written.iter().map(|field| {
+                let member = &field.member;
+
+                value(field, quote!(&self.#member))
+            });
+
+            quote! {
+                let mut __state = _serde::Serializer::serialize_tuple_struct(__serializer, #name,
+                    #len)?;
+                #(_serde::ser::SerializeTupleStruct::serialize_field(&mut __state, #values)?;)*
+                _serde::ser::SerializeTupleStruct::end(__state)
+            }
+        }
+        Style::Struct => {
+            let names = written.iter().map(|field| &field.name);
+            let values = written.iter().map(|field| {
+                let member = &field.member;
+
+                value(field, quote!(&self.#member))
+            });
+
+            quote! {
+                let mut __state = _serde::Serializer::serialize_struct(__serializer, #name,
+                    #len)?;
+                #(_serde::ser::SerializeStruct::serialize_field(&mut __state, #names, #values)?;)*
+                _serde::ser::SerializeStruct::end(__state)
+            }
+        }
+    })
+}
+
+fn serialize_variant(input: &DeriveInput, index: u32, variant: &Variant) -> TokenStream {
+    let ident = &input.ident;
+    let variant_ident = variant.ident;
+    let variant_name = &variant.name;
+    let enum_name = if variant.named {
+        quote!(_serde_dbor::private::NAMED_VARIANT_TOKEN)
+    } else {
+        let name = ident.to_string();
+
+        quote!(#name)
+    };
+
+    let written: Vec<&Field> = variant.fields.iter().filter(|field| field.is_written()).collect();
+    let len = written.len();
+    let members = written.iter().map(|field| &field.member);
+    let bindings = written.iter().map(|field| &field.binding);
+    let values = written.iter().map(|field| {
+        let binding = &field.binding;
+
+        value(field, quote!(#binding))
+    });
+
+    match variant.style {
+        Style::Unit => quote! {
+            #ident::#variant_ident => _serde::Serializer::serialize_unit_variant(__serializer,
+                #enum_name, #index, #variant_name),
+        },
+        // An `UnknownVariant` writes itself as the variant it was read from
+        Style::Newtype if variant.other => quote! {
+            #ident::#variant_ident(ref __field0) => {
+                _serde::Serialize::serialize(__field0, __serializer)
+            }
+        },
+        Style::Newtype => {
+            let value = value(&variant.fields[0], quote!(__field0));
+
+            quote! {
+                #ident::#variant_ident(ref __field0) => {
+                    _serde::Serializer::serialize_newtype_variant(__serializer, #enum_name,
+                        #index, #variant_name, #value)
+                }
+            }
+        }
+        Style::Tuple => quote! {
+            #ident::#variant_ident { #(#members: ref #bindings,)* .. } => {
+                let mut __state = _serde::Serializer::serialize_tuple_variant(__serializer,
+                    #enum_name, #index, #variant_name, #len)?;
+                #(_serde::ser::SerializeTupleVariant::serialize_field(&mut __state, #values)?;)*
+                _serde::ser::SerializeTupleVariant::end(__state)
+            }
+        },
+        Style::Struct => {
+            let names = written.iter().map(|field| &field.name);
+
+            quote! {
+                #ident::#variant_ident { #(#members: ref #bindings,)* .. } => {
+                    let mut __state = _serde::Serializer::serialize_struct_variant(__serializer,
+                        #enum_name, #index, #variant_name, #len)?;
+                    #(_serde::ser::SerializeStructVariant::serialize_field(&mut __state, #names,
+                        #values)?;)*
+                    _serde::ser::SerializeStructVariant::end(__state)
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,99 @@
+// CRC-32C (Castagnoli), the same checksum used by iSCSI, ext4, and most storage formats. It's
+//   computed eight bytes at a time with lookup tables, where `TABLES[n]` is the effect of a byte
+//   that still has `n` more bytes after it in the current chunk.
+
+// The number of bytes in a checksum trailer, which holds the checksum as a little endian `u32`
+pub(crate) const CHECKSUM_LEN: usize = 4;
+
+// The Castagnoli polynomial with its bits reversed
+const POLYNOMIAL: u32 = 0x82f63b78;
+
+static TABLES: [[u32; 256]; 8] = make_tables();
+
+const fn make_tables() -> [[u32; 256]; 8] {
+    let mut tables = [[0; 256]; 8];
+    let mut i = 0;
+
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+
+        while bit < 8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ POLYNOMIAL } else { crc >> 1 };
+            bit += 1;
+        }
+
+        tables[0][i] = crc;
+        i += 1;
+    }
+
+    let mut table = 1;
+
+    while table < 8 {
+        i = 0;
+
+        while i < 256 {
+            let previous = tables[table - 1][i];
+
+            tables[table][i] = (previous >> 8) ^ tables[0][(previous & 0xff) as usize];
+            i += 1;
+        }
+
+        table += 1;
+    }
+
+    tables
+}
+
+// A CRC-32C that's being computed over bytes as they come in
+#[derive(Clone, Copy)]
+pub(crate) struct Crc32c {
+    state: u32,
+}
+
+impl Crc32c {
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            state: !0,
+        }
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        let mut crc = self.state;
+        let mut chunks = bytes.chunks_exact(8);
+
+        for chunk in &mut chunks {
+            let low = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]) ^ crc;
+            let high = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]);
+
+            crc = TABLES[7][(low & 0xff) as usize] ^
+                TABLES[6][((low >> 8) & 0xff) as usize] ^
+                TABLES[5][((low >> 16) & 0xff) as usize] ^
+                TABLES[4][(low >> 24) as usize] ^
+                TABLES[3][(high & 0xff) as usize] ^
+                TABLES[2][((high >> 8) & 0xff) as usize] ^
+                TABLES[1][((high >> 16) & 0xff) as usize] ^
+                TABLES[0][(high >> 24) as usize];
+        }
+
+        for &byte in chunks.remainder() {
+            crc = (crc >> 8) ^ TABLES[0][((crc ^ byte as u32) & 0xff) as usize];
+        }
+
+        self.state = crc;
+    }
+
+    #[inline]
+    pub fn finish(&self) -> u32 {
+        !self.state
+    }
+}
+
+// Computes the CRC-32C of a whole slice at once
+#[inline]
+pub(crate) fn crc32c(bytes: &[u8]) -> u32 {
+    let mut crc = Crc32c::new();
+    crc.update(bytes);
+    crc.finish()
+}
@@ -0,0 +1,446 @@
+use core::fmt;
+#[cfg(feature = "std")]
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::ser::{Serialize, Serializer};
+use serde::de::{self, Deserialize, Deserializer, Visitor, SeqAccess, Unexpected};
+
+use tagged::{Tagged, TAGGED_TOKEN, tags};
+
+
+const NANOS_PER_SEC: u32 = 1_000_000_000;
+
+
+/// A point in time, stored compactly as a tagged number of seconds or nanoseconds since the Unix
+/// epoch
+///
+/// Whole seconds are written as `tags::EPOCH_TIME` followed by an int, anything more precise is
+/// written as `tags::EPOCH_TIME_NANOS` followed by an int of nanoseconds (or a seq of seconds and
+/// nanoseconds for times more than ~292 years away from 1970). Numbers in DBOR shrink to fit, so
+/// a timestamp from this century takes 6 bytes without nanoseconds and 10 bytes with them.
+///
+/// `SystemTime` and the datetime types of the `chrono` and `time` crates (behind features of the
+/// same name) can be converted into a `Timestamp`, or used directly with `#[serde(with = "...")]`
+/// through the `system_time`, `chrono` and `time` modules.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Timestamp {
+    secs: i64,
+    nanos: u32,
+}
+
+impl Timestamp {
+    /// The Unix epoch, 1970-01-01 00:00:00 UTC
+    pub const UNIX_EPOCH: Timestamp = Timestamp { secs: 0, nanos: 0 };
+
+    /// Creates a timestamp from seconds since the Unix epoch and a number of nanoseconds after
+    /// that, which can be more than a second
+    pub fn new(secs: i64, nanos: u32) -> Option<Self> {
+        let secs = secs.checked_add((nanos / NANOS_PER_SEC) as i64)?;
+
+        Some(Self {
+            secs,
+            nanos: nanos % NANOS_PER_SEC,
+        })
+    }
+
+    /// Creates a timestamp from nanoseconds since the Unix epoch
+    pub fn from_nanos(nanos: i128) -> Option<Self> {
+        let secs = nanos.div_euclid(NANOS_PER_SEC as i128);
+
+        if secs < i64::MIN as i128 || secs > i64::MAX as i128 {
+            return None;
+        }
+
+        Some(Self {
+            secs: secs as i64,
+            nanos: nanos.rem_euclid(NANOS_PER_SEC as i128) as u32,
+        })
+    }
+
+    /// Whole seconds since the Unix epoch, rounded down
+    #[inline]
+    pub fn secs(&self) -> i64 {
+        self.secs
+    }
+
+    /// Nanoseconds after `secs()`, always less than a second
+    #[inline]
+    pub fn subsec_nanos(&self) -> u32 {
+        self.nanos
+    }
+
+    /// Nanoseconds since the Unix epoch
+    #[inline]
+    pub fn as_nanos(&self) -> i128 {
+        self.secs as i128 * NANOS_PER_SEC as i128 + self.nanos as i128
+    }
+
+    /// Converts the timestamp into a `SystemTime`, if the platform can represent it
+    #[cfg(feature = "std")]
+    pub fn to_system_time(&self) -> Option<SystemTime> {
+        if self.secs >= 0 {
+            UNIX_EPOCH.checked_add(Duration::new(self.secs as u64, self.nanos))
+        } else {
+            UNIX_EPOCH.checked_sub(Duration::new(self.secs.unsigned_abs(), 0))?
+                .checked_add(Duration::new(0, self.nanos))
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<SystemTime> for Timestamp {
+    fn from(time: SystemTime) -> Self {
+        // Every platform stores a `SystemTime` in 64 bits of seconds or less, so these can't
+        //   overflow
+        match time.duration_since(UNIX_EPOCH) {
+            Ok(after) => Timestamp {
+                secs: after.as_secs() as i64,
+                nanos: after.subsec_nanos(),
+            },
+            Err(before) => {
+                let before = before.duration();
+
+                Timestamp::from_nanos(-(before.as_nanos() as i128))
+                    .unwrap_or(Timestamp { secs: i64::MIN, nanos: 0 })
+            },
+        }
+    }
+}
+
+impl Serialize for Timestamp {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.nanos == 0 {
+            Tagged::new(tags::EPOCH_TIME, self.secs).serialize(serializer)
+        } else {
+            let nanos = self.as_nanos();
+
+            if nanos >= i64::MIN as i128 && nanos <= i64::MAX as i128 {
+                Tagged::new(tags::EPOCH_TIME_NANOS, nanos as i64).serialize(serializer)
+            } else {
+                Tagged::new(tags::EPOCH_TIME_NANOS, (self.secs, self.nanos)).serialize(serializer)
+            }
+        }
+    }
+}
+
+struct TimestampVisitor;
+
+impl<'de> Visitor<'de> for TimestampVisitor {
+    type Value = Timestamp;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a timestamp")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>
+    {
+        let tag: u64 = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let amount: Amount = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(1, &self))?;
+
+        let timestamp = match (tag, amount) {
+            (tags::EPOCH_TIME, Amount::Whole(secs)) => {
+                Timestamp::from_nanos(secs.saturating_mul(NANOS_PER_SEC as i128))
+            },
+            (tags::EPOCH_TIME, Amount::Float(secs)) => {
+                if secs.is_finite() {
+                    // Rounds half away from zero like `f64::round`, which needs `std`
+                    let nanos = secs * NANOS_PER_SEC as f64;
+
+                    Timestamp::from_nanos(if nanos < 0.0 { nanos - 0.5 } else { nanos + 0.5 } as i128)
+                } else {
+                    None
+                }
+            },
+            (tags::EPOCH_TIME_NANOS, Amount::Whole(nanos)) => Timestamp::from_nanos(nanos),
+            (tags::EPOCH_TIME_NANOS, Amount::Split(secs, nanos)) => {
+                if secs < i64::MIN as i128 || secs > i64::MAX as i128 || nanos >= NANOS_PER_SEC {
+                    None
+                } else {
+                    Timestamp::new(secs as i64, nanos)
+                }
+            },
+            (tags::EPOCH_TIME, _) | (tags::EPOCH_TIME_NANOS, _) => {
+                return Err(de::Error::invalid_type(amount.unexpected(), &self));
+            },
+            _ => return Err(de::Error::invalid_value(Unexpected::Unsigned(tag), &self)),
+        };
+
+        timestamp.ok_or_else(|| de::Error::custom("timestamp out of range"))
+    }
+}
+
+impl<'de> Deserialize<'de> for Timestamp {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>
+    {
+        deserializer.deserialize_tuple_struct(TAGGED_TOKEN, 2, TimestampVisitor)
+    }
+}
+
+
+// The value behind a time tag, which can be a whole number, a float (only for compatibility with
+//   CBOR's epoch time), or a seq of seconds and nanoseconds
+#[derive(Clone, Copy)]
+enum Amount {
+    Whole(i128),
+    Float(f64),
+    Split(i128, u32),
+}
+
+impl Amount {
+    fn unexpected(&self) -> Unexpected<'static> {
+        match *self {
+            Amount::Whole(_) => Unexpected::Other("integer"),
+            Amount::Float(value) => Unexpected::Float(value),
+            Amount::Split(..) => Unexpected::Seq,
+        }
+    }
+}
+
+struct AmountVisitor;
+
+impl<'de> Visitor<'de> for AmountVisitor {
+    type Value = Amount;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a number or a seq of seconds and nanoseconds")
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Amount, E> {
+        Ok(Amount::Whole(value as i128))
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Amount, E> {
+        Ok(Amount::Whole(value as i128))
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<Amount, E> {
+        Ok(Amount::Float(value))
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>
+    {
+        let secs = match seq.next_element()? {
+            Some(Amount::Whole(secs)) => secs,
+            Some(other) => return Err(de::Error::invalid_type(other.unexpected(), &self)),
+            None => return Err(de::Error::invalid_length(0, &self)),
+        };
+        let nanos = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(1, &self))?;
+
+        Ok(Amount::Split(secs, nanos))
+    }
+}
+
+impl<'de> Deserialize<'de> for Amount {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>
+    {
+        deserializer.deserialize_any(AmountVisitor)
+    }
+}
+
+
+/// Compact encoding for `SystemTime`, for use with `#[serde(with = "serde_dbor::system_time")]`
+///
+/// The time is stored the same way as a `Timestamp`.
+#[cfg(feature = "std")]
+pub mod system_time {
+    use std::time::SystemTime;
+
+    use serde::ser::{Serialize, Serializer};
+    use serde::de::{self, Deserialize, Deserializer};
+
+    use super::Timestamp;
+
+    /// Serializes a `SystemTime` as a `Timestamp`
+    pub fn serialize<S: Serializer>(time: &SystemTime, serializer: S) -> Result<S::Ok, S::Error> {
+        Timestamp::from(*time).serialize(serializer)
+    }
+
+    /// Deserializes a `SystemTime` from a `Timestamp`
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<SystemTime, D::Error>
+    where
+        D: Deserializer<'de>
+    {
+        Timestamp::deserialize(deserializer)?.to_system_time()
+            .ok_or_else(|| de::Error::custom("timestamp out of range for SystemTime"))
+    }
+}
+
+/// Compact encoding for `Duration`, for use with `#[serde(with = "serde_dbor::duration")]`
+///
+/// Whole seconds are written as `tags::DURATION` followed by a uint, anything more precise is
+/// written as `tags::DURATION_NANOS` followed by a uint of nanoseconds (or a seq of seconds and
+/// nanoseconds for durations longer than ~584 years).
+pub mod duration {
+    use core::fmt;
+    use core::time::Duration;
+
+    use serde::ser::{Serialize, Serializer};
+    use serde::de::{self, Deserializer, Visitor, SeqAccess, Unexpected};
+
+    use tagged::{Tagged, TAGGED_TOKEN, tags};
+    use super::{Amount, NANOS_PER_SEC};
+
+    /// Serializes a `Duration` as a tagged number of seconds or nanoseconds
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        let secs = duration.as_secs();
+        let nanos = duration.subsec_nanos();
+
+        if nanos == 0 {
+            Tagged::new(tags::DURATION, secs).serialize(serializer)
+        } else if duration.as_nanos() <= u64::MAX as u128 {
+            Tagged::new(tags::DURATION_NANOS, duration.as_nanos() as u64).serialize(serializer)
+        } else {
+            Tagged::new(tags::DURATION_NANOS, (secs, nanos)).serialize(serializer)
+        }
+    }
+
+    /// Deserializes a `Duration` from a tagged number of seconds or nanoseconds
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+    where
+        D: Deserializer<'de>
+    {
+        deserializer.deserialize_tuple_struct(TAGGED_TOKEN, 2, DurationVisitor)
+    }
+
+    struct DurationVisitor;
+
+    impl<'de> Visitor<'de> for DurationVisitor {
+        type Value = Duration;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a duration")
+        }
+
+        fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+        where
+            A: SeqAccess<'de>
+        {
+            let tag: u64 = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(0, &self))?;
+            let amount: Amount = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(1, &self))?;
+
+            let duration = match (tag, amount) {
+                (tags::DURATION, Amount::Whole(secs)) if secs >= 0 && secs <= u64::MAX as i128 => {
+                    Some(Duration::from_secs(secs as u64))
+                },
+                (tags::DURATION, Amount::Float(secs)) => Duration::try_from_secs_f64(secs).ok(),
+                (tags::DURATION_NANOS, Amount::Whole(nanos)) if nanos >= 0 && nanos <= u64::MAX as i128 => {
+                    Some(Duration::from_nanos(nanos as u64))
+                },
+                (tags::DURATION_NANOS, Amount::Split(secs, nanos)) => {
+                    if secs >= 0 && secs <= u64::MAX as i128 && nanos < NANOS_PER_SEC {
+                        Some(Duration::new(secs as u64, nanos))
+                    } else {
+                        None
+                    }
+                },
+                (tags::DURATION, Amount::Whole(_)) | (tags::DURATION_NANOS, Amount::Whole(_)) => None,
+                (tags::DURATION, _) | (tags::DURATION_NANOS, _) => {
+                    return Err(de::Error::invalid_type(amount.unexpected(), &self));
+                },
+                _ => return Err(de::Error::invalid_value(Unexpected::Unsigned(tag), &self)),
+            };
+
+            duration.ok_or_else(|| de::Error::custom("duration out of range"))
+        }
+    }
+}
+
+/// Compact encoding for `chrono::DateTime<Utc>`, for use with
+/// `#[serde(with = "serde_dbor::chrono")]`
+///
+/// The time is stored the same way as a `Timestamp`. Leap seconds are folded into the following
+/// second.
+#[cfg(feature = "chrono")]
+pub mod chrono {
+    use chrono_crate::{DateTime, Utc};
+
+    use serde::ser::{Serialize, Serializer};
+    use serde::de::{self, Deserialize, Deserializer};
+
+    use super::Timestamp;
+
+    impl From<DateTime<Utc>> for Timestamp {
+        fn from(time: DateTime<Utc>) -> Self {
+            // `DateTime` represents a leap second as more than a second of nanoseconds
+            Timestamp::from_nanos(
+                time.timestamp() as i128 * 1_000_000_000 + time.timestamp_subsec_nanos() as i128
+            ).unwrap_or_default()
+        }
+    }
+
+    impl Timestamp {
+        /// Converts the timestamp into a `chrono::DateTime<Utc>`, if it's within chrono's range
+        pub fn to_chrono(&self) -> Option<DateTime<Utc>> {
+            DateTime::from_timestamp(self.secs, self.nanos)
+        }
+    }
+
+    /// Serializes a `DateTime<Utc>` as a `Timestamp`
+    pub fn serialize<S: Serializer>(time: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error> {
+        Timestamp::from(*time).serialize(serializer)
+    }
+
+    /// Deserializes a `DateTime<Utc>` from a `Timestamp`
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
+    where
+        D: Deserializer<'de>
+    {
+        Timestamp::deserialize(deserializer)?.to_chrono()
+            .ok_or_else(|| de::Error::custom("timestamp out of range for chrono"))
+    }
+}
+
+/// Compact encoding for `time::OffsetDateTime`, for use with
+/// `#[serde(with = "serde_dbor::time")]`
+///
+/// The time is stored the same way as a `Timestamp`. The UTC offset isn't stored, so times always
+/// come back in UTC.
+#[cfg(feature = "time")]
+pub mod time {
+    use time_crate::OffsetDateTime;
+
+    use serde::ser::{Serialize, Serializer};
+    use serde::de::{self, Deserialize, Deserializer};
+
+    use super::Timestamp;
+
+    impl From<OffsetDateTime> for Timestamp {
+        fn from(time: OffsetDateTime) -> Self {
+            // Every `OffsetDateTime` fits, since they're limited to a few thousand years
+            Timestamp::from_nanos(time.unix_timestamp_nanos()).unwrap_or_default()
+        }
+    }
+
+    impl Timestamp {
+        /// Converts the timestamp into a `time::OffsetDateTime` in UTC, if it's within the `time`
+        /// crate's range
+        pub fn to_offset_date_time(&self) -> Option<OffsetDateTime> {
+            OffsetDateTime::from_unix_timestamp_nanos(self.as_nanos()).ok()
+        }
+    }
+
+    /// Serializes an `OffsetDateTime` as a `Timestamp`
+    pub fn serialize<S>(time: &OffsetDateTime, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer
+    {
+        Timestamp::from(*time).serialize(serializer)
+    }
+
+    /// Deserializes an `OffsetDateTime` (in UTC) from a `Timestamp`
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<OffsetDateTime, D::Error>
+    where
+        D: Deserializer<'de>
+    {
+        Timestamp::deserialize(deserializer)?.to_offset_date_time()
+            .ok_or_else(|| de::Error::custom("timestamp out of range for the time crate"))
+    }
+}
@@ -1,18 +1,43 @@
-use std::cmp;
-use std::marker::PhantomData;
-use std::io::Read as IoRead;
-use std;
+use core::any::Any;
+use core::cmp;
+use core::convert::TryFrom;
+use core::marker::PhantomData;
+use core::ptr;
+use core;
+use alloc::rc::Rc;
+use alloc::string::String;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::collections::HashSet as Set;
+#[cfg(not(feature = "std"))]
+use alloc::collections::BTreeSet as Set;
+#[cfg(feature = "std")]
+use std::io::{Read as IoRead, BufRead};
 
 use serde::de::{Deserializer as SerdeDeserializer, DeserializeSeed, Visitor, Deserialize,
-    SeqAccess, MapAccess, EnumAccess, VariantAccess, IntoDeserializer};
+    SeqAccess, MapAccess, EnumAccess, VariantAccess, IntoDeserializer, IgnoredAny, Unexpected};
+use serde::de::Error as SerdeError;
 
 mod read;
+pub(crate) mod validate;
 
+use checksum::CHECKSUM_LEN;
 use error::*;
+use field_ids::{explicit_ids, name_of};
+use fingerprint::fingerprint;
+use header::{self, HEADER_LEN, FINGERPRINT_LEN};
+use raw::{RAW_TOKEN, VariantId};
+use shared::{self, SHARED_TOKEN};
+use tagged::TAGGED_TOKEN;
+use ser::{f32_to_f16, SerializerOptions};
 use self::read::*;
 use super::WRONG_ENDIANNESS;
 
 
+// Streams can't tell how much input is left, so size hints for them are capped at this instead
+const MAX_STREAM_SIZE_HINT: usize = 1 << 16;
+
+
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 #[repr(u8)]
 #[allow(dead_code)] // Most types are never constructed directly, instead through transmutation
@@ -39,7 +64,7 @@ pub enum Type {
     /// Represents a map type
     Map,
 
-    /// Reserved for later use
+    /// Represents extensions, like anchors and references for shared values
     Reserved,
 
     /// Used only in error messages, represents any type
@@ -60,68 +85,531 @@ fn val(byte: u8) -> u8 {
     byte & 0b00011111
 }
 
+// Widen a half precision float into a single precision float, which is always lossless
+fn f16_to_f32(half: u16) -> f32 {
+    let sign = ((half >> 15) as u32) << 31;
+    let exponent = ((half >> 10) & 0x1f) as u32;
+    let mantissa = (half & 0x3ff) as u32;
+
+    match exponent {
+        // Infinity or NaN
+        0x1f => f32::from_bits(sign | 0x7f800000 | (mantissa << 13)),
+        // Zero or subnormal, which is `mantissa * 2^-24`
+        0 => {
+            let magnitude = mantissa as f32 * f32::from_bits((127 - 24) << 23);
+
+            if sign == 0 { magnitude } else { -magnitude }
+        }
+        _ => f32::from_bits(sign | ((exponent + 127 - 15) << 23) | (mantissa << 13)),
+    }
+}
+
+// Whether an unsigned number written with the given parameter couldn't have been written any
+//   shorter
+#[inline]
+fn is_shortest_uint(value: u8, n: u64) -> bool {
+    match value {
+        24 => n >= 24,
+        25 => n > 0xff,
+        26 => n > 0xffff,
+        27 => n > 0xffffffff,
+        _ => true,
+    }
+}
+
+// Whether a signed number written with the given parameter couldn't have been written any shorter
+#[inline]
+fn is_shortest_int(value: u8, n: i64) -> bool {
+    match value {
+        24 => !(-8..=15).contains(&n),
+        25 => !(-0x80..=0x7f).contains(&n),
+        26 => !(-0x8000..=0x7fff).contains(&n),
+        27 => !(-0x80000000..=0x7fffffff).contains(&n),
+        _ => true,
+    }
+}
+
+// Whether an extended parameter couldn't have been written any shorter
+#[inline]
+fn is_shortest_extended(value: u8, n: u64) -> bool {
+    match value {
+        248 => n >= 248,
+        _ => is_shortest_uint(value - 224, n),
+    }
+}
+
+// Writes a plain byte string in its shortest form, like the serializer would
+fn write_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    let instruction = (Type::Bytes as u8) << 5;
+    let len = bytes.len() as u64;
+
+    match len {
+        0..=23 => out.push(instruction | len as u8),
+        24..=0xff => out.extend_from_slice(&[instruction | 24, len as u8]),
+        0x100..=0xffff => {
+            out.push(instruction | 25);
+            out.extend_from_slice(&(len as u16).to_le_bytes());
+        }
+        0x10000..=0xffffffff => {
+            out.push(instruction | 26);
+            out.extend_from_slice(&(len as u32).to_le_bytes());
+        }
+        _ => {
+            out.push(instruction | 27);
+            out.extend_from_slice(&len.to_le_bytes());
+        }
+    }
+
+    out.extend_from_slice(bytes);
+}
+
 
 /// Deserialize an instance of type T from an IO stream of DBOR
+#[cfg(feature = "std")]
 pub fn from_reader<'de, R: IoRead + 'de, T>(r: R) -> Result<T>
 where
     T: Deserialize<'de>
 {
-    let mut deserializer = Deserializer::from_reader(r);
+    from_reader_with_options(r, DeserializerOptions::default())
+}
+
+/// Deserialize an instance of type T from an IO stream of DBOR using specific options
+#[cfg(feature = "std")]
+pub fn from_reader_with_options<'de, R: IoRead + 'de, T>(r: R, options: DeserializerOptions)
+    -> Result<T>
+where
+    T: Deserialize<'de>
+{
+    let mut deserializer = Deserializer::from_reader_with_options(r, options);
     let t = T::deserialize(&mut deserializer)?;
 
-    if deserializer.input.finished() {
-        Ok(t)
-    } else {
-        Err(Error::TrailingBytes)
-    }
+    deserializer.end()?;
+    Ok(t)
+}
+
+/// Deserialize an instance of type T from an IO stream of DBOR that starts with a header, like the
+/// ones written by `to_writer_with_header`
+///
+/// Fails with `Error::InvalidMagic` if the stream doesn't start with a header. Data that the
+/// header says is canonical is checked like with `DeserializerOptions::canonical()`, and if the
+/// header has a fingerprint, it has to match the fingerprint of T.
+#[cfg(feature = "std")]
+pub fn from_reader_with_header<'de, R: IoRead + 'de, T>(r: R) -> Result<T>
+where
+    T: Deserialize<'de>
+{
+    from_reader_with_header_and_options(r, DeserializerOptions::default())
+}
+
+/// Deserialize an instance of type T from an IO stream of DBOR that starts with a header using
+/// specific options
+///
+/// What the header says is added on top of the options, so canonical data is always checked and
+/// a checksum in the options is required even if the header doesn't have one.
+#[cfg(feature = "std")]
+pub fn from_reader_with_header_and_options<'de, R: IoRead + 'de, T>(r: R,
+    options: DeserializerOptions) -> Result<T>
+where
+    T: Deserialize<'de>
+{
+    let mut deserializer = Deserializer::from_reader_with_options(r, options);
+    check_fingerprint::<T>(deserializer.read_header()?)?;
+    let t = T::deserialize(&mut deserializer)?;
+
+    deserializer.end()?;
+    Ok(t)
+}
+
+/// Deserialize an instance of type T from an IO stream of DBOR, buffering reads in chunks of
+/// `capacity` bytes instead of the default 1024
+///
+/// Capacities below 16 bytes are rounded up to 16.
+#[cfg(feature = "std")]
+pub fn from_reader_with_capacity<'de, R: IoRead + 'de, T>(r: R, capacity: usize) -> Result<T>
+where
+    T: Deserialize<'de>
+{
+    from_reader_with_capacity_and_options(r, capacity, DeserializerOptions::default())
+}
+
+/// Deserialize an instance of type T from an IO stream of DBOR using specific options, buffering
+/// reads in chunks of `capacity` bytes
+#[cfg(feature = "std")]
+pub fn from_reader_with_capacity_and_options<'de, R: IoRead + 'de, T>(r: R, capacity: usize,
+    options: DeserializerOptions) -> Result<T>
+where
+    T: Deserialize<'de>
+{
+    let mut deserializer = Deserializer::from_reader_with_capacity_and_options(r, capacity,
+        options);
+    let t = T::deserialize(&mut deserializer)?;
+
+    deserializer.end()?;
+    Ok(t)
+}
+
+/// Deserialize an instance of type T from a buffered IO stream of DBOR, reading straight out of
+/// its buffer instead of buffering it again
+#[cfg(feature = "std")]
+pub fn from_bufread<'de, R: BufRead + 'de, T>(r: R) -> Result<T>
+where
+    T: Deserialize<'de>
+{
+    from_bufread_with_options(r, DeserializerOptions::default())
+}
+
+/// Deserialize an instance of type T from a buffered IO stream of DBOR using specific options
+#[cfg(feature = "std")]
+pub fn from_bufread_with_options<'de, R: BufRead + 'de, T>(r: R, options: DeserializerOptions)
+    -> Result<T>
+where
+    T: Deserialize<'de>
+{
+    let mut deserializer = Deserializer::from_bufread_with_options(r, options);
+    let t = T::deserialize(&mut deserializer)?;
+
+    deserializer.end()?;
+    Ok(t)
 }
 
 /// Deserialize an instance of type T from bytes of DBOR
-pub fn from_slice<'de, S: AsRef<[u8]> + 'de, T>(bytes: &'de S) -> Result<T>
+pub fn from_slice<'de, S: AsRef<[u8]> + ?Sized + 'de, T>(bytes: &'de S) -> Result<T>
+where
+    T: Deserialize<'de>
+{
+    from_slice_with_options(bytes, DeserializerOptions::default())
+}
+
+/// Deserialize an instance of type T from bytes of DBOR using specific options
+pub fn from_slice_with_options<'de, S, T>(bytes: &'de S, options: DeserializerOptions) -> Result<T>
+where
+    S: AsRef<[u8]> + ?Sized + 'de,
+    T: Deserialize<'de>,
+{
+    let mut deserializer = Deserializer::from_slice_with_options(bytes, options);
+    let t = T::deserialize(&mut deserializer)?;
+
+    deserializer.end()?;
+    Ok(t)
+}
+
+/// Deserialize an instance of type T from bytes of DBOR that start with a header, like the ones
+/// written by `to_vec_with_header`
+pub fn from_slice_with_header<'de, S: AsRef<[u8]> + ?Sized + 'de, T>(bytes: &'de S) -> Result<T>
 where
     T: Deserialize<'de>
 {
-    let mut deserializer = Deserializer::from_slice(bytes);
+    from_slice_with_header_and_options(bytes, DeserializerOptions::default())
+}
+
+/// Deserialize an instance of type T from bytes of DBOR that start with a header using specific
+/// options, which the header adds to like with `from_reader_with_header_and_options`
+pub fn from_slice_with_header_and_options<'de, S, T>(bytes: &'de S, options: DeserializerOptions)
+    -> Result<T>
+where
+    S: AsRef<[u8]> + ?Sized + 'de,
+    T: Deserialize<'de>,
+{
+    let mut deserializer = Deserializer::from_slice_with_options(bytes, options);
+    check_fingerprint::<T>(deserializer.read_header()?)?;
     let t = T::deserialize(&mut deserializer)?;
 
-    if deserializer.input.finished() {
-        Ok(t)
-    } else {
-        Err(Error::TrailingBytes)
+    deserializer.end()?;
+    Ok(t)
+}
+
+// Makes sure that data written with a fingerprint is being read as the same type
+fn check_fingerprint<'de, T: Deserialize<'de>>(written_with: SerializerOptions) -> Result<()> {
+    match written_with.fingerprint {
+        Some(found) => {
+            let expected = fingerprint::<T>()?;
+
+            if found == expected {
+                Ok(())
+            } else {
+                Err(Error::FingerprintMismatch(found, expected))
+            }
+        }
+        None => Ok(()),
     }
 }
 
+/// Deserialize bytes of DBOR into an existing instance of type T, reusing its allocations
+///
+/// Collections and strings are refilled instead of being replaced, so decoding values of the same
+/// shape into the same place over and over doesn't allocate (serde drops the extra elements when a
+/// `Vec` gets shorter, so those have to be allocated again later). Structs only support this when
+/// their `Deserialize` implementation does, like with `serde_derive`'s `deserialize_in_place`
+/// feature. If deserialization fails, `place` is left in a valid but unspecified state.
+pub fn from_slice_in_place<'de, S, T>(bytes: &'de S, place: &mut T) -> Result<()>
+where
+    S: AsRef<[u8]> + ?Sized + 'de,
+    T: Deserialize<'de>,
+{
+    from_slice_in_place_with_options(bytes, place, DeserializerOptions::default())
+}
+
+/// Deserialize bytes of DBOR into an existing instance of type T using specific options
+pub fn from_slice_in_place_with_options<'de, S, T>(bytes: &'de S, place: &mut T,
+    options: DeserializerOptions) -> Result<()>
+where
+    S: AsRef<[u8]> + ?Sized + 'de,
+    T: Deserialize<'de>,
+{
+    let mut deserializer = Deserializer::from_slice_with_options(bytes, options);
+    T::deserialize_in_place(&mut deserializer, place)?;
+    deserializer.end()
+}
+
+
+/// Options that change what DBOR the deserializer accepts
+///
+/// Every option is off by default, which accepts anything that any `Serializer` could have
+/// written.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct DeserializerOptions {
+    /// Reject any number, length, or variant id that isn't written in its shortest form, and maps
+    /// that have the same key more than once
+    ///
+    /// Keys are compared by their encoded bytes, so two copies of an interned string don't count
+    /// as the same key. Use `canonical` when that matters.
+    pub strict: bool,
+
+    /// Reject anything that `SerializerOptions::canonical()` would have written differently,
+    /// which also turns on `strict`
+    ///
+    /// On top of the strict checks, map keys have to be sorted by their encoded bytes, floats have
+    /// to be written at their smallest lossless size (with NaN only as the `f16` quiet NaN), and
+    /// aligned bytes, interned bytes, and shared values aren't allowed at all.
+    pub canonical: bool,
+
+    /// The longest byte string, seq, or map (in bytes, items, or entries) to accept, with anything
+    /// longer returning an `Error::LengthLimitExceeded` before any of it is read
+    ///
+    /// Lengths are always checked against what's left of the input when reading from a slice, so
+    /// this mostly matters for streams, where a bogus length would otherwise only be noticed once
+    /// the stream runs out.
+    pub max_len: Option<usize>,
+
+    /// How many seqs, maps, variants, and tags can be nested inside each other, with anything
+    /// deeper returning an `Error::DepthLimitExceeded`
+    ///
+    /// Values that are skipped or validated are read by recursing into them, so without a limit,
+    /// data that's nested deeply enough (which takes a single byte per level) can overflow the
+    /// stack.
+    pub max_depth: Option<usize>,
+
+    /// Expect a 4 byte CRC-32C trailer after the data, like the one written by
+    /// `SerializerOptions::checksum`, and return an `Error::ChecksumMismatch` if it doesn't match
+    ///
+    /// The trailer is checked once the whole value has been read, so corrupted data can also fail
+    /// earlier with whatever other error it runs into.
+    pub checksum: bool,
+}
+
+impl DeserializerOptions {
+    /// Options for strict decoding, which only accepts the shortest form of every value
+    #[inline]
+    pub fn strict() -> Self {
+        Self {
+            strict: true,
+            ..Self::default()
+        }
+    }
+
+    /// Options for canonical decoding, which only accepts data written by
+    /// `SerializerOptions::canonical()`
+    #[inline]
+    pub fn canonical() -> Self {
+        Self {
+            strict: true,
+            canonical: true,
+            ..Self::default()
+        }
+    }
+}
 
 /// A structure that deserializes DBOR into Rust values
 pub struct Deserializer<'de, R: Read<'de> + 'de> {
     input: R,
+    options: DeserializerOptions,
+    interned: Vec<Interned<'de>>,
+    anchors: Vec<Anchor>,
+    // Copies of the bytes being read, innermost last
+    recordings: Vec<Vec<u8>>,
+    // The first reference in the innermost recording that couldn't be written out in full
+    unresolved: Option<usize>,
+    // How many values the one being read is nested inside of
+    depth: usize,
     phantom: PhantomData<&'de ()>,
 }
 
+// A value marked as an anchor, which later references resolve to
+#[derive(Default)]
+struct Anchor {
+    // The pointer that `Shared` created for it
+    pointer: Option<Rc<dyn Any>>,
+    // A copy of its bytes that doesn't refer to anything outside of itself, kept when it wasn't
+    //   deserialized as a `Shared` so that references can deserialize it again
+    bytes: Option<Rc<[u8]>>,
+}
+
+// A string that can be referred back to, borrowed from the input if possible
+enum Interned<'de> {
+    Borrowed(&'de [u8]),
+    Owned(Vec<u8>),
+}
+
 impl<'de> Deserializer<'de, SliceReader<'de>> {
     /// Creates a DBOR deserializer from something that converts into a `&[u8]`
-    pub fn from_slice<S: AsRef<[u8]> + 'de>(bytes: &'de S) -> Self {
-        Self {
-            input: SliceReader::new(bytes),
-            phantom: PhantomData
-        }
+    pub fn from_slice<S: AsRef<[u8]> + ?Sized + 'de>(bytes: &'de S) -> Self {
+        Self::from_slice_with_options(bytes, DeserializerOptions::default())
+    }
+
+    /// Creates a DBOR deserializer from something that converts into a `&[u8]`, using specific
+    /// options
+    pub fn from_slice_with_options<S>(bytes: &'de S, options: DeserializerOptions) -> Self
+    where
+        S: AsRef<[u8]> + ?Sized + 'de
+    {
+        Self::new(SliceReader::new(bytes), options)
     }
 }
 
+#[cfg(feature = "std")]
 impl<'de, R: IoRead> Deserializer<'de, BufferedReader<R>> {
     /// Creates a DBOR deserializer from an `io::Read`
     pub fn from_reader(reader: R) -> Self {
+        Self::from_reader_with_options(reader, DeserializerOptions::default())
+    }
+
+    /// Creates a DBOR deserializer from an `io::Read`, using specific options
+    pub fn from_reader_with_options(reader: R, options: DeserializerOptions) -> Self {
+        Self::from_reader_with_capacity_and_options(reader, DEFAULT_BUF_LEN, options)
+    }
+
+    /// Creates a DBOR deserializer from an `io::Read`, buffering reads in chunks of `capacity`
+    /// bytes
+    pub fn from_reader_with_capacity(reader: R, capacity: usize) -> Self {
+        Self::from_reader_with_capacity_and_options(reader, capacity,
+            DeserializerOptions::default())
+    }
+
+    /// Creates a DBOR deserializer from an `io::Read`, using specific options and buffering reads
+    /// in chunks of `capacity` bytes
+    pub fn from_reader_with_capacity_and_options(reader: R, capacity: usize,
+        options: DeserializerOptions) -> Self
+    {
+        Self::new(BufferedReader::with_capacity(reader, capacity), options)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'de, R: BufRead> Deserializer<'de, BufReadReader<R>> {
+    /// Creates a DBOR deserializer from an `io::BufRead`
+    ///
+    /// Once the deserializer is dropped, the stream is left right after the last byte that was
+    /// read, so several values can be read one after another through a `&mut` reference.
+    pub fn from_bufread(reader: R) -> Self {
+        Self::from_bufread_with_options(reader, DeserializerOptions::default())
+    }
+
+    /// Creates a DBOR deserializer from an `io::BufRead`, using specific options
+    pub fn from_bufread_with_options(reader: R, options: DeserializerOptions) -> Self {
+        Self::new(BufReadReader::new(reader), options)
+    }
+}
+
+impl<'de, R: Read<'de>> Deserializer<'de, R> {
+    #[inline]
+    fn new(mut input: R, mut options: DeserializerOptions) -> Self {
+        if options.canonical {
+            options.strict = true;
+        }
+
+        if options.checksum {
+            input.start_checksum();
+        }
+
         Self {
-            input: BufferedReader::new(reader),
+            input,
+            options,
+            interned: Vec::new(),
+            anchors: Vec::new(),
+            recordings: Vec::new(),
+            unresolved: None,
+            depth: 0,
             phantom: PhantomData
         }
     }
-}
 
-impl<'de, R: Read<'de>> Deserializer<'de, R> {
+    /// Reads a header like the ones written by `to_writer_with_header`, and returns the options
+    /// that the data after it was written with
+    ///
+    /// This has to be called before anything else is deserialized. If the header says the data is
+    /// canonical, the deserializer switches to canonical checks for everything after it, and if it
+    /// says there's a checksum, `end` checks it.
+    ///
+    /// The fingerprint from the header (if any) is returned with the options, but it isn't checked,
+    /// since the deserializer doesn't know what type it's about to be used for.
+    pub fn read_header(&mut self) -> Result<SerializerOptions> {
+        let mut header = [0; HEADER_LEN];
+
+        header.copy_from_slice(self.must_read(HEADER_LEN, false)?.as_slice());
+
+        let mut written_with = header::decode(&header)?;
+
+        if header::has_fingerprint(&header) {
+            written_with.fingerprint = Some(unsafe {
+                ptr::read_unaligned(self.must_read(FINGERPRINT_LEN, true)?.as_slice().as_ptr()
+                    as *const u64)
+            });
+        }
+
+        if written_with.canonical {
+            self.options.strict = true;
+            self.options.canonical = true;
+        }
+
+        // Checksums never cover the header itself
+        if written_with.checksum || self.options.checksum {
+            self.options.checksum = true;
+            self.input.start_checksum();
+        }
+
+        Ok(written_with)
+    }
+
+    /// Finishes reading the input after a value has been deserialized, checking the checksum
+    /// trailer if there is one and making sure that there's nothing left after it
+    pub fn end(&mut self) -> Result<()> {
+        if let Some(checksum) = self.input.finish_checksum() {
+            let expected = unsafe {
+                ptr::read_unaligned(self.must_read(CHECKSUM_LEN, true)?.as_slice().as_ptr()
+                    as *const u32)
+            };
+
+            if checksum != expected {
+                return Err(Error::ChecksumMismatch);
+            }
+        }
+
+        if self.input.finished() {
+            Ok(())
+        } else {
+            Err(Error::TrailingBytes)
+        }
+    }
+
     #[inline]
     fn next(&mut self) -> Result<u8> {
-        self.input.next().ok_or(Error::Eof)
+        let byte = self.input.next().ok_or(Error::Eof)?;
+
+        if let Some(recording) = self.recordings.last_mut() {
+            recording.push(byte);
+        }
+
+        Ok(byte)
     }
 
     #[inline]
@@ -131,12 +619,28 @@ impl<'de, R: Read<'de>> Deserializer<'de, R> {
 
     #[inline]
     fn read<'a>(&'a mut self, bytes: usize, should_flip: bool) -> Result<Borrowed<'a, 'de>> {
-        self.input.read(bytes, *WRONG_ENDIANNESS && should_flip).ok_or(Error::Eof)
+        // Readers have nothing to give back once they're empty, even when nothing is asked for
+        if bytes == 0 {
+            return Ok(Borrowed::Permanent(&[]));
+        }
+
+        let flipped = WRONG_ENDIANNESS && should_flip;
+        let borrowed = self.input.read(bytes, flipped).ok_or(Error::Eof)?;
+
+        if let Some(recording) = self.recordings.last_mut() {
+            if flipped {
+                recording.extend(borrowed.as_slice().iter().rev());
+            } else {
+                recording.extend_from_slice(borrowed.as_slice());
+            }
+        }
+
+        Ok(borrowed)
     }
 
     #[inline]
     fn must_read<'a>(&'a mut self, bytes: usize, should_flip: bool) -> Result<Borrowed<'a, 'de>> {
-        let borrowed = self.read(bytes, *WRONG_ENDIANNESS && should_flip)?;
+        let borrowed = self.read(bytes, WRONG_ENDIANNESS && should_flip)?;
 
         if borrowed.len() != bytes {
             Err(Error::Eof)
@@ -152,7 +656,23 @@ impl<'de, R: Read<'de>> Deserializer<'de, R> {
 
     #[inline]
     fn consume(&mut self, bytes: usize) -> Result<usize> {
-        self.input.consume(bytes).ok_or(Error::Eof)
+        if self.recordings.is_empty() {
+            self.input.consume(bytes).ok_or(Error::Eof)
+        } else {
+            // Skipped bytes still have to be recorded
+            self.read(bytes, false).map(|borrowed| borrowed.len())
+        }
+    }
+
+    // Caps the number of items left in a seq or map by how many could fit in the rest of the input
+    //   (when every item takes at least `min_len` bytes), so a bogus length can't make a visitor
+    //   allocate more than the input could ever fill
+    #[inline]
+    fn size_hint(&self, items: usize, min_len: usize) -> usize {
+        match self.input.remaining() {
+            Some(remaining) => cmp::min(items, remaining / min_len),
+            None => cmp::min(items, MAX_STREAM_SIZE_HINT),
+        }
     }
 
     #[inline]
@@ -160,38 +680,196 @@ impl<'de, R: Read<'de>> Deserializer<'de, R> {
         let mut total_consumed = 0;
 
         while total_consumed < bytes {
-            match self.input.consume(bytes - total_consumed) {
-                Some(consumed) => total_consumed += consumed,
-                None => {
-                    return Err(Error::Eof)
-                },
+            match self.consume(bytes - total_consumed)? {
+                0 => return Err(Error::Eof),
+                consumed => total_consumed += consumed,
             }
         }
 
         Ok(())
     }
 
+    // Reads something nested inside of a seq, map, variant, tag, or anchor, which counts towards
+    //   `max_depth`
     #[inline]
-    fn get_param(&mut self, value: u8) -> Result<usize> {
-        match value {
-            0...23 => Ok(value as usize),
-            24 => Ok(self.next()? as usize),
-            25 => Ok(
-                unsafe { *(self.must_read(2, true)?.as_slice().as_ptr() as *const u16) as usize }
-            ),
-            26 => Ok(
-                unsafe { *(self.must_read(4, true)?.as_slice().as_ptr() as *const u32) as usize }
-            ),
-            #[cfg(target_pointer_width = "64")]
-            27 => Ok(
-                unsafe { *(self.must_read(8, true)?.as_slice().as_ptr() as *const u64) as usize }
-            ),
-            #[cfg(not(target_pointer_width = "64"))]
-            27 => Err(Error::UsizeOverflow),
-            _ => Err(Error::UnexpectedValue(Type::Any, value)),
+    fn nested<T, F>(&mut self, read: F) -> Result<T>
+    where
+        F: FnOnce(&mut Self) -> Result<T>
+    {
+        self.enter()?;
+        let result = read(self);
+        self.leave();
+
+        result
+    }
+
+    // Goes one level deeper, unless that's deeper than `max_depth` allows
+    #[inline]
+    fn enter(&mut self) -> Result<()> {
+        if let Some(max_depth) = self.options.max_depth {
+            if self.depth >= max_depth {
+                return Err(Error::DepthLimitExceeded);
+            }
+        }
+
+        self.depth += 1;
+
+        Ok(())
+    }
+
+    #[inline]
+    fn leave(&mut self) {
+        self.depth -= 1;
+    }
+
+    // Starts keeping a copy of every byte that gets read
+    #[inline]
+    fn start_recording(&mut self) {
+        self.recordings.push(Vec::new());
+    }
+
+    // Throws away everything in the innermost recording after its first `len` bytes, if there is
+    //   one
+    #[inline]
+    fn truncate_recording(&mut self, len: Option<usize>) {
+        if let (Some(recording), Some(len)) = (self.recordings.last_mut(), len) {
+            recording.truncate(len);
+        }
+    }
+
+    // Skips a value while keeping a copy of its bytes, where interned strings and shared values
+    //   are written out in full so that the copy doesn't refer to anything outside of itself
+    //
+    // If the value has a reference that can't be written out like that (because it points to a
+    //   value that was deserialized as a `Shared`), its index is returned instead of the copy.
+    fn record_value(&mut self) -> Result<core::result::Result<Vec<u8>, usize>> {
+        let outer = self.unresolved.take();
+
+        self.start_recording();
+        let value = self.ignore_value();
+        let recording = self.finish_recording();
+        let unresolved = self.unresolved;
+
+        // The outer recording (if any) has the same references in it
+        self.unresolved = match self.recordings.is_empty() {
+            true => None,
+            false => outer.or(unresolved),
+        };
+        value?;
+
+        Ok(match unresolved {
+            Some(index) => Err(index),
+            None => Ok(recording),
+        })
+    }
+
+    // Creates a deserializer that reads the bytes of an anchored value again
+    fn replay(&self, bytes: Rc<[u8]>) -> Deserializer<'de, RecordedReader> {
+        let mut options = self.options;
+
+        // The bytes were already part of the checksum when they were first read
+        options.checksum = false;
+
+        let mut de = Deserializer::new(RecordedReader::new(bytes), options);
+
+        // It's still nested inside of everything around the reference
+        de.depth = self.depth;
+        de
+    }
+
+    // Stops the innermost recording and returns every byte read since it started
+    fn finish_recording(&mut self) -> Vec<u8> {
+        let recording = self.recordings.pop().unwrap_or_default();
+
+        if let Some(outer) = self.recordings.last_mut() {
+            outer.extend_from_slice(&recording);
+        }
+
+        recording
+    }
+
+    #[inline]
+    fn check_uint(&self, ty: Type, value: u8, n: u64) -> Result<()> {
+        if self.options.strict && !is_shortest_uint(value, n) {
+            Err(Error::NonMinimalInt(ty, value))
+        } else {
+            Ok(())
         }
     }
 
+    #[inline]
+    fn check_int(&self, value: u8, n: i64) -> Result<()> {
+        if self.options.strict && !is_shortest_int(value, n) {
+            Err(Error::NonMinimalInt(Type::Int, value))
+        } else {
+            Ok(())
+        }
+    }
+
+    #[inline]
+    fn check_length(&self, ty: Type, value: u8, n: u64) -> Result<()> {
+        if self.options.strict && !is_shortest_uint(value, n) {
+            Err(Error::NonMinimalLength(ty, value))
+        } else {
+            Ok(())
+        }
+    }
+
+    // Rejects encodings that canonical data never contains, in canonical mode
+    #[inline]
+    fn check_canonical(&self, ty: Type, value: u8) -> Result<()> {
+        if self.options.canonical {
+            Err(Error::NonCanonical(ty, value))
+        } else {
+            Ok(())
+        }
+    }
+
+    #[inline]
+    fn get_param(&mut self, ty: Type, value: u8) -> Result<usize> {
+        let param = match value {
+            0..=23 => value as u64,
+            24 => self.next()? as u64,
+            25 => unsafe {
+                ptr::read_unaligned(self.must_read(2, true)?.as_slice().as_ptr() as *const u16) as u64
+            },
+            26 => unsafe {
+                ptr::read_unaligned(self.must_read(4, true)?.as_slice().as_ptr() as *const u32) as u64
+            },
+            27 => unsafe {
+                ptr::read_unaligned(self.must_read(8, true)?.as_slice().as_ptr() as *const u64)
+            },
+            _ => return Err(Error::UnexpectedValue(ty, value)),
+        };
+
+        self.check_length(ty, value, param)?;
+        self.check_claimed_len(ty, param)
+    }
+
+    // Makes sure that a length could actually fit in the rest of the input (where every item of a
+    //   seq takes at least one byte, and every entry of a map at least two) and isn't over the
+    //   limit, before anything gets read or allocated for it
+    fn check_claimed_len(&self, ty: Type, len: u64) -> Result<usize> {
+        if let Some(max_len) = self.options.max_len {
+            if len > max_len as u64 {
+                return Err(Error::LengthLimitExceeded(ty, len));
+            }
+        }
+
+        if let Some(remaining) = self.input.remaining() {
+            let min_bytes = if ty == Type::Map {
+                len.saturating_mul(2)
+            } else {
+                len
+            };
+
+            if min_bytes > remaining as u64 {
+                return Err(Error::Eof);
+            }
+        }
+
+        usize::try_from(len).map_err(|_| Error::UsizeOverflow)
+    }
 
     #[inline]
     fn parse_uint<V>(&mut self, visitor: V, value: u8) -> Result<V::Value>
@@ -199,17 +877,37 @@ impl<'de, R: Read<'de>> Deserializer<'de, R> {
         V: Visitor<'de>
     {
         match value {
-            0...23 => visitor.visit_u8(value as u8),
-            24 => visitor.visit_u8(self.next()? as u8),
-            25 => visitor.visit_u16(
-                unsafe { *(self.must_read(2, true)?.as_slice().as_ptr() as *const u16) }
-            ),
-            26 => visitor.visit_u32(
-                unsafe { *(self.must_read(4, true)?.as_slice().as_ptr() as *const u32) }
-            ),
-            27 => visitor.visit_u64(
-                unsafe { *(self.must_read(8, true)?.as_slice().as_ptr() as *const u64) }
-            ),
+            0..=23 => visitor.visit_u8(value),
+            24 => {
+                let n = self.next()?;
+
+                self.check_uint(Type::Uint, value, n as u64)?;
+                visitor.visit_u8(n)
+            }
+            25 => {
+                let n = unsafe {
+                    ptr::read_unaligned(self.must_read(2, true)?.as_slice().as_ptr() as *const u16)
+                };
+
+                self.check_uint(Type::Uint, value, n as u64)?;
+                visitor.visit_u16(n)
+            }
+            26 => {
+                let n = unsafe {
+                    ptr::read_unaligned(self.must_read(4, true)?.as_slice().as_ptr() as *const u32)
+                };
+
+                self.check_uint(Type::Uint, value, n as u64)?;
+                visitor.visit_u32(n)
+            }
+            27 => {
+                let n = unsafe {
+                    ptr::read_unaligned(self.must_read(8, true)?.as_slice().as_ptr() as *const u64)
+                };
+
+                self.check_uint(Type::Uint, value, n)?;
+                visitor.visit_u64(n)
+            }
             _ => Err(Error::UnexpectedValue(Type::Uint, value)),
         }
     }
@@ -220,20 +918,38 @@ impl<'de, R: Read<'de>> Deserializer<'de, R> {
         V: Visitor<'de>
     {
         match value {
-            0...15 => visitor.visit_i8(value as i8),
-            16...23 => visitor.visit_i8(value as i8 - 24),
-            24 => visitor.visit_i8(
-                unsafe { *(&mut self.next()? as *mut u8 as *mut i8) }
-            ),
-            25 => visitor.visit_i16(
-                unsafe { *(self.must_read(2, true)?.as_slice().as_ptr() as *const i16) }
-            ),
-            26 => visitor.visit_i32(
-                unsafe { *(self.must_read(4, true)?.as_slice().as_ptr() as *const i32) }
-            ),
-            27 => visitor.visit_i64(
-                unsafe { *(self.must_read(8, true)?.as_slice().as_ptr() as *const i64) }
-            ),
+            0..=15 => visitor.visit_i8(value as i8),
+            16..=23 => visitor.visit_i8(value as i8 - 24),
+            24 => {
+                let n = unsafe { *(&mut self.next()? as *mut u8 as *mut i8) };
+
+                self.check_int(value, n as i64)?;
+                visitor.visit_i8(n)
+            }
+            25 => {
+                let n = unsafe {
+                    ptr::read_unaligned(self.must_read(2, true)?.as_slice().as_ptr() as *const i16)
+                };
+
+                self.check_int(value, n as i64)?;
+                visitor.visit_i16(n)
+            }
+            26 => {
+                let n = unsafe {
+                    ptr::read_unaligned(self.must_read(4, true)?.as_slice().as_ptr() as *const i32)
+                };
+
+                self.check_int(value, n as i64)?;
+                visitor.visit_i32(n)
+            }
+            27 => {
+                let n = unsafe {
+                    ptr::read_unaligned(self.must_read(8, true)?.as_slice().as_ptr() as *const i64)
+                };
+
+                self.check_int(value, n)?;
+                visitor.visit_i64(n)
+            }
             _ => Err(Error::UnexpectedValue(Type::Int, value)),
         }
     }
@@ -244,30 +960,356 @@ impl<'de, R: Read<'de>> Deserializer<'de, R> {
         V: Visitor<'de>
     {
         match value {
-            4 => visitor.visit_f32(
-                unsafe { *(self.must_read(4, true)?.as_slice().as_ptr() as *const f32) }
-            ),
-            5 => visitor.visit_f64(
-                unsafe { *(self.must_read(8, true)?.as_slice().as_ptr() as *const f64) }
-            ),
+            4 => {
+                let v = unsafe {
+                    ptr::read_unaligned(self.must_read(4, true)?.as_slice().as_ptr() as *const f32)
+                };
+
+                if self.options.canonical && (v.is_nan() || f32_to_f16(v).is_some()) {
+                    return Err(Error::NonMinimalFloat(value));
+                }
+
+                visitor.visit_f32(v)
+            }
+            5 => {
+                let v = unsafe {
+                    ptr::read_unaligned(self.must_read(8, true)?.as_slice().as_ptr() as *const f64)
+                };
+
+                if self.options.canonical
+                    && (v.is_nan() || (v as f32 as f64).to_bits() == v.to_bits())
+                {
+                    return Err(Error::NonMinimalFloat(value));
+                }
+
+                visitor.visit_f64(v)
+            }
+            6 => {
+                let half = unsafe {
+                    ptr::read_unaligned(self.must_read(2, true)?.as_slice().as_ptr() as *const u16)
+                };
+                let v = f16_to_f32(half);
+
+                // The only NaN that canonical data contains is the quiet NaN without a payload
+                if self.options.canonical && v.is_nan() && half != 0x7e00 {
+                    return Err(Error::NonMinimalFloat(value));
+                }
+
+                visitor.visit_f32(v)
+            }
             _ => Err(Error::UnexpectedValue(Type::Misc, value)),
         }
     }
 
+    // #[inline]
+    fn parse_bytes<V>(&mut self, visitor: V, value: u8) -> Result<V::Value>
+    where
+        V: Visitor<'de>
+    {
+        match value {
+            28 => {
+                self.check_canonical(Type::Bytes, value)?;
+
+                let value = self.skip_padding()?;
+                let len = self.get_param(Type::Bytes, value)?;
+
+                self.visit_content(visitor, len)
+            }
+            29 => {
+                self.check_canonical(Type::Bytes, value)?;
+
+                // Read the string and remember it for later back-references
+                let value = self.next_plain_bytes()?;
+                let len = self.get_param(Type::Bytes, value)?;
+
+                let interned = if self.input.max_instant_read() < len {
+                    Interned::Owned(self.read_content(len)?)
+                } else {
+                    match self.read(len, false)? {
+                        Borrowed::Permanent(bytes) => Interned::Borrowed(bytes),
+                        bytes => Interned::Owned(bytes.into_vec()),
+                    }
+                };
+
+                self.interned.push(interned);
+
+                self.visit_interned(visitor, self.interned.len() - 1)
+            }
+            30 => {
+                self.check_canonical(Type::Bytes, value)?;
+
+                let index = self.get_extended_param(Type::Bytes)?;
+
+                self.visit_interned(visitor, index)
+            }
+            value => {
+                let len = self.get_param(Type::Bytes, value)?;
+
+                self.visit_content(visitor, len)
+            }
+        }
+    }
+
+    // Reads the content of a string of a known length and passes it to the visitor, borrowing it
+    //   if possible
+    #[inline]
+    fn visit_content<V>(&mut self, visitor: V, len: usize) -> Result<V::Value>
+    where
+        V: Visitor<'de>
+    {
+        if self.input.max_instant_read() < len {
+            // need multiple reads to get full buffer
+            visitor.visit_byte_buf(self.read_content(len)?)
+        } else {
+            match self.must_read(len, false)? {
+                Borrowed::Transient(bytes) => visitor.visit_bytes(bytes),
+                Borrowed::Permanent(bytes) => visitor.visit_borrowed_bytes(bytes),
+                Borrowed::Copied(bytes) => visitor.visit_byte_buf(bytes),
+            }
+        }
+    }
+
+    // Reads the content of a string of a known length into a new buffer, using as many reads as
+    //   necessary
+    fn read_content(&mut self, len: usize) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        let mut bytes_to_parse = len;
+
+        while bytes_to_parse > 0 {
+            let bytes_to_read = cmp::min(self.input.max_instant_read(), bytes_to_parse);
+
+            let bytes = self.read(bytes_to_read, false)?;
+
+            // A read that gets nothing means the input ran out
+            if bytes.len() == 0 {
+                return Err(Error::Eof);
+            }
+
+            bytes_to_parse -= bytes.len();
+            buf.extend_from_slice(bytes.as_slice());
+        }
+
+        Ok(buf)
+    }
+
+    #[inline]
+    fn visit_interned<V>(&self, visitor: V, index: usize) -> Result<V::Value>
+    where
+        V: Visitor<'de>
+    {
+        match self.interned.get(index) {
+            Some(&Interned::Borrowed(bytes)) => visitor.visit_borrowed_bytes(bytes),
+            Some(Interned::Owned(bytes)) => visitor.visit_bytes(bytes),
+            None => Err(Error::UnknownInternedString(index)),
+        }
+    }
+
+    // Reads the instruction byte of a byte string that has to be written out in full, and returns
+    //   its parameter
+    #[inline]
+    fn next_plain_bytes(&mut self) -> Result<u8> {
+        let byte = self.next()?;
+
+        if ty(byte) != Type::Bytes {
+            Err(Error::ExpectedType(vec![Type::Bytes], byte))
+        } else if val(byte) > 27 {
+            Err(Error::UnexpectedValue(Type::Bytes, val(byte)))
+        } else {
+            Ok(val(byte))
+        }
+    }
+
+    #[inline]
+    fn get_tag(&mut self, value: u8) -> Result<u64> {
+        let tag = match value {
+            0..=23 => return Ok(value as u64),
+            24 => self.next()? as u64,
+            25 => unsafe {
+                ptr::read_unaligned(self.must_read(2, true)?.as_slice().as_ptr() as *const u16) as u64
+            },
+            26 => unsafe {
+                ptr::read_unaligned(self.must_read(4, true)?.as_slice().as_ptr() as *const u32) as u64
+            },
+            27 => unsafe {
+                ptr::read_unaligned(self.must_read(8, true)?.as_slice().as_ptr() as *const u64)
+            },
+            _ => return Err(Error::UnexpectedValue(Type::Reserved, value)),
+        };
+
+        self.check_uint(Type::Reserved, value, tag)?;
+
+        Ok(tag)
+    }
+
+    // Reads the id of a variant that isn't written by name
+    #[inline]
+    fn get_variant_id(&mut self, ty: Type, value: u8) -> Result<u32> {
+        let id = match value {
+            0..=23 => return Ok(value as u32),
+            24 => self.next()? as u32,
+            25 => unsafe {
+                ptr::read_unaligned(self.must_read(2, true)?.as_slice().as_ptr() as *const u16) as u32
+            },
+            26 => unsafe {
+                ptr::read_unaligned(self.must_read(4, true)?.as_slice().as_ptr() as *const u32)
+            },
+            _ => return Err(Error::UnexpectedValue(ty, value)),
+        };
+
+        if self.options.strict && !is_shortest_uint(value, id as u64) {
+            return Err(Error::NonMinimalVariant(value));
+        }
+
+        Ok(id)
+    }
+
+    // Reads the index or name of a variant, after its instruction byte
+    fn get_variant(&mut self, value: u8) -> Result<VariantId> {
+        if value != 27 {
+            return self.get_variant_id(Type::Variant, value).map(VariantId::Index);
+        }
+
+        let len = self.get_extended_param(Type::Variant)?;
+        let len = self.check_claimed_len(Type::Variant, len as u64)?;
+        let name = if self.input.max_instant_read() < len {
+            self.read_content(len)?
+        } else {
+            self.must_read(len, false)?.into_vec()
+        };
+
+        String::from_utf8(name).map(VariantId::Name).map_err(|error| {
+            Error::invalid_value(Unexpected::Bytes(error.as_bytes()), &"a variant name")
+        })
+    }
+
+    // Reads a parameter that takes up a whole byte, like the length of a variant name
+    #[inline]
+    fn get_extended_param(&mut self, ty: Type) -> Result<usize> {
+        let value = self.next()?;
+        let param = match value {
+            0..=247 => return Ok(value as usize),
+            248 => self.next()? as u64,
+            249 => unsafe {
+                ptr::read_unaligned(self.must_read(2, true)?.as_slice().as_ptr() as *const u16) as u64
+            },
+            250 => unsafe {
+                ptr::read_unaligned(self.must_read(4, true)?.as_slice().as_ptr() as *const u32) as u64
+            },
+            251 => unsafe {
+                ptr::read_unaligned(self.must_read(8, true)?.as_slice().as_ptr() as *const u64)
+            },
+            _ => return Err(Error::UnexpectedValue(ty, value)),
+        };
+
+        if self.options.strict && !is_shortest_extended(value, param) {
+            return Err(Error::NonMinimalLength(ty, value));
+        }
+
+        usize::try_from(param).map_err(|_| Error::UsizeOverflow)
+    }
+
+    // Skips the padding in front of an aligned byte string, and returns the parameter of the byte
+    //   string that follows it
+    #[inline]
+    fn skip_padding(&mut self) -> Result<u8> {
+        let padding = self.next()? as usize;
+
+        self.must_consume(padding)?;
+
+        self.next_plain_bytes()
+    }
+
+    #[inline]
+    fn ignore_content(&mut self, len: usize) -> Result<()> {
+        let mut bytes_to_parse = len;
+
+        while bytes_to_parse > 0 {
+            let bytes_to_read = cmp::min(self.input.max_instant_read(), bytes_to_parse);
+
+            match self.consume(bytes_to_read)? {
+                0 => return Err(Error::Eof),
+                consumed => bytes_to_parse -= consumed,
+            }
+        }
+
+        Ok(())
+    }
+
+    // Reads the fields of a struct (or tuple struct, or variant of either) in order. A struct that
+    //   has since gained trailing fields leaves the surplus unread, so it gets skipped once the
+    //   visitor is done, and a struct that's since lost some runs out early, where the visitor is
+    //   given `None` for the missing fields so that `#[serde(default)]` can fill them in
+    fn deserialize_positional<V>(&mut self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>
+    {
+        let byte = self.peek_next()?;
+
+        if ty(byte) != Type::Seq {
+            return self.deserialize_seq(visitor);
+        }
+
+        self.must_consume(1)?;
+
+        let len = self.get_param(Type::Seq, val(byte))?;
+
+        self.nested(|de| {
+            let mut fields = SeqVisitor::new(de, len);
+            let value = visitor.visit_seq(&mut fields)?;
+
+            fields.skip_remaining()?;
+
+            Ok(value)
+        })
+    }
+
+    // Reads the fields of a struct (or struct variant), which are either in order or keyed by
+    //   their IDs. Fields with IDs that the struct doesn't know about are skipped.
+    fn deserialize_fields<V>(&mut self, ids: Option<&'static str>,
+        fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>
+    {
+        let byte = self.peek_next()?;
+
+        if ty(byte) != Type::Map {
+            return self.deserialize_positional(visitor);
+        }
+
+        self.must_consume(1)?;
+
+        let len = self.get_param(Type::Map, val(byte))?;
+
+        self.nested(|de| {
+            let mut entries = FieldIdVisitor::new(de, len, ids, fields);
+            let value = visitor.visit_map(&mut entries)?;
+
+            entries.skip_remaining()?;
+
+            Ok(value)
+        })
+    }
+
     // #[inline]
     fn ignore_value(&mut self) -> Result<()> {
+        // Where the value starts in the innermost recording, which interned strings and shared
+        //   values get written out in full from
+        let start = self.recordings.last().map(Vec::len);
         let byte = self.next()?;
 
         match ty(byte) {
+            // Strict mode has to look at numbers to check their size
+            Type::Uint if self.options.strict => self.parse_uint(IgnoredAny, val(byte)).map(|_| ())?,
+            Type::Int if self.options.strict => self.parse_int(IgnoredAny, val(byte)).map(|_| ())?,
             Type::Uint |
             Type::Int => match val(byte) {
-                0...23 => {}, // Self-contained byte
-                value @ 24...27 => {
+                0..=23 => {}, // Self-contained byte
+                value @ 24..=27 => {
                     // 24 => 1
                     // 25 => 2
                     // 26 => 4
                     // 27 => 8
-                    let to_read = 2 << (value - 24);
+                    let to_read = 1 << (value - 24);
 
                     // Don't have to worry about recursive reading because we will never read more
                     //   than the max buffer size
@@ -276,11 +1318,19 @@ impl<'de, R: Read<'de>> Deserializer<'de, R> {
                 value => return Err(Error::UnexpectedValue(ty(byte), value)),
             }
             Type::Misc => match val(byte) {
-                0...3 => {},
-                value @ 4...5 => {
+                0..=3 => {},
+                value @ 4..=6 if self.options.canonical => {
+                    self.parse_float(IgnoredAny, value)?;
+                }
+                value @ 4..=6 => {
                     // 4 => 4
                     // 5 => 8
-                    let to_read = 2 << (value - 2);
+                    // 6 => 2
+                    let to_read = match value {
+                        4 => 4,
+                        5 => 8,
+                        _ => 2,
+                    };
 
                     // Don't have to worry about recursive reading because we will never read more
                     //   than the max buffer size
@@ -290,58 +1340,136 @@ impl<'de, R: Read<'de>> Deserializer<'de, R> {
             }
             Type::Variant => {
                 match val(byte) {
-                    0...23 => {}
-                    value @ 24...26 => {
-                        // 24 => 1
-                        // 25 => 2
-                        // 26 => 4
-                        let to_read = 2 << (value - 24);
-
-                        // Don't have to worry about recursive reading because we will never read more
-                        //   than the max buffer size
-                        self.must_consume(to_read)?;
+                    0..=23 => {}
+                    value @ 24..=26 => {
+                        self.get_variant_id(Type::Variant, value)?;
+                    }
+                    27 => {
+                        // Ignore the variant name
+                        let len = self.get_extended_param(Type::Variant)?;
+
+                        self.ignore_content(len)?;
                     }
-                    27 => self.ignore_value()?, // Ignore the string variant name
                     value => return Err(Error::UnexpectedValue(Type::Variant, value)),
                 }
 
                 // Ignore the variant content
-                self.ignore_value()?;
+                self.nested(Self::ignore_value)?;
             }
             Type::Seq => {
-                let len = self.get_param(val(byte))?;
+                let len = self.get_param(Type::Seq, val(byte))?;
 
-                for _ in 0..len {
-                    self.ignore_value()?;
-                }
+                self.nested(|de| {
+                    for _ in 0..len {
+                        de.ignore_value()?;
+                    }
+
+                    Ok(())
+                })?;
             }
-            Type::Bytes => {
-                let len = self.get_param(val(byte))?;
-                let mut bytes_to_parse = len;
+            Type::Bytes => match val(byte) {
+                28 => {
+                    self.check_canonical(Type::Bytes, 28)?;
+
+                    let value = self.skip_padding()?;
+                    let len = self.get_param(Type::Bytes, value)?;
+
+                    self.ignore_content(len)?;
+                }
+                // Interned strings still have to be remembered for later back-references
+                29 => {
+                    self.truncate_recording(start);
+                    self.parse_bytes(IgnoredAny, 29)?;
+                }
+                30 => {
+                    self.check_canonical(Type::Bytes, 30)?;
+
+                    let index = self.get_extended_param(Type::Bytes)?;
+
+                    self.truncate_recording(start);
+
+                    let bytes = match self.interned.get(index) {
+                        Some(&Interned::Borrowed(bytes)) => bytes,
+                        Some(Interned::Owned(bytes)) => bytes.as_slice(),
+                        None => return Err(Error::UnknownInternedString(index)),
+                    };
 
-                while bytes_to_parse > 0 {
-                    let bytes_to_read = cmp::min(self.input.max_instant_read(), bytes_to_parse);
+                    if let Some(recording) = self.recordings.last_mut() {
+                        write_bytes(recording, bytes);
+                    }
+                }
+                value => {
+                    let len = self.get_param(Type::Bytes, value)?;
 
-                    bytes_to_parse -= self.consume(bytes_to_read)?;
+                    self.ignore_content(len)?;
                 }
             }
             Type::Map => {
-                let len = self.get_param(val(byte))?;
+                let len = self.get_param(Type::Map, val(byte))?;
+                let mut keys = KeyChecker::new();
+
+                self.nested(|de| {
+                    for _ in 0..len {
+                        if de.options.strict {
+                            de.start_recording();
+                            let key = de.ignore_value();
+                            let recording = de.finish_recording();
+
+                            key?;
+                            keys.check(recording, de.options.canonical)?;
+                        } else {
+                            de.ignore_value()?; // key
+                        }
+
+                        de.ignore_value()?; // value
+                    }
+
+                    Ok(())
+                })?;
+            }
+            Type::Reserved => match val(byte) {
+                value @ 0..=27 => {
+                    // Ignore the tag and the value it's attached to
+                    self.get_tag(value)?;
+                    self.nested(Self::ignore_value)?;
+                }
+                28 => {
+                    self.check_canonical(Type::Reserved, 28)?;
+                    self.truncate_recording(start);
+
+                    // Keep a copy of the anchored value, so that later references to it can still
+                    //   be deserialized
+                    let index = self.anchors.len();
+
+                    self.anchors.push(Anchor::default());
+                    self.anchors[index].bytes = self.nested(Self::record_value)?.ok()
+                        .map(Rc::from);
+                }
+                29 => {
+                    self.check_canonical(Type::Reserved, 29)?;
+
+                    let index = self.get_extended_param(Type::Reserved)?;
+                    let bytes = match self.anchors.get(index) {
+                        Some(anchor) => anchor.bytes.clone(),
+                        None => return Err(Error::UnknownAnchor(index)),
+                    };
+
+                    match bytes {
+                        Some(bytes) => {
+                            self.truncate_recording(start);
 
-                for _ in 0..len {
-                    self.ignore_value()?; // key
-                    self.ignore_value()?; // value
+                            if let Some(recording) = self.recordings.last_mut() {
+                                recording.extend_from_slice(&bytes);
+                            }
+                        }
+                        None if !self.recordings.is_empty() => {
+                            self.unresolved = self.unresolved.or(Some(index));
+                        }
+                        None => {}
+                    }
                 }
+                value => return Err(Error::UnexpectedValue(Type::Reserved, value)),
             }
-            Type::Reserved => return Err(Error::ExpectedType(vec![
-                Type::Uint,
-                Type::Int,
-                Type::Misc,
-                Type::Variant,
-                Type::Seq,
-                Type::Bytes,
-                Type::Map
-            ], byte)),
             _ => return Err(Error::NotAType),
         }
 
@@ -387,18 +1515,24 @@ macro_rules! forward_to {
     };
 }
 
-impl<'de, 'a, R: Read<'de>> SerdeDeserializer<'de> for &'a mut Deserializer<'de, R> {
+impl<'de, R: Read<'de>> SerdeDeserializer<'de> for &mut Deserializer<'de, R> {
     type Error = Error;
 
     // #[inline]
-    fn deserialize_any<V>(mut self, visitor: V) -> Result<V::Value>
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>
     {
         let byte = self.peek_next()?;
 
         if ty(byte) == Type::Variant {
-            return visitor.visit_enum(VariantVisitor::new(&mut self));
+            self.must_consume(1)?;
+
+            // There's no telling which variants are known about, so unit variants may have any
+            //   payload
+            let id = self.get_variant(val(byte))?;
+
+            return self.nested(|de| visitor.visit_enum(VariantVisitor::new(de, id, false)));
         }
 
         self.must_consume(1)?;
@@ -411,62 +1545,62 @@ impl<'de, 'a, R: Read<'de>> SerdeDeserializer<'de> for &'a mut Deserializer<'de,
                 1 => visitor.visit_bool(true),
                 2 => visitor.visit_unit(),
                 3 => visitor.visit_none(),
-                4 => visitor.visit_f32(
-                    unsafe { *(self.must_read(4, true)?.as_slice().as_ptr() as *const f32) }
-                ),
-                5 => visitor.visit_f64(
-                    unsafe { *(self.must_read(8, true)?.as_slice().as_ptr() as *const f64) }
-                ),
+                value @ 4..=6 => self.parse_float(visitor, value),
                 _ => Err(Error::UnexpectedValue(Type::Misc, val(byte))),
             }
             Type::Variant => unreachable!(),
             Type::Seq => {
-                let len = self.get_param(val(byte))?;
+                let len = self.get_param(Type::Seq, val(byte))?;
 
-                visitor.visit_seq(SeqVisitor::new(&mut self, len))
+                self.nested(|de| visitor.visit_seq(SeqVisitor::new(de, len)))
             }
-            Type::Bytes => {
-                let len = self.get_param(val(byte))?;
+            Type::Bytes => self.parse_bytes(visitor, val(byte)),
+            Type::Map => {
+                let len = self.get_param(Type::Map, val(byte))?;
+
+                self.nested(|de| visitor.visit_map(SeqVisitor::new(de, len)))
+            }
+            Type::Reserved => match val(byte) {
+                // Tags show up as a seq of the tag number and the value, just like how `Tagged`
+                //   looks to other formats
+                value @ 0..=27 => {
+                    let tag = self.get_tag(value)?;
 
-                if self.input.max_instant_read() < len {
-                    // need multiple reads to get full buffer
+                    self.nested(|de| visitor.visit_seq(TagVisitor::new(de, tag)))
+                }
+                28 => {
+                    self.check_canonical(Type::Reserved, 28)?;
 
-                    let mut buf = Vec::new();
-                    let mut bytes_to_parse = len;
+                    // Without knowing what type of pointer to create, an anchored value can only
+                    //   be deserialized as if it wasn't shared, from a copy that references to it
+                    //   can use as well
+                    let index = self.anchors.len();
 
-                    while bytes_to_parse > 0 {
-                        let bytes_to_read = cmp::min(self.input.max_instant_read(), bytes_to_parse);
+                    self.anchors.push(Anchor::default());
 
-                        let bytes = self.read(bytes_to_read, false)?;
+                    match self.record_value()? {
+                        Ok(bytes) => {
+                            let bytes = Rc::from(bytes);
 
-                        bytes_to_parse -= bytes.len();
-                        buf.extend_from_slice(bytes.as_slice());
+                            self.anchors[index].bytes = Some(Rc::clone(&bytes));
+                            self.replay(bytes).deserialize_any(visitor)
+                        }
+                        Err(reference) => Err(Error::UnknownAnchor(reference)),
                     }
+                }
+                29 => {
+                    self.check_canonical(Type::Reserved, 29)?;
 
-                    visitor.visit_byte_buf(buf)
-                } else {
-                    match self.read(len, false)? {
-                        Borrowed::Transient(bytes) => visitor.visit_bytes(bytes),
-                        Borrowed::Permanent(bytes) => visitor.visit_borrowed_bytes(bytes),
-                        Borrowed::Copied(bytes) => visitor.visit_byte_buf(bytes),
+                    let index = self.get_extended_param(Type::Reserved)?;
+
+                    match self.anchors.get(index).and_then(|anchor| anchor.bytes.clone()) {
+                        Some(bytes) => self.replay(bytes).deserialize_any(visitor),
+                        None => Err(Error::UnknownAnchor(index)),
                     }
                 }
+                value => Err(Error::UnexpectedValue(Type::Reserved, value)),
             }
-            Type::Map => {
-                let len = self.get_param(val(byte))?;
-
-                visitor.visit_map(SeqVisitor::new(&mut self, len))
-            }
-            Type::Reserved => Err(Error::ExpectedType(vec![
-                Type::Int,
-                Type::Uint,
-                Type::Misc,
-                Type::Variant,
-                Type::Seq,
-                Type::Bytes,
-                Type::Map,
-            ], byte)),
-            _ => return Err(Error::NotAType),
+            _ => Err(Error::NotAType),
         }
     }
 
@@ -483,7 +1617,7 @@ impl<'de, 'a, R: Read<'de>> SerdeDeserializer<'de> for &'a mut Deserializer<'de,
             match val(byte) {
                 0 => visitor.visit_bool(false),
                 1 => visitor.visit_bool(true),
-                2...5 => Err(Error::UnexpectedValue(Type::Misc, val(byte))),
+                2..=5 => Err(Error::UnexpectedValue(Type::Misc, val(byte))),
                 _ => Err(Error::UnexpectedValue(Type::Misc, val(byte))),
             }
         }
@@ -515,25 +1649,28 @@ impl<'de, 'a, R: Read<'de>> SerdeDeserializer<'de> for &'a mut Deserializer<'de,
         let byte = self.next()?;
 
         match ty(byte) {
-            Type::Uint => visitor.visit_char(
-                std::char::from_u32(
-                    match val(byte) {
-                        value @ 0...23 => value as u32,
-                        24 => self.next()? as u32,
-                        25 => unsafe {
-                            *(self.must_read(2, true)?.as_slice().as_ptr() as *const u16) as u32
-                        },
-                        26 => unsafe {
-                            *(self.must_read(4, true)?.as_slice().as_ptr() as *const u32)
-                        },
-                        27 => return Err(Error::UnexpectedValue(Type::Char, 27)),
-                        value => return Err(Error::UnexpectedValue(Type::Char, value)), // Unexpected value
-                    }
-                ).ok_or(Error::FailedToParseChar)?
-            ),
+            Type::Uint => {
+                let value = val(byte);
+                let code = match value {
+                    0..=23 => value as u32,
+                    24 => self.next()? as u32,
+                    25 => unsafe {
+                        ptr::read_unaligned(self.must_read(2, true)?.as_slice().as_ptr() as *const u16) as u32
+                    },
+                    26 => unsafe {
+                        ptr::read_unaligned(self.must_read(4, true)?.as_slice().as_ptr() as *const u32)
+                    },
+                    27 => return Err(Error::UnexpectedValue(Type::Char, 27)),
+                    value => return Err(Error::UnexpectedValue(Type::Char, value)), // Unexpected value
+                };
+
+                self.check_uint(Type::Uint, value, code as u64)?;
+
+                visitor.visit_char(core::char::from_u32(code).ok_or(Error::FailedToParseChar)?)
+            }
             Type::Bytes => {
                 match val(byte) {
-                    bytes @ 1...4 => {
+                    bytes @ 1..=4 => {
                         match String::from_utf8(self.read(bytes as usize, false)?.into_vec()) {
                             Ok(s) => {
                                 let mut chars = s.chars();
@@ -543,7 +1680,7 @@ impl<'de, 'a, R: Read<'de>> SerdeDeserializer<'de> for &'a mut Deserializer<'de,
                                     None => return Err(Error::FailedToParseChar),
                                 };
 
-                                if chars.next() != None {
+                                if chars.next().is_some() {
                                     Err(Error::FailedToParseChar)
                                 } else {
                                     visitor.visit_char(first_char)
@@ -567,33 +1704,7 @@ impl<'de, 'a, R: Read<'de>> SerdeDeserializer<'de> for &'a mut Deserializer<'de,
         let byte = self.next()?;
 
         match ty(byte) {
-            Type::Bytes => {
-                let len = self.get_param(val(byte))?;
-
-                if self.input.max_instant_read() < len {
-                    // need multiple reads to get full buffer
-
-                    let mut buf = Vec::new();
-                    let mut bytes_to_parse = len;
-
-                    while bytes_to_parse > 0 {
-                        let bytes_to_read = cmp::min(self.input.max_instant_read(), bytes_to_parse);
-
-                        let bytes = self.read(bytes_to_read, false)?;
-
-                        bytes_to_parse -= bytes.len();
-                        buf.extend_from_slice(bytes.as_slice());
-                    }
-
-                    visitor.visit_byte_buf(buf)
-                } else {
-                    match self.read(len, false)? {
-                        Borrowed::Transient(bytes) => visitor.visit_bytes(bytes),
-                        Borrowed::Permanent(bytes) => visitor.visit_borrowed_bytes(bytes),
-                        Borrowed::Copied(bytes) => visitor.visit_byte_buf(bytes),
-                    }
-                }
-            }
+            Type::Bytes => self.parse_bytes(visitor, val(byte)),
             // Type::Seq => {
             //     let len = self.get_param(val(byte))?;
             //     let mut buf = Vec::new();
@@ -605,7 +1716,7 @@ impl<'de, 'a, R: Read<'de>> SerdeDeserializer<'de> for &'a mut Deserializer<'de,
             //             return Err(Error::ExpectedType(vec![Type::Uint], key));
             //         }
             //         match val(key) {
-            //             value @ 0...23 => buf.push(value),
+            //             value @ 0..=23 => buf.push(value),
             //             24 => buf.push(self.next()?),
             //             value => return Err(Error::UnexpectedValue(Type::Uint, value)),
             //         }
@@ -659,15 +1770,72 @@ impl<'de, 'a, R: Read<'de>> SerdeDeserializer<'de> for &'a mut Deserializer<'de,
     }
 
     #[inline]
-    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    fn deserialize_newtype_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>
     {
+        if name == RAW_TOKEN {
+            // Skip the value while keeping a copy of its bytes, which can't refer to anything
+            //   outside of themselves
+            return match self.record_value()? {
+                Ok(bytes) => visitor.visit_byte_buf(bytes),
+                Err(reference) => Err(Error::UnknownAnchor(reference)),
+            };
+        }
+
+        if name == SHARED_TOKEN {
+            let byte = self.peek_next()?;
+
+            if byte == (Type::Reserved as u8) << 5 | 28 { // anchor
+                self.check_canonical(Type::Reserved, 28)?;
+                self.must_consume(1)?;
+
+                let index = self.anchors.len();
+
+                self.anchors.push(Anchor::default());
+
+                shared::begin_anchor();
+                let value = visitor.visit_newtype_struct(&mut *self);
+                self.anchors[index].pointer = shared::finish_anchor();
+
+                return value;
+            } else if byte == (Type::Reserved as u8) << 5 | 29 { // reference
+                self.check_canonical(Type::Reserved, 29)?;
+                self.must_consume(1)?;
+
+                let index = self.get_extended_param(Type::Reserved)?;
+
+                let anchor = match self.anchors.get(index) {
+                    Some(anchor) => anchor,
+                    None => return Err(Error::UnknownAnchor(index)),
+                };
+
+                if let Some(ref pointer) = anchor.pointer {
+                    shared::provide_reference(Rc::clone(pointer));
+
+                    return visitor.visit_unit();
+                }
+
+                // The anchored value was skipped or deserialized as something else, so the first
+                //   reference to it creates the pointer that every later one gets
+                let bytes = match anchor.bytes {
+                    Some(ref bytes) => Rc::clone(bytes),
+                    None => return Err(Error::UnknownAnchor(index)),
+                };
+
+                shared::begin_anchor();
+                let value = visitor.visit_newtype_struct(&mut self.replay(bytes));
+                self.anchors[index].pointer = shared::finish_anchor();
+
+                return value;
+            }
+        }
+
         visitor.visit_newtype_struct(self)
     }
 
     #[inline]
-    fn deserialize_seq<V>(mut self, visitor: V) -> Result<V::Value>
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>
     {
@@ -675,16 +1843,22 @@ impl<'de, 'a, R: Read<'de>> SerdeDeserializer<'de> for &'a mut Deserializer<'de,
 
         match ty(byte) {
             Type::Seq => {
-                let len = self.get_param(val(byte))?;
+                let len = self.get_param(Type::Seq, val(byte))?;
 
-                visitor.visit_seq(SeqVisitor::new(&mut self, len))
+                self.nested(|de| visitor.visit_seq(SeqVisitor::new(de, len)))
+            }
+            Type::Reserved if val(byte) <= 27 => {
+                // A tag can be read as a seq of the tag number and the value
+                let tag = self.get_tag(val(byte))?;
+
+                self.nested(|de| visitor.visit_seq(TagVisitor::new(de, tag)))
             }
             _ => Err(Error::ExpectedType(vec![Type::Seq], byte))
         }
     }
 
     #[inline]
-    fn deserialize_tuple<V>(mut self, len: usize, visitor: V) -> Result<V::Value>
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>
     {
@@ -692,54 +1866,76 @@ impl<'de, 'a, R: Read<'de>> SerdeDeserializer<'de> for &'a mut Deserializer<'de,
 
         match ty(byte) {
             Type::Seq => {
-                let seq_len = self.get_param(val(byte))?;
+                let seq_len = self.get_param(Type::Seq, val(byte))?;
 
                 if seq_len != len {
                     Err(Error::UnexpectedValue(Type::Seq, val(byte)))
                 } else {
-                    visitor.visit_seq(SeqVisitor::new(&mut self, len))
+                    self.nested(|de| visitor.visit_seq(SeqVisitor::new(de, len)))
                 }
             }
+            Type::Reserved if val(byte) <= 27 && len == 2 => {
+                // A tag can be read as a pair of the tag number and the value
+                let tag = self.get_tag(val(byte))?;
+
+                self.nested(|de| visitor.visit_seq(TagVisitor::new(de, tag)))
+            }
             _ => Err(Error::ExpectedType(vec![Type::Seq], byte)),
         }
     }
 
     #[inline]
-    fn deserialize_tuple_struct<V>(self, _name: &'static str, len: usize, visitor: V)
+    fn deserialize_tuple_struct<V>(self, name: &'static str, _len: usize, visitor: V)
         -> Result<V::Value>
     where
         V: Visitor<'de>
     {
-        self.deserialize_tuple(len, visitor)
+        if name == TAGGED_TOKEN {
+            let byte = self.next()?;
+
+            return match ty(byte) {
+                Type::Reserved => match val(byte) {
+                    value @ 0..=27 => {
+                        let tag = self.get_tag(value)?;
+
+                        self.nested(|de| visitor.visit_seq(TagVisitor::new(de, tag)))
+                    }
+                    value => Err(Error::UnexpectedValue(Type::Reserved, value)),
+                }
+                _ => Err(Error::ExpectedType(vec![Type::Reserved], byte)),
+            };
+        }
+
+        self.deserialize_positional(visitor)
     }
 
     #[inline]
-    fn deserialize_map<V>(mut self, visitor: V) -> Result<V::Value>
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value>
         where V: Visitor<'de>
     {
         let byte = self.next()?;
 
         match ty(byte) {
             Type::Map => {
-                let len = self.get_param(val(byte))?;
+                let len = self.get_param(Type::Map, val(byte))?;
 
-                visitor.visit_map(SeqVisitor::new(&mut self, len))
+                self.nested(|de| visitor.visit_map(SeqVisitor::new(de, len)))
             }
             _ => Err(Error::ExpectedType(vec![Type::Map], byte))
         }
     }
 
     #[inline]
-    fn deserialize_struct<V>(self, _name: &'static str, _fields: &'static [&'static str],
+    fn deserialize_struct<V>(self, name: &'static str, fields: &'static [&'static str],
         visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>
     {
-        self.deserialize_seq(visitor)
+        self.deserialize_fields(explicit_ids(name), fields, visitor)
     }
 
     #[inline]
-    fn deserialize_enum<V>(mut self, _name: &'static str, _variants: &'static [&'static str],
+    fn deserialize_enum<V>(self, _name: &'static str, variants: &'static [&'static str],
         visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>
@@ -747,27 +1943,33 @@ impl<'de, 'a, R: Read<'de>> SerdeDeserializer<'de> for &'a mut Deserializer<'de,
         let byte = self.peek_next()?;
 
         match ty(byte) {
+            // Unit variants used to be written as just their id
             Type::Uint => {
                 self.must_consume(1)?;
+                self.check_canonical(Type::Uint, val(byte))?;
 
                 match val(byte) {
-                    value @ 0...23 => visitor.visit_enum((value as u32).into_deserializer()),
-                    24 => visitor.visit_enum((self.next()? as u32).into_deserializer()),
-                    25 => visitor.visit_enum(
-                        unsafe {
-                            *(self.must_read(2, true)?.as_slice().as_ptr() as *const u16) as u32
-                        }.into_deserializer()
-                    ),
-                    26 => visitor.visit_enum(
-                        unsafe {
-                            *(self.must_read(4, true)?.as_slice().as_ptr() as *const u32)
-                        }.into_deserializer()
-                    ),
                     27 => Err(Error::UsizeOverflow),
-                    value => Err(Error::UnexpectedValue(Type::Uint, value)),
+                    value => {
+                        let id = self.get_variant_id(Type::Uint, value)?;
+
+                        visitor.visit_enum(UintVariant::new(id))
+                    }
                 }
             }
-            Type::Variant => visitor.visit_enum(VariantVisitor::new(&mut self)),
+            Type::Variant => {
+                self.must_consume(1)?;
+
+                // Whether the type knows about the variant decides what its payload can be, so
+                //   the id is read here instead of by the identifier
+                let id = self.get_variant(val(byte))?;
+                let known = match id {
+                    VariantId::Index(index) => (index as usize) < variants.len(),
+                    VariantId::Name(ref name) => variants.contains(&name.as_str()),
+                };
+
+                self.nested(|de| visitor.visit_enum(VariantVisitor::new(de, id, known)))
+            }
             _ => Err(Error::ExpectedType(vec![Type::Uint, Type::Variant], byte)),
         }
     }
@@ -781,60 +1983,17 @@ impl<'de, 'a, R: Read<'de>> SerdeDeserializer<'de> for &'a mut Deserializer<'de,
 
         match ty(byte) {
             Type::Variant => match val(byte) {
-                value @ 0...23 => visitor.visit_u32(value as u32),
-                24 => visitor.visit_u32(self.next()? as u32),
-                25 => visitor.visit_u32(
-                    unsafe {
-                        *(self.must_read(2, true)?.as_slice().as_ptr() as *const u16) as u32
-                    }
-                ),
-                26 => visitor.visit_u32(
-                    unsafe {
-                        *(self.must_read(4, true)?.as_slice().as_ptr() as *const u32)
-                    }
-                ),
                 27 => {
-                    let len = match self.next()? {
-                        value @ 0...247 => value as usize,
-                        248 => self.next()? as usize,
-                        249 => (
-                            unsafe {
-                                *(self.must_read(2, true)?.as_slice().as_ptr() as *const u16)
-                            } as usize
-                        ),
-                        250 => (
-                            unsafe {
-                                *(self.must_read(4, true)?.as_slice().as_ptr() as *const u32)
-                            } as usize
-                        ),
-                        value => return Err(Error::UnexpectedValue(Type::Variant, value)),
-                    };
-
-                    if self.input.max_instant_read() < len {
-                        // need multiple reads to get full buffer
-
-                        let mut buf = Vec::new();
-                        let mut bytes_to_parse = len;
-
-                        while bytes_to_parse > 0 {
-                            let bytes_to_read = cmp::min(self.input.max_instant_read(), bytes_to_parse);
+                    let len = self.get_extended_param(Type::Variant)?;
+                    let len = self.check_claimed_len(Type::Variant, len as u64)?;
 
-                            let bytes = self.read(bytes_to_read, false)?;
-
-                            bytes_to_parse -= bytes.len();
-                            buf.extend_from_slice(bytes.as_slice());
-                        }
+                    self.visit_content(visitor, len)
+                }
+                value => {
+                    let id = self.get_variant_id(Type::Variant, value)?;
 
-                        visitor.visit_byte_buf(buf)
-                    } else {
-                        match self.read(len, false)? {
-                            Borrowed::Transient(bytes) => visitor.visit_bytes(bytes),
-                            Borrowed::Permanent(bytes) => visitor.visit_borrowed_bytes(bytes),
-                            Borrowed::Copied(bytes) => visitor.visit_byte_buf(bytes),
-                        }
-                    }
+                    visitor.visit_u32(id)
                 }
-                value => Err(Error::UnexpectedValue(Type::Variant, value)),
             }
             _ => Err(Error::ExpectedType(vec![Type::Variant], byte)), // Unexpected type
         }
@@ -855,6 +2014,7 @@ struct SeqVisitor<'a, 'de: 'a, R: Read<'de> + 'de> {
     de: &'a mut Deserializer<'de, R>,
     index: usize,
     len: usize,
+    keys: KeyChecker,
 }
 
 impl<'a, 'de, R: Read<'de>> SeqVisitor<'a, 'de, R> {
@@ -864,7 +2024,18 @@ impl<'a, 'de, R: Read<'de>> SeqVisitor<'a, 'de, R> {
             de,
             len,
             index: 0,
+            keys: KeyChecker::new(),
+        }
+    }
+
+    // Skips every element that the visitor didn't ask for
+    pub fn skip_remaining(&mut self) -> Result<()> {
+        while self.index < self.len {
+            self.index += 1;
+            self.de.ignore_value()?;
         }
+
+        Ok(())
     }
 }
 
@@ -884,6 +2055,11 @@ impl<'a, 'de, R: Read<'de>> SeqAccess<'de> for SeqVisitor<'a, 'de, R> {
 
         seed.deserialize(&mut *self.de).map(Some)
     }
+
+    #[inline]
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.de.size_hint(self.len - self.index, 1))
+    }
 }
 
 impl<'a, 'de, R: Read<'de>> MapAccess<'de> for SeqVisitor<'a, 'de, R> {
@@ -898,7 +2074,115 @@ impl<'a, 'de, R: Read<'de>> MapAccess<'de> for SeqVisitor<'a, 'de, R> {
             return Ok(None);
         }
 
-        seed.deserialize(&mut *self.de).map(Some)
+        if !self.de.options.strict {
+            return seed.deserialize(&mut *self.de).map(Some);
+        }
+
+        self.de.start_recording();
+        let key = seed.deserialize(&mut *self.de);
+        let recording = self.de.finish_recording();
+
+        let key = key?;
+        self.keys.check(recording, self.de.options.canonical)?;
+
+        Ok(Some(key))
+    }
+
+    #[inline]
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+    where
+        V: DeserializeSeed<'de>
+    {
+        self.index += 1;
+
+        seed.deserialize(&mut *self.de)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.de.size_hint(self.len - self.index, 2))
+    }
+}
+
+
+// Gives the entries of a struct written with field IDs to a visitor as if they were keyed by the
+//   names of the fields
+struct FieldIdVisitor<'a, 'de: 'a, R: Read<'de> + 'de> {
+    de: &'a mut Deserializer<'de, R>,
+    index: usize,
+    len: usize,
+    ids: Option<&'static str>,
+    fields: &'static [&'static str],
+    keys: KeyChecker,
+}
+
+impl<'a, 'de, R: Read<'de>> FieldIdVisitor<'a, 'de, R> {
+    #[inline]
+    pub fn new(de: &'a mut Deserializer<'de, R>, len: usize, ids: Option<&'static str>,
+        fields: &'static [&'static str]) -> Self
+    {
+        Self {
+            de,
+            len,
+            ids,
+            fields,
+            index: 0,
+            keys: KeyChecker::new(),
+        }
+    }
+
+    fn next_id(&mut self) -> Result<u32> {
+        if !self.de.options.strict {
+            return u32::deserialize(&mut *self.de);
+        }
+
+        self.de.start_recording();
+        let id = u32::deserialize(&mut *self.de);
+        let recording = self.de.finish_recording();
+
+        let id = id?;
+        self.keys.check(recording, self.de.options.canonical)?;
+
+        Ok(id)
+    }
+
+    // Skips every entry that the visitor didn't ask for
+    pub fn skip_remaining(&mut self) -> Result<()> {
+        while self.index < self.len {
+            self.index += 1;
+            self.de.ignore_value()?;
+            self.de.ignore_value()?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a, 'de, R: Read<'de>> MapAccess<'de> for FieldIdVisitor<'a, 'de, R> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    where
+        K: DeserializeSeed<'de>
+    {
+        while self.index < self.len {
+            let id = self.next_id()?;
+            let name = match self.ids {
+                Some(ids) => name_of(ids, id),
+                None => self.fields.get(id as usize).cloned(),
+            };
+
+            match name {
+                Some(name) => return seed.deserialize(name.into_deserializer()).map(Some),
+                None => {
+                    // A field from a newer version of the struct
+                    self.index += 1;
+                    self.de.ignore_value()?;
+                }
+            }
+        }
+
+        Ok(None)
     }
 
     #[inline]
@@ -910,18 +2194,107 @@ impl<'a, 'de, R: Read<'de>> MapAccess<'de> for SeqVisitor<'a, 'de, R> {
 
         seed.deserialize(&mut *self.de)
     }
+
+    #[inline]
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.de.size_hint(self.len - self.index, 2))
+    }
+}
+
+
+// Keeps track of the encoded keys of a map in strict mode
+struct KeyChecker {
+    last: Option<Vec<u8>>,
+    seen: Set<Vec<u8>>,
+}
+
+impl KeyChecker {
+    #[inline]
+    fn new() -> Self {
+        Self {
+            last: None,
+            seen: Set::new(),
+        }
+    }
+
+    fn check(&mut self, key: Vec<u8>, canonical: bool) -> Result<()> {
+        if canonical {
+            // Sorted keys only have to be compared with the one right before them
+            if let Some(ref last) = self.last {
+                if key == *last {
+                    return Err(Error::DuplicateMapKey(key));
+                } else if key < *last {
+                    return Err(Error::UnsortedMapKey(key));
+                }
+            }
+
+            self.last = Some(key);
+        } else if self.seen.contains(&key) {
+            return Err(Error::DuplicateMapKey(key));
+        } else {
+            self.seen.insert(key);
+        }
+
+        Ok(())
+    }
+}
+
+
+struct TagVisitor<'a, 'de: 'a, R: Read<'de> + 'de> {
+    de: &'a mut Deserializer<'de, R>,
+    tag: u64,
+    index: usize,
+}
+
+impl<'a, 'de, R: Read<'de>> TagVisitor<'a, 'de, R> {
+    #[inline]
+    fn new(de: &'a mut Deserializer<'de, R>, tag: u64) -> Self {
+        Self {
+            de,
+            tag,
+            index: 0,
+        }
+    }
+}
+
+impl<'a, 'de, R: Read<'de>> SeqAccess<'de> for TagVisitor<'a, 'de, R> {
+    type Error = Error;
+
+    #[inline]
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: DeserializeSeed<'de>
+    {
+        self.index += 1;
+
+        match self.index {
+            1 => seed.deserialize(self.tag.into_deserializer()).map(Some),
+            2 => seed.deserialize(&mut *self.de).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> Option<usize> {
+        Some(2usize.saturating_sub(self.index))
+    }
 }
 
 
 struct VariantVisitor<'a, 'de: 'a, R: Read<'de> + 'de> {
     de: &'a mut Deserializer<'de, R>,
+    id: VariantId,
+    // Whether the id is one of the enum's variants, rather than one it doesn't know about
+    known: bool,
 }
 
 impl<'a, 'de, R: Read<'de>> VariantVisitor<'a, 'de, R> {
     #[inline]
-    fn new(de: &'a mut Deserializer<'de, R>) -> Self {
+    fn new(de: &'a mut Deserializer<'de, R>, id: VariantId, known: bool) -> Self {
         Self {
-            de
+            de,
+            id,
+            known,
         }
     }
 }
@@ -935,8 +2308,12 @@ impl<'a, 'de, R: Read<'de>> EnumAccess<'de> for VariantVisitor<'a, 'de, R> {
     where
         V: DeserializeSeed<'de>
     {
-        // Seed is going to call deserialize_identifier, which is where we get the variant
-        Ok((seed.deserialize(&mut *self.de)?, self))
+        let value = match self.id {
+            VariantId::Index(index) => seed.deserialize(index.into_deserializer())?,
+            VariantId::Name(ref name) => seed.deserialize(name.as_str().into_deserializer())?,
+        };
+
+        Ok((value, self))
     }
 }
 
@@ -945,15 +2322,13 @@ impl<'de, 'a, R: Read<'de>> VariantAccess<'de> for VariantVisitor<'a, 'de, R> {
 
     #[inline]
     fn unit_variant(self) -> Result<()> {
-        let byte = self.de.next()?;
-
-        if byte == (Type::Misc as u8) << 5 | 2 {
-            Ok(())
-        } else if ty(byte) == Type::Misc {
-            Err(Error::UnexpectedValue(Type::Misc, val(byte)))
-        } else {
-            Err(Error::ExpectedType(vec![Type::Misc], byte))
+        // A variant that isn't known about gets read as a `#[serde(other)]` unit variant, so
+        //   whatever payload it has is skipped
+        if !self.known {
+            return self.de.ignore_value();
         }
+
+        <()>::deserialize(self.de)
     }
 
     #[inline]
@@ -965,18 +2340,81 @@ impl<'de, 'a, R: Read<'de>> VariantAccess<'de> for VariantVisitor<'a, 'de, R> {
     }
 
     #[inline]
-    fn tuple_variant<V>(self, len: usize, visitor: V) -> Result<V::Value>
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>
+    {
+        self.de.deserialize_positional(visitor)
+    }
+
+    #[inline]
+    fn struct_variant<V>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>
+    {
+        self.de.deserialize_fields(None, fields, visitor)
+    }
+}
+
+
+// A unit variant that was written as a plain uint, like older versions of DBOR did
+struct UintVariant {
+    id: u32,
+}
+
+impl UintVariant {
+    #[inline]
+    fn new(id: u32) -> Self {
+        Self {
+            id
+        }
+    }
+}
+
+impl<'de> EnumAccess<'de> for UintVariant {
+    type Error = Error;
+    type Variant = Self;
+
+    #[inline]
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant)>
+    where
+        V: DeserializeSeed<'de>
+    {
+        Ok((seed.deserialize(self.id.into_deserializer())?, self))
+    }
+}
+
+impl<'de> VariantAccess<'de> for UintVariant {
+    type Error = Error;
+
+    #[inline]
+    fn unit_variant(self) -> Result<()> {
+        Ok(())
+    }
+
+    // Only reached by a variant that isn't known about (like one caught by `#[dbor(other)]`),
+    //   which is given the unit that the uint stands for
+    #[inline]
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
+    where
+        T: DeserializeSeed<'de>
+    {
+        seed.deserialize(&mut Deserializer::from_slice(&[(Type::Misc as u8) << 5 | 2]))
+    }
+
+    #[inline]
+    fn tuple_variant<V>(self, _len: usize, _visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>
     {
-        self.de.deserialize_tuple(len, visitor)
+        Err(Error::invalid_type(Unexpected::UnitVariant, &"tuple variant"))
     }
 
     #[inline]
-    fn struct_variant<V>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+    fn struct_variant<V>(self, _fields: &'static [&'static str], _visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>
     {
-        self.de.deserialize_seq(visitor)
+        Err(Error::invalid_type(Unexpected::UnitVariant, &"struct variant"))
     }
 }
@@ -1,5 +1,14 @@
-use std::io::{self, Read as IoRead, ErrorKind as IoErrorKind};
-use std::cmp;
+use core::cmp;
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use alloc::boxed::Box;
+#[cfg(feature = "std")]
+use std::io::{self, Read as IoRead, BufRead, ErrorKind as IoErrorKind};
+
+#[cfg(feature = "std")]
+use checksum::Crc32c;
+use checksum::crc32c;
 
 
 pub enum Borrowed<'a, 'de: 'a> {
@@ -11,28 +20,28 @@ pub enum Borrowed<'a, 'de: 'a> {
 impl<'a, 'de> Borrowed<'a, 'de> {
     #[inline]
     pub fn as_slice(&'a self) -> &'a [u8] {
-        match self {
-            &Borrowed::Transient(slice) => slice,
-            &Borrowed::Permanent(slice) => slice,
-            &Borrowed::Copied(ref vec) => vec.as_slice(),
+        match *self {
+            Borrowed::Transient(slice) => slice,
+            Borrowed::Permanent(slice) => slice,
+            Borrowed::Copied(ref vec) => vec.as_slice(),
         }
     }
 
     #[inline]
-    pub fn into_vec(&self) -> Vec<u8> {
+    pub fn into_vec(self) -> Vec<u8> {
         match self {
-            &Borrowed::Transient(slice) => Vec::from(slice),
-            &Borrowed::Permanent(slice) => Vec::from(slice),
-            &Borrowed::Copied(ref vec) => vec.clone(),
+            Borrowed::Transient(slice) => Vec::from(slice),
+            Borrowed::Permanent(slice) => Vec::from(slice),
+            Borrowed::Copied(vec) => vec,
         }
     }
 
     #[inline]
     pub fn len(&self) -> usize {
-        match self {
-            &Borrowed::Transient(slice) => slice.len(),
-            &Borrowed::Permanent(slice) => slice.len(),
-            &Borrowed::Copied(ref vec) => vec.len(),
+        match *self {
+            Borrowed::Transient(slice) => slice.len(),
+            Borrowed::Permanent(slice) => slice.len(),
+            Borrowed::Copied(ref vec) => vec.len(),
         }
     }
 }
@@ -47,32 +56,50 @@ pub trait Read<'de> {
     fn peek<'a>(&'a mut self, bytes: usize) -> Option<Borrowed<'a, 'de>>;
     fn consume(&mut self, bytes: usize) -> Option<usize>;
     fn max_instant_read(&self) -> usize;
+    // How many bytes are left in the input, if that's known ahead of time
+    fn remaining(&self) -> Option<usize>;
+    // Starts (or restarts) a checksum of every byte consumed from here on
+    fn start_checksum(&mut self);
+    // Stops the checksum and returns it, if one was started
+    fn finish_checksum(&mut self) -> Option<u32>;
     fn finished(&mut self) -> bool;
 }
 
 
-const MAX_BUF_LEN: usize = 1024;
+// The size of the buffer used by `from_reader`
+#[cfg(feature = "std")]
+pub(crate) const DEFAULT_BUF_LEN: usize = 1024;
 
-pub(crate) struct BufferedReader<T: io::Read> {
+// Buffers have to be able to hold the biggest parameter (a `u64`) in one go, so smaller capacities
+//   get rounded up to this
+#[cfg(feature = "std")]
+pub(crate) const MIN_BUF_LEN: usize = 16;
+
+#[cfg(feature = "std")]
+pub struct BufferedReader<T: io::Read> {
     internal: T,
-    buffer: [u8; MAX_BUF_LEN],
+    buffer: Box<[u8]>,
     buf_len: usize,
     index: usize,
     finished: bool,
+    checksum: Option<Crc32c>,
 }
 
+#[cfg(feature = "std")]
 impl<T: IoRead> BufferedReader<T> {
-    pub fn new(reader: T) -> Self {
+    pub fn with_capacity(reader: T, capacity: usize) -> Self {
         Self {
             internal: reader,
-            buffer: [0; MAX_BUF_LEN],
+            buffer: vec![0; cmp::max(capacity, MIN_BUF_LEN)].into_boxed_slice(),
             buf_len: 0,
             index: 0,
             finished: false,
+            checksum: None,
         }
     }
 }
 
+#[cfg(feature = "std")]
 impl<'de, T: IoRead> Read<'de> for BufferedReader<T> {
     fn next(&mut self) -> Option<u8> {
          if self.index >= self.buf_len {
@@ -89,6 +116,11 @@ impl<'de, T: IoRead> Read<'de> for BufferedReader<T> {
                                 self.buf_len = bytes_read;
 
                                 self.index = 1;
+
+                                if let Some(ref mut checksum) = self.checksum {
+                                    checksum.update(&self.buffer[..1]);
+                                }
+
                                 break Some(self.buffer[0]);
                             }
                         }
@@ -105,6 +137,10 @@ impl<'de, T: IoRead> Read<'de> for BufferedReader<T> {
 
             self.index += 1;
 
+            if let Some(ref mut checksum) = self.checksum {
+                checksum.update(&[byte]);
+            }
+
             Some(byte)
         }
     }
@@ -140,8 +176,8 @@ impl<'de, T: IoRead> Read<'de> for BufferedReader<T> {
     }
 
     fn read<'a>(&'a mut self, bytes: usize, flipped: bool) -> Option<Borrowed<'a, 'de>> {
-        if bytes > MAX_BUF_LEN {
-            panic!("Cannot read more than {} bytes from buffer", MAX_BUF_LEN);
+        if bytes > self.buffer.len() {
+            panic!("Cannot read more than {} bytes from buffer", self.buffer.len());
         } else if self.finished {
             if self.index >= self.buf_len {
                 // No bytes left to peek
@@ -154,6 +190,10 @@ impl<'de, T: IoRead> Read<'de> for BufferedReader<T> {
 
                 self.index = cmp::min(self.index + bytes, self.buf_len);
 
+                if let Some(ref mut checksum) = self.checksum {
+                    checksum.update(consumed);
+                }
+
                 // We are never going to read these bytes again, so we might as well flip them in
                 //   place
                 if flipped {
@@ -167,12 +207,8 @@ impl<'de, T: IoRead> Read<'de> for BufferedReader<T> {
                 // Window requested requires us to read more bytes than we have buffered, so read
                 //   in more
 
-                let mut new_buffer = [0; MAX_BUF_LEN];
-
-                // Shift the array over so that index is back at the start
-                new_buffer[..MAX_BUF_LEN - self.index].copy_from_slice(&self.buffer[self.index..]);
-
-                self.buffer = new_buffer;
+                // Shift the buffer over so that index is back at the start
+                self.buffer.copy_within(self.index..self.buf_len, 0);
 
                 let index = self.index;
                 let buf_len = self.buf_len;
@@ -190,6 +226,10 @@ impl<'de, T: IoRead> Read<'de> for BufferedReader<T> {
                                 // Return as many bytes as we have left
                                 let consumed = &mut self.buffer[..self.buf_len];
 
+                                if let Some(ref mut checksum) = self.checksum {
+                                    checksum.update(consumed);
+                                }
+
                                 // We are never going to read these bytes again, so we might as
                                 //   well flip them in place
                                 if flipped {
@@ -204,6 +244,10 @@ impl<'de, T: IoRead> Read<'de> for BufferedReader<T> {
                                 // Return as many bytes as we can
                                 let consumed = &mut self.buffer[..cmp::min(bytes, self.buf_len)];
 
+                                if let Some(ref mut checksum) = self.checksum {
+                                    checksum.update(consumed);
+                                }
+
                                 // We are never going to read these bytes again, so we might as
                                 //   well flip them in place
                                 if flipped {
@@ -221,6 +265,10 @@ impl<'de, T: IoRead> Read<'de> for BufferedReader<T> {
                             // Return as many bytes as we have left
                             let consumed = &mut self.buffer[..self.buf_len];
 
+                            if let Some(ref mut checksum) = self.checksum {
+                                checksum.update(consumed);
+                            }
+
                             // We are never going to read these bytes again, so we might as well
                             //   flip them in place
                             if flipped {
@@ -237,6 +285,10 @@ impl<'de, T: IoRead> Read<'de> for BufferedReader<T> {
 
                 let consumed = &mut self.buffer[orig_index..self.index];
 
+                if let Some(ref mut checksum) = self.checksum {
+                    checksum.update(consumed);
+                }
+
                 // We are never going to read these bytes again, so we might as well flip them in
                 //   place
                 if flipped {
@@ -249,8 +301,8 @@ impl<'de, T: IoRead> Read<'de> for BufferedReader<T> {
     }
 
     fn peek<'a>(&'a mut self, bytes: usize) -> Option<Borrowed<'a, 'de>> {
-        if bytes > MAX_BUF_LEN {
-            panic!("Cannot read more than {} bytes from buffer", MAX_BUF_LEN);
+        if bytes > self.buffer.len() {
+            panic!("Cannot read more than {} bytes from buffer", self.buffer.len());
         } else if self.finished {
             if self.index >= self.buf_len {
                 // No bytes left to peek
@@ -266,12 +318,8 @@ impl<'de, T: IoRead> Read<'de> for BufferedReader<T> {
                 // Window requested requires us to read more bytes than we have buffered, so read
                 //   in more
 
-                let mut new_buffer = [0; MAX_BUF_LEN];
-
-                // Shift the array over so that index is back at the start
-                new_buffer[..MAX_BUF_LEN - self.index].copy_from_slice(&self.buffer[self.index..]);
-
-                self.buffer = new_buffer;
+                // Shift the buffer over so that index is back at the start
+                self.buffer.copy_within(self.index..self.buf_len, 0);
 
                 let index = self.index;
                 let buf_len = self.buf_len;
@@ -313,72 +361,87 @@ impl<'de, T: IoRead> Read<'de> for BufferedReader<T> {
     }
 
     fn consume(&mut self, bytes: usize) -> Option<usize> {
-        if bytes > MAX_BUF_LEN {
-            panic!("Cannot consume more than {} bytes from buffer", MAX_BUF_LEN);
-        } else if self.finished {
+        if self.finished {
             if self.index < self.buf_len {
                 let consumed = cmp::min(bytes, self.buf_len - self.index);
 
+                if let Some(ref mut checksum) = self.checksum {
+                    checksum.update(&self.buffer[self.index..self.index + consumed]);
+                }
+
                 self.index += consumed;
 
                 Some(consumed)
             } else {
                 None
             }
-        } else {
-            if bytes + self.index >= self.buf_len {
-                // Window requested requires us to read more bytes than we have buffered, so read
-                //   in more
-
-                let mut tmp_buffer = [0; MAX_BUF_LEN * 2];
-
-                // The number of bytes which are going to be consumed from the read
-                let bytes_preconsumed = bytes + self.index - self.buf_len;
+        } else if bytes + self.index >= self.buf_len {
+            // Window requested goes past what we have buffered, so throw away the rest of the
+            //   buffer and keep refilling it until enough bytes have been skipped
+            let mut consumed = self.buf_len - self.index;
 
-                // Try to read enough bytes to refill the buffer completely even after consumption
-                loop {
-                    match self.internal.read(&mut tmp_buffer[..bytes_preconsumed + MAX_BUF_LEN]) {
-                        Ok(bytes_read) => {
-                            if bytes_read < bytes_preconsumed {
-                                let consumed = bytes_read + (self.buf_len - self.index);
+            if let Some(ref mut checksum) = self.checksum {
+                checksum.update(&self.buffer[self.index..self.buf_len]);
+            }
 
-                                // Consumed all bytes, none left
-                                self.finished = true;
-                                self.buf_len = 0;
+            self.index = 0;
+            self.buf_len = 0;
 
-                                break Some(consumed);
-                            } else {
-                                self.buf_len = bytes_read - bytes_preconsumed;
-                                self.buffer.copy_from_slice(&tmp_buffer[
-                                    bytes_preconsumed..bytes_read
-                                ]);
+            while consumed < bytes {
+                match self.internal.read(&mut self.buffer) {
+                    Ok(0) => {
+                        self.finished = true;
+                        break;
+                    }
+                    Ok(bytes_read) => {
+                        let skipped = cmp::min(bytes_read, bytes - consumed);
 
-                                break Some(bytes);
-                            }
+                        if let Some(ref mut checksum) = self.checksum {
+                            checksum.update(&self.buffer[..skipped]);
                         }
-                        Err(ref error) if error.kind() == IoErrorKind::Interrupted => {}
-                        Err(_) => {
-                            let consumed = self.buf_len - self.index;
 
-                            // Consumed all bytes, none left
-                            self.finished = true;
-                            self.buf_len = 0;
-
-                            break Some(consumed);
-                        }
+                        consumed += skipped;
+                        self.index = skipped;
+                        self.buf_len = bytes_read;
+                    }
+                    Err(ref error) if error.kind() == IoErrorKind::Interrupted => {}
+                    Err(_) => {
+                        self.finished = true;
+                        break;
                     }
                 }
-            } else {
-                self.index += bytes;
+            }
 
-                Some(bytes)
+            Some(consumed)
+        } else {
+            if let Some(ref mut checksum) = self.checksum {
+                checksum.update(&self.buffer[self.index..self.index + bytes]);
             }
+
+            self.index += bytes;
+
+            Some(bytes)
         }
     }
 
     #[inline]
     fn max_instant_read(&self) -> usize {
-        cmp::min(self.buf_len, MAX_BUF_LEN)
+        cmp::min(self.buf_len, self.buffer.len())
+    }
+
+    #[inline]
+    fn remaining(&self) -> Option<usize> {
+        None
+    }
+
+    #[inline]
+    fn start_checksum(&mut self) {
+        self.checksum = Some(Crc32c::new());
+    }
+
+    #[inline]
+    fn finish_checksum(&mut self) -> Option<u32> {
+        self.checksum.take().map(|checksum| checksum.finish())
     }
 
     #[inline]
@@ -391,16 +454,232 @@ impl<'de, T: IoRead> Read<'de> for BufferedReader<T> {
 }
 
 
-pub(crate) struct SliceReader<'de> {
+// The most `BufReadReader` will copy into its scratch buffer in one read, longer strings get read
+//   in chunks instead
+#[cfg(feature = "std")]
+const MAX_BUFREAD_LEN: usize = 8 * 1024;
+
+// Reads straight out of the buffer of a `BufRead`, only copying when a read goes past the end of
+//   what it has buffered
+#[cfg(feature = "std")]
+pub struct BufReadReader<T: BufRead> {
+    internal: T,
+    // Bytes that were handed out by the last read, which can't be consumed until the borrow ends
+    pending: usize,
+    scratch: Vec<u8>,
+    finished: bool,
+    checksum: Option<Crc32c>,
+}
+
+#[cfg(feature = "std")]
+impl<T: BufRead> BufReadReader<T> {
+    pub fn new(reader: T) -> Self {
+        Self {
+            internal: reader,
+            pending: 0,
+            scratch: Vec::new(),
+            finished: false,
+            checksum: None,
+        }
+    }
+
+    // Consumes the bytes handed out by the last read, and returns how many bytes are buffered
+    //   after them (which is only zero at the end of the input)
+    fn available(&mut self) -> usize {
+        if self.pending > 0 {
+            self.internal.consume(self.pending);
+            self.pending = 0;
+        }
+
+        if self.finished {
+            return 0;
+        }
+
+        loop {
+            match self.internal.fill_buf() {
+                Ok(buffer) => {
+                    if buffer.is_empty() {
+                        self.finished = true;
+                    }
+
+                    break buffer.len();
+                }
+                Err(ref error) if error.kind() == IoErrorKind::Interrupted => {}
+                Err(_) => {
+                    self.finished = true;
+                    break 0;
+                }
+            }
+        }
+    }
+
+    // Only called right after `available` returned a nonzero length, so this never does any io
+    #[inline]
+    fn buffer(&mut self) -> &[u8] {
+        self.internal.fill_buf().unwrap_or(&[])
+    }
+}
+
+// Leaves the `BufRead` right after the last byte that was read, so it can keep being used
+#[cfg(feature = "std")]
+impl<T: BufRead> Drop for BufReadReader<T> {
+    fn drop(&mut self) {
+        self.internal.consume(self.pending);
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'de, T: BufRead> Read<'de> for BufReadReader<T> {
+    fn next(&mut self) -> Option<u8> {
+        if self.available() == 0 {
+            None
+        } else {
+            let byte = self.buffer()[0];
+
+            if let Some(ref mut checksum) = self.checksum {
+                checksum.update(&[byte]);
+            }
+
+            self.internal.consume(1);
+
+            Some(byte)
+        }
+    }
+
+    fn peek_next(&mut self) -> Option<u8> {
+        if self.available() == 0 {
+            None
+        } else {
+            Some(self.buffer()[0])
+        }
+    }
+
+    fn read<'a>(&'a mut self, bytes: usize, flipped: bool) -> Option<Borrowed<'a, 'de>> {
+        let available = self.available();
+
+        if available == 0 {
+            None
+        } else if available >= bytes && !flipped {
+            self.pending = bytes;
+
+            let buffer = self.internal.fill_buf().unwrap_or(&[]);
+
+            if let Some(ref mut checksum) = self.checksum {
+                checksum.update(&buffer[..bytes]);
+            }
+
+            Some(Borrowed::Transient(&buffer[..bytes]))
+        } else {
+            // Copy the bytes across as many refills of the internal buffer as it takes
+            self.scratch.clear();
+
+            while self.scratch.len() < bytes {
+                let available = self.available();
+
+                if available == 0 {
+                    break;
+                }
+
+                let copied = cmp::min(available, bytes - self.scratch.len());
+
+                self.scratch.extend_from_slice(&self.internal.fill_buf().unwrap_or(&[])[..copied]);
+                self.internal.consume(copied);
+            }
+
+            if self.scratch.len() < bytes {
+                // Ran out of input partway through
+                return None;
+            }
+
+            if let Some(ref mut checksum) = self.checksum {
+                checksum.update(&self.scratch);
+            }
+
+            if flipped {
+                self.scratch.reverse();
+            }
+
+            Some(Borrowed::Transient(&self.scratch))
+        }
+    }
+
+    fn peek<'a>(&'a mut self, bytes: usize) -> Option<Borrowed<'a, 'de>> {
+        let available = self.available();
+
+        if available == 0 {
+            None
+        } else {
+            // Return as many bytes as are buffered
+            Some(Borrowed::Transient(&self.buffer()[..cmp::min(bytes, available)]))
+        }
+    }
+
+    fn consume(&mut self, bytes: usize) -> Option<usize> {
+        let mut consumed = 0;
+
+        while consumed < bytes {
+            let available = self.available();
+
+            if available == 0 {
+                break;
+            }
+
+            let skipped = cmp::min(available, bytes - consumed);
+
+            if let Some(ref mut checksum) = self.checksum {
+                checksum.update(&self.internal.fill_buf().unwrap_or(&[])[..skipped]);
+            }
+
+            self.internal.consume(skipped);
+            consumed += skipped;
+        }
+
+        if consumed == 0 && bytes > 0 {
+            None
+        } else {
+            Some(consumed)
+        }
+    }
+
+    #[inline]
+    fn max_instant_read(&self) -> usize {
+        MAX_BUFREAD_LEN
+    }
+
+    #[inline]
+    fn remaining(&self) -> Option<usize> {
+        None
+    }
+
+    #[inline]
+    fn start_checksum(&mut self) {
+        self.checksum = Some(Crc32c::new());
+    }
+
+    #[inline]
+    fn finish_checksum(&mut self) -> Option<u32> {
+        self.checksum.take().map(|checksum| checksum.finish())
+    }
+
+    #[inline]
+    fn finished(&mut self) -> bool {
+        self.available() == 0
+    }
+}
+
+pub struct SliceReader<'de> {
     internal: &'de [u8],
     index: usize,
+    // Where the checksummed bytes start, which are all checksummed at once when they're finished
+    checksum_start: Option<usize>,
 }
 
 impl<'de> SliceReader<'de> {
-    pub fn new<T: AsRef<[u8]> + 'de>(data: &'de T) -> Self {
+    pub fn new<T: AsRef<[u8]> + ?Sized + 'de>(data: &'de T) -> Self {
         Self {
             internal: data.as_ref(),
             index: 0,
+            checksum_start: None,
         }
     }
 }
@@ -481,6 +760,120 @@ impl<'de> Read<'de> for SliceReader<'de> {
         self.internal.len() - self.index
     }
 
+    #[inline]
+    fn remaining(&self) -> Option<usize> {
+        Some(self.internal.len() - self.index)
+    }
+
+    #[inline]
+    fn start_checksum(&mut self) {
+        self.checksum_start = Some(self.index);
+    }
+
+    #[inline]
+    fn finish_checksum(&mut self) -> Option<u32> {
+        self.checksum_start.take().map(|start| crc32c(&self.internal[start..self.index]))
+    }
+
+    #[inline]
+    fn finished(&mut self) -> bool {
+        self.index >= self.internal.len()
+    }
+}
+
+// Reads bytes that were copied out of the input earlier, which can't be borrowed for as long as
+//   the input can
+pub(crate) struct RecordedReader {
+    internal: Rc<[u8]>,
+    index: usize,
+}
+
+impl RecordedReader {
+    pub fn new(data: Rc<[u8]>) -> Self {
+        Self {
+            internal: data,
+            index: 0,
+        }
+    }
+}
+
+impl<'de> Read<'de> for RecordedReader {
+    #[inline]
+    fn next(&mut self) -> Option<u8> {
+        let byte = self.peek_next()?;
+
+        self.index += 1;
+
+        Some(byte)
+    }
+
+    #[inline]
+    fn peek_next(&mut self) -> Option<u8> {
+        self.internal.get(self.index).cloned()
+    }
+
+    fn read<'a>(&'a mut self, bytes: usize, flipped: bool) -> Option<Borrowed<'a, 'de>> {
+        if self.index >= self.internal.len() {
+            None
+        } else {
+            let new_index = cmp::min(self.index + bytes, self.internal.len());
+            let consumed = &self.internal[self.index..new_index];
+
+            self.index = new_index;
+
+            if flipped {
+                let mut consumed = consumed.to_vec();
+
+                consumed.reverse();
+
+                Some(Borrowed::Copied(consumed))
+            } else {
+                Some(Borrowed::Transient(consumed))
+            }
+        }
+    }
+
+    fn peek<'a>(&'a mut self, bytes: usize) -> Option<Borrowed<'a, 'de>> {
+        if self.index >= self.internal.len() {
+            None
+        } else {
+            let end = cmp::min(self.index + bytes, self.internal.len());
+
+            Some(Borrowed::Transient(&self.internal[self.index..end]))
+        }
+    }
+
+    fn consume(&mut self, bytes: usize) -> Option<usize> {
+        if self.index >= self.internal.len() {
+            None
+        } else {
+            let consumed = cmp::min(bytes, self.internal.len() - self.index);
+
+            self.index += consumed;
+
+            Some(consumed)
+        }
+    }
+
+    #[inline]
+    fn max_instant_read(&self) -> usize {
+        self.internal.len() - self.index
+    }
+
+    #[inline]
+    fn remaining(&self) -> Option<usize> {
+        Some(self.internal.len() - self.index)
+    }
+
+    // The bytes were already checksummed when they were read from the input
+    #[inline]
+    fn start_checksum(&mut self) {}
+
+    #[inline]
+    fn finish_checksum(&mut self) -> Option<u32> {
+        None
+    }
+
     #[inline]
     fn finished(&mut self) -> bool {
         self.index >= self.internal.len()
@@ -0,0 +1,681 @@
+use core::{fmt, ptr, str};
+use alloc::string::String;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::io::Read as IoRead;
+
+use serde::de::Visitor;
+
+use error::*;
+use raw::VariantId;
+use schema::{Schema, Item, Len, Field, MapKey, Violation, ViolationKind};
+use super::{Deserializer, DeserializerOptions, Type, Anchor, KeyChecker, ty, val};
+use super::read::*;
+
+
+const UNIT: u8 = (Type::Misc as u8) << 5 | 2;
+const NONE: u8 = (Type::Misc as u8) << 5 | 3;
+const ANCHOR: u8 = (Type::Reserved as u8) << 5 | 28;
+const REFERENCE: u8 = (Type::Reserved as u8) << 5 | 29;
+
+// What items that can't be found in the schema are treated as
+static ANY: Item = Item::Any;
+
+
+pub(crate) fn validate_slice(bytes: &[u8], schema: &Schema, options: DeserializerOptions)
+    -> Vec<Violation>
+{
+    Validator::new(SliceReader::new(bytes), schema, options).run()
+}
+
+#[cfg(feature = "std")]
+pub(crate) fn validate_reader<R: IoRead>(reader: R, schema: &Schema,
+    options: DeserializerOptions) -> Vec<Violation>
+{
+    Validator::new(BufferedReader::with_capacity(reader, DEFAULT_BUF_LEN), schema, options).run()
+}
+
+
+// Keeps count of how many bytes have been taken from the input, so that violations can say where
+//   they are
+pub(crate) struct Counted<R> {
+    input: R,
+    position: usize,
+}
+
+impl<'de, R: Read<'de>> Read<'de> for Counted<R> {
+    #[inline]
+    fn next(&mut self) -> Option<u8> {
+        let byte = self.input.next()?;
+
+        self.position += 1;
+
+        Some(byte)
+    }
+
+    #[inline]
+    fn peek_next(&mut self) -> Option<u8> {
+        self.input.peek_next()
+    }
+
+    #[inline]
+    fn read<'a>(&'a mut self, bytes: usize, flipped: bool) -> Option<Borrowed<'a, 'de>> {
+        let borrowed = self.input.read(bytes, flipped)?;
+
+        self.position += borrowed.len();
+
+        Some(borrowed)
+    }
+
+    #[inline]
+    fn peek<'a>(&'a mut self, bytes: usize) -> Option<Borrowed<'a, 'de>> {
+        self.input.peek(bytes)
+    }
+
+    #[inline]
+    fn consume(&mut self, bytes: usize) -> Option<usize> {
+        let consumed = self.input.consume(bytes)?;
+
+        self.position += consumed;
+
+        Some(consumed)
+    }
+
+    #[inline]
+    fn max_instant_read(&self) -> usize {
+        self.input.max_instant_read()
+    }
+
+    #[inline]
+    fn remaining(&self) -> Option<usize> {
+        self.input.remaining()
+    }
+
+    #[inline]
+    fn start_checksum(&mut self) {
+        self.input.start_checksum()
+    }
+
+    #[inline]
+    fn finish_checksum(&mut self) -> Option<u32> {
+        self.input.finish_checksum()
+    }
+
+    #[inline]
+    fn finished(&mut self) -> bool {
+        self.input.finished()
+    }
+}
+
+
+// Walks through the data with a deserializer, checking every value against the schema as it goes
+struct Validator<'s, 'de, R: Read<'de> + 'de> {
+    de: Deserializer<'de, Counted<R>>,
+    schema: &'s Schema,
+    violations: Vec<Violation>,
+    // The item that each anchored value was checked against, or `None` for ones that were skipped
+    anchored: Vec<Option<&'s Item>>,
+}
+
+impl<'s, 'de, R: Read<'de>> Validator<'s, 'de, R> {
+    fn new(input: R, schema: &'s Schema, options: DeserializerOptions) -> Self {
+        let input = Counted {
+            input,
+            position: 0,
+        };
+
+        Self {
+            de: Deserializer::new(input, options),
+            schema,
+            violations: Vec::new(),
+            anchored: Vec::new(),
+        }
+    }
+
+    fn run(mut self) -> Vec<Violation> {
+        let schema = self.schema;
+        let result = self.validate_item(&schema.root, false).and_then(|_| self.de.end());
+
+        if let Err(error) = result {
+            let offset = self.position();
+
+            self.report(offset, ViolationKind::Malformed(error));
+        }
+
+        self.violations
+    }
+
+    #[inline]
+    fn position(&self) -> usize {
+        self.de.input.position
+    }
+
+    #[inline]
+    fn report(&mut self, offset: usize, kind: ViolationKind) {
+        self.violations.push(Violation {
+            offset,
+            kind,
+        });
+    }
+
+    // Follows references to definitions until it gets to an actual item
+    fn resolve(&mut self, mut item: &'s Item) -> &'s Item {
+        let schema = self.schema;
+        let definitions = &schema.definitions;
+
+        // Every definition can be gone through once, any more than that means they're in a loop
+        for _ in 0..=definitions.len() {
+            let index = match *item {
+                Item::Ref(index) => index,
+                _ => return item,
+            };
+
+            match definitions.get(index) {
+                Some(definition) => item = definition,
+                None => {
+                    let offset = self.position();
+
+                    self.report(offset, ViolationKind::UnknownDefinition(index));
+
+                    return &ANY;
+                }
+            }
+        }
+
+        if let Item::Ref(index) = *item {
+            let offset = self.position();
+
+            self.report(offset, ViolationKind::UnknownDefinition(index));
+        }
+
+        &ANY
+    }
+
+    // Checks a value inside of another one
+    #[inline]
+    fn validate(&mut self, item: &'s Item) -> Result<()> {
+        self.validate_nested(item, false).map(|_| ())
+    }
+
+    // Checks a map key, and returns it if it's one that required keys can be compared against
+    #[inline]
+    fn validate_key(&mut self, item: &'s Item) -> Result<Option<MapKey>> {
+        self.validate_nested(item, true)
+    }
+
+    // Checks a value one level deeper, which counts towards `max_depth` just like deserializing
+    //   it would
+    fn validate_nested(&mut self, item: &'s Item, is_key: bool) -> Result<Option<MapKey>> {
+        self.de.enter()?;
+        let result = self.validate_item(item, is_key);
+        self.de.leave();
+
+        result
+    }
+
+    // Reports a value that's the wrong type for its item, and skips it
+    fn mismatch(&mut self, offset: usize, byte: u8) -> Result<()> {
+        self.report(offset, ViolationKind::UnexpectedType(ty(byte), val(byte)));
+        self.de.ignore_value()
+    }
+
+    fn validate_item(&mut self, item: &'s Item, is_key: bool) -> Result<Option<MapKey>> {
+        let item = self.resolve(item);
+        let mut offset = self.position();
+        let mut byte = self.de.peek_next()?;
+
+        // Any value can be shared, and the value after the anchor is what gets checked
+        while byte == ANCHOR {
+            self.de.check_canonical(Type::Reserved, 28)?;
+            self.de.must_consume(1)?;
+
+            // Anchors inside of skipped values were never checked against anything
+            self.anchored.resize(self.de.anchors.len(), None);
+            self.anchored.push(Some(item));
+            self.de.anchors.push(Anchor::default());
+
+            offset = self.position();
+            byte = self.de.peek_next()?;
+        }
+
+        // A reference to a shared value only fits if it was checked against the same item where
+        //   it was anchored
+        if byte == REFERENCE {
+            self.de.must_consume(1)?;
+            self.de.check_canonical(Type::Reserved, 29)?;
+
+            let index = self.de.get_extended_param(Type::Reserved)?;
+
+            if index >= self.de.anchors.len() {
+                return Err(Error::UnknownAnchor(index));
+            }
+
+            let checked = match self.anchored.get(index) {
+                Some(&Some(anchored)) => ptr::eq(anchored, item) || *anchored == *item,
+                _ => false,
+            };
+
+            if !checked && *item != Item::Any {
+                self.report(offset, ViolationKind::UncheckedReference(index));
+            }
+
+            return Ok(None);
+        }
+
+        match *item {
+            Item::Unit if byte == UNIT => self.de.must_consume(1)?,
+            Item::Bool if ty(byte) == Type::Misc && val(byte) <= 1 => self.de.must_consume(1)?,
+            Item::Integer { min, max } => match ty(byte) {
+                Type::Uint | Type::Int => {
+                    let n = match self.next_number(byte)? {
+                        Number::Int(n) => n,
+                        Number::Float => unreachable!(),
+                    };
+
+                    if n < min || n > max {
+                        self.report(offset, ViolationKind::OutOfRange);
+                    }
+
+                    if is_key && n >= 0 && n <= u64::MAX as i128 {
+                        return Ok(Some(MapKey::Uint(n as u64)));
+                    }
+                }
+                _ => self.mismatch(offset, byte)?,
+            }
+            Item::Float => match ty(byte) {
+                Type::Uint | Type::Int => {
+                    self.next_number(byte)?;
+                }
+                Type::Misc if (4..=6).contains(&val(byte)) => {
+                    self.next_number(byte)?;
+                }
+                _ => self.mismatch(offset, byte)?,
+            }
+            Item::Char => self.validate_char(offset, byte)?,
+            Item::Str(len) if ty(byte) == Type::Bytes => {
+                let contents = self.next_bytes(byte, true, is_key)?;
+
+                self.check_len(offset, len, contents.len);
+
+                if !contents.valid {
+                    self.report(offset, ViolationKind::InvalidUtf8);
+                }
+
+                if let Some(text) = contents.text {
+                    return Ok(Some(MapKey::Str(text)));
+                }
+            }
+            Item::Bytes(len) if ty(byte) == Type::Bytes => {
+                let contents = self.next_bytes(byte, false, false)?;
+
+                self.check_len(offset, len, contents.len);
+            }
+            Item::Option(_) if byte == NONE => self.de.must_consume(1)?,
+            Item::Option(ref inner) => return self.validate_item(inner, is_key),
+            Item::Seq(ref inner, len) if ty(byte) == Type::Seq => {
+                self.de.must_consume(1)?;
+
+                let items = self.de.get_param(Type::Seq, val(byte))?;
+
+                self.check_len(offset, len, items);
+
+                for _ in 0..items {
+                    self.validate(inner)?;
+                }
+            }
+            Item::Tuple(ref items) => self.validate_tuple(offset, byte, items)?,
+            Item::Map { ref keys, ref values, len, ref required } if ty(byte) == Type::Map => {
+                self.de.must_consume(1)?;
+
+                let entries = self.de.get_param(Type::Map, val(byte))?;
+                let mut checker = KeyChecker::new();
+                let mut found = Vec::new();
+
+                self.check_len(offset, len, entries);
+
+                for _ in 0..entries {
+                    self.start_key();
+                    let key = if required.is_empty() {
+                        self.validate(keys).map(|_| None)
+                    } else {
+                        self.validate_key(keys)
+                    };
+                    self.finish_key(&mut checker)?;
+
+                    found.extend(key?);
+
+                    self.validate(values)?;
+                }
+
+                for key in required {
+                    if !found.contains(key) {
+                        self.report(offset, ViolationKind::MissingKey(key.clone()));
+                    }
+                }
+            }
+            Item::Struct(ref fields) => self.validate_struct(offset, byte, fields)?,
+            Item::Enum(ref variants) => match ty(byte) {
+                // Unit variants used to be written as just their id
+                Type::Uint => {
+                    self.de.must_consume(1)?;
+                    self.de.check_canonical(Type::Uint, val(byte))?;
+
+                    if val(byte) == 27 {
+                        return Err(Error::UsizeOverflow);
+                    }
+
+                    let index = self.de.get_variant_id(Type::Uint, val(byte))?;
+
+                    match variants.get(index as usize) {
+                        Some(variant) => match *self.resolve(&variant.item) {
+                            Item::Unit | Item::Any | Item::Option(_) => {}
+                            _ => self.report(offset, ViolationKind::UnexpectedType(Type::Uint,
+                                val(byte))),
+                        }
+                        None => self.report(offset,
+                            ViolationKind::UnknownVariant(VariantId::Index(index))),
+                    }
+                }
+                Type::Variant => {
+                    self.de.must_consume(1)?;
+
+                    let (id, variant) = match val(byte) {
+                        27 => {
+                            let len = self.de.get_extended_param(Type::Variant)?;
+                            let name = self.de.read_content(len)?;
+                            let name = String::from_utf8_lossy(&name).into_owned();
+                            let variant = variants.iter().find(|variant| variant.name == name);
+
+                            (VariantId::Name(name), variant)
+                        }
+                        value => {
+                            let index = self.de.get_variant_id(Type::Variant, value)?;
+
+                            (VariantId::Index(index), variants.get(index as usize))
+                        }
+                    };
+
+                    match variant {
+                        Some(variant) => self.validate(&variant.item)?,
+                        None => {
+                            self.report(offset, ViolationKind::UnknownVariant(id));
+                            self.de.ignore_value()?;
+                        }
+                    }
+                }
+                _ => self.mismatch(offset, byte)?,
+            }
+            Item::Tagged(ref inner) if ty(byte) == Type::Reserved && val(byte) <= 27 => {
+                self.de.must_consume(1)?;
+                self.de.get_tag(val(byte))?;
+
+                return self.validate_nested(inner, is_key);
+            }
+            // Keys that could be anything can still be required ones
+            Item::Any if is_key && ty(byte) == Type::Uint => {
+                if let Number::Int(n) = self.next_number(byte)? {
+                    return Ok(Some(MapKey::Uint(n as u64)));
+                }
+            }
+            Item::Any if is_key && ty(byte) == Type::Bytes => {
+                let contents = self.next_bytes(byte, true, true)?;
+
+                return Ok(contents.text.map(MapKey::Str));
+            }
+            Item::Any => self.de.ignore_value()?,
+            _ => self.mismatch(offset, byte)?,
+        }
+
+        Ok(None)
+    }
+
+    // Reads a whole number written as a uint, an int, or a float
+    fn next_number(&mut self, byte: u8) -> Result<Number> {
+        self.de.must_consume(1)?;
+
+        match ty(byte) {
+            Type::Uint => self.de.parse_uint(NumberVisitor, val(byte)),
+            Type::Int => self.de.parse_int(NumberVisitor, val(byte)),
+            _ => self.de.parse_float(NumberVisitor, val(byte)),
+        }
+    }
+
+    // Reads a whole byte string, which might be aligned or interned
+    fn next_bytes(&mut self, byte: u8, utf8: bool, keep: bool) -> Result<Contents> {
+        self.de.must_consume(1)?;
+        self.de.parse_bytes(BytesVisitor { utf8, keep }, val(byte))
+    }
+
+    fn check_len(&mut self, offset: usize, len: Len, found: usize) {
+        if !len.contains(found) {
+            self.report(offset, ViolationKind::UnexpectedLength(found));
+        }
+    }
+
+    // Starts keeping a copy of a key, when keys have to be checked for duplicates
+    #[inline]
+    fn start_key(&mut self) {
+        if self.de.options.strict {
+            self.de.start_recording();
+        }
+    }
+
+    #[inline]
+    fn finish_key(&mut self, checker: &mut KeyChecker) -> Result<()> {
+        if self.de.options.strict {
+            let recording = self.de.finish_recording();
+
+            checker.check(recording, self.de.options.canonical)?;
+        }
+
+        Ok(())
+    }
+
+    fn validate_char(&mut self, offset: usize, byte: u8) -> Result<()> {
+        let valid = match (ty(byte), val(byte)) {
+            (Type::Uint, _) => match self.next_number(byte)? {
+                Number::Int(n) => val(byte) < 27 && core::char::from_u32(n as u32).is_some(),
+                Number::Float => unreachable!(),
+            },
+            (Type::Bytes, len @ 1..=4) => {
+                self.de.must_consume(1)?;
+
+                let bytes = self.de.must_read(len as usize, false)?.into_vec();
+                let mut chars = str::from_utf8(&bytes).map(str::chars).ok();
+
+                match chars {
+                    Some(ref mut chars) => chars.next().is_some() && chars.next().is_none(),
+                    None => false,
+                }
+            }
+            (Type::Bytes, _) => {
+                self.de.ignore_value()?;
+
+                false
+            }
+            _ => return self.mismatch(offset, byte),
+        };
+
+        if !valid {
+            self.report(offset, ViolationKind::InvalidChar);
+        }
+
+        Ok(())
+    }
+
+    fn validate_tuple(&mut self, offset: usize, byte: u8, items: &'s [Item]) -> Result<()> {
+        match ty(byte) {
+            Type::Seq => {
+                self.de.must_consume(1)?;
+
+                let len = self.de.get_param(Type::Seq, val(byte))?;
+
+                if len != items.len() {
+                    self.report(offset, ViolationKind::UnexpectedLength(len));
+                }
+
+                for index in 0..len {
+                    match items.get(index) {
+                        Some(item) => self.validate(item)?,
+                        None => self.de.ignore_value()?,
+                    }
+                }
+            }
+            // A tag can be read as a pair of the tag number and the value
+            Type::Reserved if val(byte) <= 27 && items.len() == 2 => {
+                self.de.must_consume(1)?;
+
+                let tag = self.de.get_tag(val(byte))?;
+
+                match *self.resolve(&items[0]) {
+                    Item::Integer { min, max } if (tag as i128) < min || (tag as i128) > max => {
+                        self.report(offset, ViolationKind::OutOfRange);
+                    }
+                    Item::Integer { .. } | Item::Float | Item::Any => {}
+                    _ => self.report(offset, ViolationKind::UnexpectedType(Type::Reserved,
+                        val(byte))),
+                }
+
+                self.validate(&items[1])?;
+            }
+            _ => self.mismatch(offset, byte)?,
+        }
+
+        Ok(())
+    }
+
+    fn validate_struct(&mut self, offset: usize, byte: u8, fields: &'s [Field])
+        -> Result<()>
+    {
+        let mut found = vec![false; fields.len()];
+
+        match ty(byte) {
+            Type::Seq => {
+                self.de.must_consume(1)?;
+
+                let len = self.de.get_param(Type::Seq, val(byte))?;
+
+                for (field, found) in fields.iter().zip(&mut found).take(len) {
+                    *found = true;
+                    self.validate(&field.item)?;
+                }
+
+                // Surplus fields are from a newer version of the struct
+                for _ in fields.len()..len {
+                    self.de.ignore_value()?;
+                }
+            }
+            Type::Map => {
+                self.de.must_consume(1)?;
+
+                let len = self.de.get_param(Type::Map, val(byte))?;
+                let mut checker = KeyChecker::new();
+
+                for _ in 0..len {
+                    let key_offset = self.position();
+                    let key = self.de.peek_next()?;
+
+                    self.start_key();
+                    let id = match ty(key) {
+                        Type::Uint => self.next_number(key).map(|id| match id {
+                            Number::Int(id) => Some(id),
+                            Number::Float => None,
+                        }),
+                        _ => self.mismatch(key_offset, key).map(|_| None),
+                    };
+                    self.finish_key(&mut checker)?;
+
+                    let index = id?.and_then(|id| {
+                        (0..fields.len()).find(|&index| {
+                            fields[index].id.map_or(index as i128, |field_id| field_id as i128) == id
+                        })
+                    });
+
+                    // Fields with IDs that aren't known about are from a newer version of the
+                    //   struct
+                    match index {
+                        Some(index) => {
+                            found[index] = true;
+                            self.validate(&fields[index].item)?;
+                        }
+                        None => self.de.ignore_value()?,
+                    }
+                }
+            }
+            _ => return self.mismatch(offset, byte),
+        }
+
+        for (field, found) in fields.iter().zip(found) {
+            if field.required && !found {
+                self.report(offset, ViolationKind::MissingField(field.name.clone()));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+
+// A number that was read while validating, where only whole numbers matter
+enum Number {
+    Int(i128),
+    Float,
+}
+
+struct NumberVisitor;
+
+impl<'de> Visitor<'de> for NumberVisitor {
+    type Value = Number;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a number")
+    }
+
+    #[inline]
+    fn visit_u64<E>(self, v: u64) -> core::result::Result<Number, E> {
+        Ok(Number::Int(v as i128))
+    }
+
+    #[inline]
+    fn visit_i64<E>(self, v: i64) -> core::result::Result<Number, E> {
+        Ok(Number::Int(v as i128))
+    }
+
+    #[inline]
+    fn visit_f64<E>(self, _v: f64) -> core::result::Result<Number, E> {
+        Ok(Number::Float)
+    }
+}
+
+// What was found in a byte string while validating
+struct Contents {
+    len: usize,
+    valid: bool,
+    // The string itself, if it was asked for and is valid UTF-8
+    text: Option<String>,
+}
+
+struct BytesVisitor {
+    // Whether the bytes have to be UTF-8
+    utf8: bool,
+    // Whether to keep a copy of the string
+    keep: bool,
+}
+
+impl<'de> Visitor<'de> for BytesVisitor {
+    type Value = Contents;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a byte string")
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> core::result::Result<Contents, E> {
+        let text = if self.utf8 { str::from_utf8(v).ok() } else { None };
+
+        Ok(Contents {
+            len: v.len(),
+            valid: !self.utf8 || text.is_some(),
+            text: text.filter(|_| self.keep).map(String::from),
+        })
+    }
+}
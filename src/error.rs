@@ -1,14 +1,34 @@
-use std;
-use std::fmt::{self, Display};
-use std::io::Error as IoError;
+use core;
+use core::fmt::{self, Display};
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+pub use std::io::Error as IoError;
 
 use serde::{ser, de};
 
 /// Alias for a `Result` with the error type `serde_dbor::Error`
-pub type Result<T> = std::result::Result<T, Error>;
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// Stands in for `std::io::Error` in `Error::Io` without the `std` feature, where there's no IO
+/// that could fail, so it can never actually be created
+#[cfg(not(feature = "std"))]
+#[derive(Debug)]
+pub enum IoError {}
+
+#[cfg(not(feature = "std"))]
+impl Display for IoError {
+    fn fmt(&self, _: &mut fmt::Formatter) -> fmt::Result {
+        match *self {}
+    }
+}
 
 /// Every single possible error that can be thrown from either Serialization or Deserialization
+///
+/// More errors may be added in later versions, and the same errors exist with or without the
+/// `std` feature.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Error {
     /// A generic error message (used by serde for custom errors)
     Message(String),
@@ -40,6 +60,76 @@ pub enum Error {
 
     /// Maps and sequences must have a known size before serialization
     MustKnowItemSize,
+
+    /// Found a back-reference to an interned string that hasn't been defined yet
+    UnknownInternedString(usize),
+
+    /// Found a reference to a shared value whose anchor hasn't been defined or finished yet
+    UnknownAnchor(usize),
+
+    /// Found the same key more than once in a canonical map or in strict mode, with the key's
+    /// encoded bytes
+    DuplicateMapKey(Vec<u8>),
+
+    /// Found a number or tag of this type written with a longer parameter than it needed, in
+    /// strict mode
+    NonMinimalInt(super::Type, u8),
+
+    /// Found a length or index for this type written with a longer parameter than it needed, in
+    /// strict mode
+    NonMinimalLength(super::Type, u8),
+
+    /// Found a variant id written with a longer parameter than it needed, in strict mode
+    NonMinimalVariant(u8),
+
+    /// Found a float that fits in a smaller size, or a NaN other than the canonical one, in
+    /// canonical mode
+    NonMinimalFloat(u8),
+
+    /// Found a map key that sorts before the key right before it in canonical mode, with the
+    /// key's encoded bytes
+    UnsortedMapKey(Vec<u8>),
+
+    /// Found an encoding that canonical data never contains, like aligned bytes, interned bytes,
+    /// or shared values
+    NonCanonical(super::Type, u8),
+
+    /// Tried to deserialize a key without knowing its type, which keys don't store
+    NotSelfDescribing,
+
+    /// Found a byte in a key that isn't valid at that position
+    InvalidKeyByte(u8),
+
+    /// The buffer given to `to_slice` is too small to hold the serialized data
+    BufferTooSmall,
+
+    /// Found a length for this type that's longer than `DeserializerOptions::max_len` allows
+    LengthLimitExceeded(super::Type, u64),
+
+    /// Expected a header, but the data doesn't start with the DBOR magic bytes
+    InvalidMagic,
+
+    /// Found a header for a version of the format that this version of the crate can't read
+    UnsupportedVersion(u8),
+
+    /// Found header flags that this version of the crate doesn't know about
+    UnknownHeaderFlags(u8),
+
+    /// The checksum trailer doesn't match the data, which must have been corrupted
+    ChecksumMismatch,
+
+    /// Couldn't compute the fingerprint of a type, because tracing this enum never ends
+    UntraceableType(&'static str),
+
+    /// The fingerprint in the header doesn't match the type being deserialized, with the
+    /// fingerprint from the header and then the type's own fingerprint
+    FingerprintMismatch(u64, u64),
+
+    /// A struct gives its fields their own IDs, but leaves this one out
+    MissingFieldId(&'static str),
+
+    /// Found values nested more deeply than `DeserializerOptions::max_depth` allows
+    DepthLimitExceeded,
 }
 
 impl ser::Error for Error {
@@ -57,30 +147,72 @@ impl de::Error for Error {
 impl Display for Error {
     fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
         match *self {
+            Error::Message(ref msg) => formatter.write_str(msg),
             Error::Io(ref io_error) => io_error.fmt(formatter),
+            Error::Eof => formatter.write_str("Unexpected end of input"),
             Error::ExpectedType(ref expected, ref got) => write!(formatter, "Expected any of \
                 {:?}, but instead got byte {:x}", expected, got),
             Error::UnexpectedValue(ref ty, ref val) => write!(formatter, "Value {:x} is an \
                 invalid value for type {:?}", val, ty),
-            _ => formatter.write_str(std::error::Error::description(self)),
+            Error::TrailingBytes => formatter.write_str("Finished deserialization with trailing \
+                bytes"),
+            Error::UsizeOverflow => formatter.write_str("Number is too big to be deserialized \
+                into a usize"),
+            Error::NotAType => formatter.write_str("You should never see this, a type was \
+                deserialized that doesn't actually exist"),
+            Error::FailedToParseChar => formatter.write_str("Failed to turn byte array into char"),
+            Error::MustKnowItemSize => formatter.write_str("Map or seq had unknown size during \
+                serialization"),
+            Error::UnknownInternedString(index) => write!(formatter, "Back-reference to interned \
+                string {} which hasn't been defined", index),
+            Error::UnknownAnchor(index) => write!(formatter, "Reference to shared value {} which \
+                hasn't been defined or finished", index),
+            Error::DuplicateMapKey(ref key) => write!(formatter, "Map has more than one entry with \
+                the key {:x?}", key),
+            Error::NonMinimalInt(ref ty, ref val) => write!(formatter, "Value of type {:?} written \
+                with parameter {} would fit in a shorter form", ty, val),
+            Error::NonMinimalLength(ref ty, ref val) => write!(formatter, "Length or index of type \
+                {:?} written with parameter {} would fit in a shorter form", ty, val),
+            Error::NonMinimalVariant(ref val) => write!(formatter, "Variant id written with \
+                parameter {} would fit in a shorter form", val),
+            Error::NonMinimalFloat(ref val) => write!(formatter, "Float written with parameter {} \
+                isn't in its canonical form", val),
+            Error::UnsortedMapKey(ref key) => write!(formatter, "Map key {:x?} is out of order", key),
+            Error::NonCanonical(ref ty, ref val) => write!(formatter, "Value {:x} of type {:?} is \
+                never written in canonical data", val, ty),
+            Error::NotSelfDescribing => formatter.write_str("Keys can't be deserialized without \
+                knowing their type"),
+            Error::InvalidKeyByte(ref val) => write!(formatter, "Byte {:x} isn't valid at this \
+                position in a key", val),
+            Error::BufferTooSmall => formatter.write_str("Output buffer is too small to hold the \
+                serialized data"),
+            Error::LengthLimitExceeded(ref ty, len) => write!(formatter, "Length {} of type {:?} \
+                is longer than the limit", len, ty),
+            Error::InvalidMagic => formatter.write_str("Data doesn't start with a DBOR header"),
+            Error::UnsupportedVersion(version) => write!(formatter, "DBOR format version {} isn't \
+                supported", version),
+            Error::UnknownHeaderFlags(flags) => write!(formatter, "Header has unknown flags \
+                {:08b}", flags),
+            Error::ChecksumMismatch => formatter.write_str("Checksum doesn't match the data"),
+            Error::UntraceableType(name) => write!(formatter, "Can't trace the shape of {}, every \
+                variant contains itself", name),
+            Error::FingerprintMismatch(found, expected) => write!(formatter, "Data was written \
+                for a type with fingerprint {:016x}, but is being read as a type with fingerprint \
+                {:016x}", found, expected),
+            Error::MissingFieldId(field) => write!(formatter, "Field `{}` doesn't have an ID",
+                field),
+            Error::DepthLimitExceeded => formatter.write_str("Values are nested more deeply than \
+                the limit"),
         }
     }
 }
 
+#[cfg(feature = "std")]
 impl std::error::Error for Error {
-    fn description(&self) -> &str {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match *self {
-            Error::Message(ref msg) => msg,
-            Error::Io(ref io_error) => io_error.description(),
-            Error::Eof => "Unexpected end of input",
-            Error::ExpectedType(_, _) => "Expected a different type",
-            Error::UnexpectedValue(_, _) => "Did not expect a specific value",
-            Error::TrailingBytes => "Finished deserialization with trailing bytes",
-            Error::UsizeOverflow => "Number is too big to be deserialized into a usize",
-            Error::NotAType => "You should never see this, a type was deserialized that doesn't \
-                actually exist",
-            Error::FailedToParseChar => "Failed to turn byte array into char",
-            Error::MustKnowItemSize => "Map or seq had unknown size during serialization",
+            Error::Io(ref io_error) => Some(io_error),
+            _ => None,
         }
     }
 }
@@ -0,0 +1,33 @@
+// Struct name used to give fields their own IDs instead of numbering them in order. It's followed
+//   by a comma separated list of `name=id` pairs, like `$serde_dbor::private::FieldIds:a=0,b=7`,
+//   and other serializers just see a strangely named struct.
+pub const FIELD_IDS_TOKEN: &str = "$serde_dbor::private::FieldIds:";
+
+// The list of `name=id` pairs in a struct name, if it has one
+#[inline]
+pub(crate) fn explicit_ids(name: &'static str) -> Option<&'static str> {
+    name.strip_prefix(FIELD_IDS_TOKEN)
+}
+
+// Splits a list of `name=id` pairs, skipping anything that isn't a pair
+fn pairs<'a>(ids: &'a str) -> impl Iterator<Item = (&'a str, &'a str)> + 'a {
+    ids.split(',').filter_map(|pair| {
+        let mut parts = pair.splitn(2, '=');
+
+        Some((parts.next()?, parts.next()?))
+    })
+}
+
+// Looks up the ID of a field by its name
+pub(crate) fn id_of(ids: &str, field: &str) -> Option<u32> {
+    pairs(ids)
+        .find(|&(name, _)| name == field)
+        .and_then(|(_, id)| id.parse().ok())
+}
+
+// Looks up the name of a field by its ID
+pub(crate) fn name_of(ids: &'static str, id: u32) -> Option<&'static str> {
+    pairs(ids)
+        .find(|&(_, field_id)| field_id.parse() == Ok(id))
+        .map(|(name, _)| name)
+}
@@ -0,0 +1,819 @@
+use core::mem;
+use alloc::vec::Vec;
+
+use serde::de::{self, Deserialize, DeserializeSeed, Deserializer, Visitor, SeqAccess, MapAccess,
+    EnumAccess, VariantAccess, IntoDeserializer};
+
+use error::*;
+
+
+// Tokens written to the description of a type, which gets hashed into its fingerprint (or turned
+//   into a schema)
+pub(crate) const TOKEN_BOOL: u8 = 0x01;
+pub(crate) const TOKEN_I8: u8 = 0x02;
+pub(crate) const TOKEN_I16: u8 = 0x03;
+pub(crate) const TOKEN_I32: u8 = 0x04;
+pub(crate) const TOKEN_I64: u8 = 0x05;
+pub(crate) const TOKEN_I128: u8 = 0x06;
+pub(crate) const TOKEN_U8: u8 = 0x07;
+pub(crate) const TOKEN_U16: u8 = 0x08;
+pub(crate) const TOKEN_U32: u8 = 0x09;
+pub(crate) const TOKEN_U64: u8 = 0x0a;
+pub(crate) const TOKEN_U128: u8 = 0x0b;
+pub(crate) const TOKEN_F32: u8 = 0x0c;
+pub(crate) const TOKEN_F64: u8 = 0x0d;
+pub(crate) const TOKEN_CHAR: u8 = 0x0e;
+pub(crate) const TOKEN_STR: u8 = 0x0f;
+pub(crate) const TOKEN_BYTES: u8 = 0x10;
+pub(crate) const TOKEN_OPTION: u8 = 0x11;
+pub(crate) const TOKEN_UNIT: u8 = 0x12;
+pub(crate) const TOKEN_SEQ: u8 = 0x13;
+pub(crate) const TOKEN_TUPLE: u8 = 0x14;
+pub(crate) const TOKEN_MAP: u8 = 0x15;
+pub(crate) const TOKEN_STRUCT: u8 = 0x16;
+pub(crate) const TOKEN_ENUM: u8 = 0x17;
+pub(crate) const TOKEN_ANY: u8 = 0x18;
+pub(crate) const TOKEN_IDENTIFIER: u8 = 0x19;
+pub(crate) const TOKEN_RECURSION: u8 = 0x1a;
+pub(crate) const TOKEN_UNIT_VARIANT: u8 = 0x1b;
+pub(crate) const TOKEN_NEWTYPE_VARIANT: u8 = 0x1c;
+pub(crate) const TOKEN_TUPLE_VARIANT: u8 = 0x1d;
+pub(crate) const TOKEN_STRUCT_VARIANT: u8 = 0x1e;
+// Only written when tracing for a schema, followed by the index of the container's name
+pub(crate) const TOKEN_CONTAINER: u8 = 0x1f;
+
+// Types nested deeper than this are assumed to never end
+const MAX_DEPTH: usize = 256;
+
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+
+/// Computes a fingerprint of the shape of type T, as seen through its `Deserialize`
+/// implementation
+///
+/// The fingerprint covers the order, names, and types of every field and the names and contents
+/// of every enum variant, but not the names of the types themselves, so renaming a struct keeps
+/// its fingerprint while reordering two of its fields doesn't. It's the same on every platform,
+/// which makes it useful for catching data written by a different version of a type, like with
+/// `SerializerOptions::fingerprint`.
+///
+/// The type is traced without any data by handing its `Deserialize` implementation placeholder
+/// values (ones for integers, empty strings, and one element for every seq and map), so types that
+/// reject those values can't be fingerprinted, and neither can types that only work through
+/// `deserialize_any` (like untagged enums). Recursive types are fine as long as every recursive
+/// enum has at least one variant that doesn't contain itself, otherwise `Error::UntraceableType`
+/// is returned.
+pub fn fingerprint<'de, T: Deserialize<'de>>() -> Result<u64> {
+    let trace = trace::<T>(false)?;
+    let mut hash = Fnv::new();
+
+    hash.update(&trace.main);
+
+    for &(variants, ref contents) in &trace.enums {
+        hash.update(&[TOKEN_ENUM]);
+        hash.update(&(variants.len() as u32).to_le_bytes());
+
+        for (name, contents) in variants.iter().zip(contents) {
+            hash.update(&(name.len() as u32).to_le_bytes());
+            hash.update(name.as_bytes());
+            hash.update(contents);
+        }
+    }
+
+    Ok(hash.finish())
+}
+
+
+// The description of a type, split into the type itself and the variants of every enum in it,
+//   which the type refers to by their index
+pub(crate) struct Trace {
+    pub(crate) main: Vec<u8>,
+    // The names and descriptions of the variants of every enum
+    pub(crate) enums: Vec<(&'static [&'static str], Vec<Vec<u8>>)>,
+    // The names and members of every struct, tuple struct, newtype, and enum, when `names` is set
+    pub(crate) containers: Vec<(&'static str, &'static [&'static str])>,
+}
+
+// Traces the shape of type T. With `names`, containers are also marked with `TOKEN_CONTAINER`
+//   where they start, and recursion says which container it points back to, which a schema needs
+//   but a fingerprint can't have since it would change every existing fingerprint.
+pub(crate) fn trace<'de, T: Deserialize<'de>>(names: bool) -> Result<Trace> {
+    let mut tracer = Tracer {
+        enums: Vec::new(),
+        containers: Vec::new(),
+        frames: Vec::new(),
+        description: Vec::new(),
+        muted: 0,
+        target: None,
+        aborted: false,
+        names: if names { Some(Vec::new()) } else { None },
+    };
+    let mut main = None;
+
+    // Every pass traces one more variant of some enum, until every variant has been traced once
+    loop {
+        tracer.containers.clear();
+        tracer.frames.clear();
+        tracer.description.clear();
+        tracer.muted = 0;
+        tracer.aborted = false;
+
+        let result = T::deserialize(&mut tracer);
+
+        if !tracer.aborted {
+            result?;
+
+            if main.is_none() {
+                main = Some(mem::take(&mut tracer.description));
+            }
+
+            match tracer.next_target()? {
+                Some(target) => tracer.target = Some(target),
+                None => break,
+            }
+        }
+    }
+
+    let enums = tracer.enums.into_iter()
+        .map(|entry| {
+            let contents = entry.contents.into_iter().map(Option::unwrap_or_default).collect();
+
+            (entry.variants, contents)
+        })
+        .collect();
+
+    Ok(Trace {
+        main: main.unwrap_or_default(),
+        enums,
+        containers: tracer.names.unwrap_or_default(),
+    })
+}
+
+
+// 64-bit FNV-1a, which is simple and stable everywhere
+struct Fnv {
+    state: u64,
+}
+
+impl Fnv {
+    #[inline]
+    fn new() -> Self {
+        Self {
+            state: FNV_OFFSET,
+        }
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.state ^= byte as u64;
+            self.state = self.state.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    #[inline]
+    fn finish(&self) -> u64 {
+        self.state
+    }
+}
+
+
+// Everything known so far about an enum, which is described once after the rest of the type
+struct EnumEntry {
+    name: &'static str,
+    variants: &'static [&'static str],
+    // The description of every variant that has been traced
+    contents: Vec<Option<Vec<u8>>>,
+    // Variants that led back into the enum before there was a way out of it
+    deferred: Vec<bool>,
+    // A variant that doesn't lead back into the enum, for ending recursion
+    escape: Option<u32>,
+}
+
+// A struct or enum whose contents are being traced
+struct Container {
+    name: &'static str,
+    members: &'static [&'static str],
+}
+
+// An enum variant whose contents are being traced into their own description
+struct Frame {
+    entry: usize,
+    variant: u32,
+    // Where the enum is in the container stack
+    depth: usize,
+    recursed: bool,
+    description: Vec<u8>,
+}
+
+// A deserializer that records every type it's asked for instead of reading anything
+struct Tracer {
+    enums: Vec<EnumEntry>,
+    containers: Vec<Container>,
+    frames: Vec<Frame>,
+    description: Vec<u8>,
+    // Contents of a recursive type are traced to build a value, but not described again
+    muted: usize,
+    // The enum variant that the current pass is meant to trace
+    target: Option<(usize, u32)>,
+    aborted: bool,
+    // Every container that's been named so far, when tracing for a schema
+    names: Option<Vec<(&'static str, &'static [&'static str])>>,
+}
+
+impl Tracer {
+    fn put(&mut self, bytes: &[u8]) {
+        if self.muted == 0 {
+            match self.frames.last_mut() {
+                Some(frame) => frame.description.extend_from_slice(bytes),
+                None => self.description.extend_from_slice(bytes),
+            }
+        }
+    }
+
+    #[inline]
+    fn put_token(&mut self, token: u8) {
+        self.put(&[token]);
+    }
+
+    fn put_len(&mut self, len: usize) {
+        self.put(&(len as u32).to_le_bytes());
+    }
+
+    fn put_names(&mut self, names: &[&str]) {
+        self.put_len(names.len());
+
+        for name in names {
+            self.put_len(name.len());
+            self.put(name.as_bytes());
+        }
+    }
+
+    // Finds the index of a container's name, when tracing for a schema
+    fn name_container(&mut self, name: &'static str, members: &'static [&'static str])
+        -> Option<usize>
+    {
+        let names = self.names.as_mut()?;
+        let index = names.iter().position(|&(other, other_members)| {
+            other == name && other_members == members
+        });
+
+        Some(index.unwrap_or_else(|| {
+            names.push((name, members));
+            names.len() - 1
+        }))
+    }
+
+    // Finds a container that's already being traced, which means the type is recursive
+    fn find_container(&self, name: &'static str, members: &'static [&'static str])
+        -> Option<usize>
+    {
+        self.containers.iter().rposition(|container| {
+            container.name == name && container.members == members
+        })
+    }
+
+    // Starts tracing the contents of a container, returning whether it's recursive
+    fn enter(&mut self, name: &'static str, members: &'static [&'static str]) -> Result<bool> {
+        if self.containers.len() >= MAX_DEPTH {
+            return Err(Error::UntraceableType(name));
+        }
+
+        let recursive = match self.find_container(name, members) {
+            Some(depth) => {
+                // Recursion is described by how far up the container it points back to is
+                let distance = self.containers.len() - depth;
+
+                self.put_token(TOKEN_RECURSION);
+                self.put_len(distance);
+
+                if let Some(index) = self.name_container(name, members) {
+                    self.put_len(index);
+                }
+
+                // Every variant that's part of the loop can't be used to end it
+                for frame in &mut self.frames {
+                    if frame.depth >= depth {
+                        frame.recursed = true;
+                    }
+                }
+
+                self.muted += 1;
+
+                true
+            }
+            None => {
+                if let Some(index) = self.name_container(name, members) {
+                    self.put_token(TOKEN_CONTAINER);
+                    self.put_len(index);
+                }
+
+                false
+            }
+        };
+
+        self.containers.push(Container {
+            name,
+            members,
+        });
+
+        Ok(recursive)
+    }
+
+    fn leave(&mut self, recursive: bool) {
+        self.containers.pop();
+
+        if recursive {
+            self.muted -= 1;
+        }
+    }
+
+    fn find_enum(&self, name: &'static str, variants: &'static [&'static str]) -> Option<usize> {
+        self.enums.iter().position(|entry| entry.name == name && entry.variants == variants)
+    }
+
+    // Picks which variant of an enum to trace this time
+    fn choose_variant(&mut self, entry: usize, recursive: bool) -> Result<u32> {
+        let enum_entry = &self.enums[entry];
+        let traced = enum_entry.contents.iter().position(Option::is_some).map(|i| i as u32);
+        let untried = enum_entry.deferred.iter().position(|deferred| !deferred).map(|i| i as u32);
+
+        let chosen = if recursive {
+            enum_entry.escape
+        } else if self.muted > 0 {
+            Some(enum_entry.escape.or(traced).or(untried).unwrap_or(0))
+        } else {
+            match self.target {
+                Some((target, variant)) if target == entry => Some(variant),
+                _ => enum_entry.escape.or(traced).or(untried),
+            }
+        };
+
+        if let Some(variant) = chosen {
+            return Ok(variant);
+        }
+
+        // There's no known way out of the enum yet, so put off the variant that led back into it
+        //   and try again with another one
+        if recursive {
+            if let Some(index) = self.frames.iter().position(|frame| frame.entry == entry) {
+                let variant = self.frames[index].variant as usize;
+
+                if !self.enums[entry].deferred[variant] {
+                    self.enums[entry].deferred[variant] = true;
+                    self.aborted = true;
+                }
+            }
+        }
+
+        Err(Error::UntraceableType(self.enums[entry].name))
+    }
+
+    // Finds the next variant that hasn't been traced yet
+    fn next_target(&self) -> Result<Option<(usize, u32)>> {
+        for (index, entry) in self.enums.iter().enumerate() {
+            let untraced = entry.contents.iter().zip(&entry.deferred)
+                .position(|(contents, &deferred)| {
+                    contents.is_none() && (!deferred || entry.escape.is_some())
+                });
+
+            if let Some(variant) = untraced {
+                return Ok(Some((index, variant as u32)));
+            }
+
+            if entry.contents.iter().any(Option::is_none) {
+                // Only variants that lead back into the enum are left, and none of the others
+                //   are a way out of it
+                return Err(Error::UntraceableType(entry.name));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn trace_enum<'de, V>(&mut self, name: &'static str, variants: &'static [&'static str],
+        visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>
+    {
+        if variants.is_empty() {
+            return Err(Error::UntraceableType(name));
+        }
+
+        let entry = match self.find_enum(name, variants) {
+            Some(entry) => entry,
+            None if self.muted == 0 => {
+                self.enums.push(EnumEntry {
+                    name,
+                    variants,
+                    contents: vec![None; variants.len()],
+                    deferred: vec![false; variants.len()],
+                    escape: None,
+                });
+
+                self.enums.len() - 1
+            }
+            // Enums that have only been seen inside a recursive type just pick their first
+            //   variant, since they'll get traced properly wherever they're described
+            None => {
+                let recursive = self.enter(name, variants)?;
+                let value = visitor.visit_enum(TraceEnum {
+                    tracer: &mut *self,
+                    variant: 0,
+                });
+
+                self.leave(recursive);
+
+                return value;
+            }
+        };
+
+        self.put_token(TOKEN_ENUM);
+        self.put_len(entry);
+
+        let depth = self.containers.len();
+        let recursive = self.enter(name, variants)?;
+        let value = self.choose_variant(entry, recursive).and_then(|variant| {
+            let framed = self.muted == 0;
+
+            if framed {
+                self.frames.push(Frame {
+                    entry,
+                    variant,
+                    depth,
+                    recursed: false,
+                    description: Vec::new(),
+                });
+            }
+
+            let value = visitor.visit_enum(TraceEnum {
+                tracer: &mut *self,
+                variant,
+            });
+
+            if framed {
+                if let Some(frame) = self.frames.pop() {
+                    if value.is_ok() {
+                        let enum_entry = &mut self.enums[frame.entry];
+                        let slot = &mut enum_entry.contents[frame.variant as usize];
+
+                        if slot.is_none() {
+                            *slot = Some(frame.description);
+                        }
+
+                        if !frame.recursed && enum_entry.escape.is_none() {
+                            enum_entry.escape = Some(frame.variant);
+                        }
+                    }
+                }
+            }
+
+            value
+        });
+
+        self.leave(recursive);
+
+        value
+    }
+}
+
+impl<'de> Deserializer<'de> for &mut Tracer {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        // Self-describing types can't say what they want, so they just get a unit
+        self.put_token(TOKEN_ANY);
+        visitor.visit_unit()
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.put_token(TOKEN_BOOL);
+        visitor.visit_bool(false)
+    }
+
+    fn deserialize_i8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.put_token(TOKEN_I8);
+        visitor.visit_i8(1)
+    }
+
+    fn deserialize_i16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.put_token(TOKEN_I16);
+        visitor.visit_i16(1)
+    }
+
+    fn deserialize_i32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.put_token(TOKEN_I32);
+        visitor.visit_i32(1)
+    }
+
+    fn deserialize_i64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.put_token(TOKEN_I64);
+        visitor.visit_i64(1)
+    }
+
+    fn deserialize_i128<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.put_token(TOKEN_I128);
+        visitor.visit_i128(1)
+    }
+
+    fn deserialize_u8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.put_token(TOKEN_U8);
+        visitor.visit_u8(1)
+    }
+
+    fn deserialize_u16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.put_token(TOKEN_U16);
+        visitor.visit_u16(1)
+    }
+
+    fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.put_token(TOKEN_U32);
+        visitor.visit_u32(1)
+    }
+
+    fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.put_token(TOKEN_U64);
+        visitor.visit_u64(1)
+    }
+
+    fn deserialize_u128<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.put_token(TOKEN_U128);
+        visitor.visit_u128(1)
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.put_token(TOKEN_F32);
+        visitor.visit_f32(0.0)
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.put_token(TOKEN_F64);
+        visitor.visit_f64(0.0)
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.put_token(TOKEN_CHAR);
+        visitor.visit_char('\0')
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.put_token(TOKEN_STR);
+        visitor.visit_borrowed_str("")
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.put_token(TOKEN_BYTES);
+        visitor.visit_borrowed_bytes(&[])
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.put_token(TOKEN_OPTION);
+
+        if self.muted > 0 {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.put_token(TOKEN_UNIT);
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V)
+        -> Result<V::Value>
+    {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, name: &'static str, visitor: V)
+        -> Result<V::Value>
+    {
+        // Newtypes are encoded as whatever they wrap, but they can still be recursive
+        let recursive = self.enter(name, &[])?;
+        let value = visitor.visit_newtype_struct(&mut *self);
+
+        self.leave(recursive);
+
+        value
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.put_token(TOKEN_SEQ);
+
+        let len = if self.muted > 0 { 0 } else { 1 };
+
+        visitor.visit_seq(TraceSeq {
+            tracer: self,
+            left: len,
+        })
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value> {
+        self.put_token(TOKEN_TUPLE);
+        self.put_len(len);
+
+        visitor.visit_seq(TraceSeq {
+            tracer: self,
+            left: len,
+        })
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(self, name: &'static str, len: usize,
+        visitor: V) -> Result<V::Value>
+    {
+        let recursive = self.enter(name, &[])?;
+
+        if !recursive {
+            self.put_token(TOKEN_TUPLE);
+            self.put_len(len);
+        }
+
+        let value = visitor.visit_seq(TraceSeq {
+            tracer: &mut *self,
+            left: len,
+        });
+
+        self.leave(recursive);
+
+        value
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.put_token(TOKEN_MAP);
+
+        let len = if self.muted > 0 { 0 } else { 1 };
+
+        visitor.visit_map(TraceSeq {
+            tracer: self,
+            left: len,
+        })
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(self, name: &'static str,
+        fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+    {
+        let recursive = self.enter(name, fields)?;
+
+        if !recursive {
+            self.put_token(TOKEN_STRUCT);
+            self.put_names(fields);
+        }
+
+        let value = visitor.visit_seq(TraceSeq {
+            tracer: &mut *self,
+            left: fields.len(),
+        });
+
+        self.leave(recursive);
+
+        value
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(self, name: &'static str,
+        variants: &'static [&'static str], visitor: V) -> Result<V::Value>
+    {
+        self.trace_enum(name, variants, visitor)
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.put_token(TOKEN_IDENTIFIER);
+        visitor.visit_u32(0)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    #[inline]
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+// Hands out a fixed number of placeholder elements or entries
+struct TraceSeq<'a> {
+    tracer: &'a mut Tracer,
+    left: usize,
+}
+
+impl<'de, 'a> SeqAccess<'de> for TraceSeq<'a> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: DeserializeSeed<'de>
+    {
+        if self.left == 0 {
+            Ok(None)
+        } else {
+            self.left -= 1;
+            seed.deserialize(&mut *self.tracer).map(Some)
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.left)
+    }
+}
+
+impl<'de, 'a> MapAccess<'de> for TraceSeq<'a> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    where
+        K: DeserializeSeed<'de>
+    {
+        if self.left == 0 {
+            Ok(None)
+        } else {
+            self.left -= 1;
+            seed.deserialize(&mut *self.tracer).map(Some)
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+    where
+        V: DeserializeSeed<'de>
+    {
+        seed.deserialize(&mut *self.tracer)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.left)
+    }
+}
+
+// Hands out whichever variant the tracer picked
+struct TraceEnum<'a> {
+    tracer: &'a mut Tracer,
+    variant: u32,
+}
+
+impl<'de, 'a> EnumAccess<'de> for TraceEnum<'a> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self)>
+    where
+        V: DeserializeSeed<'de>
+    {
+        let variant: de::value::U32Deserializer<Error> = self.variant.into_deserializer();
+
+        Ok((seed.deserialize(variant)?, self))
+    }
+}
+
+impl<'de, 'a> VariantAccess<'de> for TraceEnum<'a> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        self.tracer.put_token(TOKEN_UNIT_VARIANT);
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
+    where
+        T: DeserializeSeed<'de>
+    {
+        self.tracer.put_token(TOKEN_NEWTYPE_VARIANT);
+        seed.deserialize(self.tracer)
+    }
+
+    fn tuple_variant<V>(self, len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>
+    {
+        self.tracer.put_token(TOKEN_TUPLE_VARIANT);
+        self.tracer.put_len(len);
+
+        visitor.visit_seq(TraceSeq {
+            tracer: self.tracer,
+            left: len,
+        })
+    }
+
+    fn struct_variant<V>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>
+    {
+        self.tracer.put_token(TOKEN_STRUCT_VARIANT);
+        self.tracer.put_names(fields);
+
+        visitor.visit_seq(TraceSeq {
+            tracer: self.tracer,
+            left: fields.len(),
+        })
+    }
+}
@@ -0,0 +1,95 @@
+use error::*;
+use ser::SerializerOptions;
+
+
+// Every header starts with these bytes, so that other formats (or garbage) get caught before any
+//   of it is parsed as DBOR
+pub(crate) const MAGIC: [u8; 4] = *b"DBOR";
+
+// The only version of the format so far
+pub(crate) const VERSION: u8 = 1;
+
+pub(crate) const HEADER_LEN: usize = 6;
+
+// Fingerprints come right after the header when its flags say there is one
+pub(crate) const FINGERPRINT_LEN: usize = 8;
+
+const FLAG_ALIGN_PACKED: u8 = 0b00000001;
+const FLAG_SHRINK_FLOATS: u8 = 0b00000010;
+const FLAG_INTERN_STRINGS: u8 = 0b00000100;
+const FLAG_CANONICAL: u8 = 0b00001000;
+const FLAG_CHECKSUM: u8 = 0b00010000;
+const FLAG_FINGERPRINT: u8 = 0b00100000;
+const FLAG_FIELD_IDS: u8 = 0b01000000;
+
+const KNOWN_FLAGS: u8 = FLAG_ALIGN_PACKED | FLAG_SHRINK_FLOATS | FLAG_INTERN_STRINGS |
+    FLAG_CANONICAL | FLAG_CHECKSUM | FLAG_FINGERPRINT | FLAG_FIELD_IDS;
+
+// Builds the header for data written with the given options, which should already have been
+//   adjusted by the serializer (so canonical data never claims to be aligned or interned)
+pub(crate) fn encode(options: &SerializerOptions) -> [u8; HEADER_LEN] {
+    let mut flags = 0;
+
+    if options.align_packed {
+        flags |= FLAG_ALIGN_PACKED;
+    }
+    if options.shrink_floats {
+        flags |= FLAG_SHRINK_FLOATS;
+    }
+    if options.intern_strings {
+        flags |= FLAG_INTERN_STRINGS;
+    }
+    if options.canonical {
+        flags |= FLAG_CANONICAL;
+    }
+    if options.checksum {
+        flags |= FLAG_CHECKSUM;
+    }
+    if options.fingerprint.is_some() {
+        flags |= FLAG_FINGERPRINT;
+    }
+    if options.field_ids {
+        flags |= FLAG_FIELD_IDS;
+    }
+
+    [MAGIC[0], MAGIC[1], MAGIC[2], MAGIC[3], VERSION, flags]
+}
+
+// Checks a header and returns the options that the data after it was written with, leaving out
+//   the fingerprint (see `has_fingerprint`)
+pub(crate) fn decode(header: &[u8]) -> Result<SerializerOptions> {
+    if header.len() < HEADER_LEN {
+        return Err(Error::Eof);
+    }
+
+    if header[..4] != MAGIC {
+        return Err(Error::InvalidMagic);
+    }
+
+    if header[4] != VERSION {
+        return Err(Error::UnsupportedVersion(header[4]));
+    }
+
+    // Flags from a newer version might mean the data uses encodings that this version can't read
+    let flags = header[5];
+
+    if flags & !KNOWN_FLAGS != 0 {
+        return Err(Error::UnknownHeaderFlags(flags & !KNOWN_FLAGS));
+    }
+
+    Ok(SerializerOptions {
+        align_packed: flags & FLAG_ALIGN_PACKED != 0,
+        shrink_floats: flags & FLAG_SHRINK_FLOATS != 0,
+        intern_strings: flags & FLAG_INTERN_STRINGS != 0,
+        canonical: flags & FLAG_CANONICAL != 0,
+        checksum: flags & FLAG_CHECKSUM != 0,
+        fingerprint: None,
+        field_ids: flags & FLAG_FIELD_IDS != 0,
+    })
+}
+
+// Whether a header that's already been checked is followed by a fingerprint
+#[inline]
+pub(crate) fn has_fingerprint(header: &[u8]) -> bool {
+    header[5] & FLAG_FINGERPRINT != 0
+}
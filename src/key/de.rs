@@ -0,0 +1,528 @@
+use alloc::vec::Vec;
+
+use serde::de::{self, DeserializeSeed, Visitor, SeqAccess, MapAccess, EnumAccess, VariantAccess,
+    IntoDeserializer};
+
+use error::*;
+
+
+/// A structure that deserializes order-preserving keys into Rust values
+pub struct Deserializer<'de> {
+    input: &'de [u8],
+    index: usize,
+}
+
+impl<'de> Deserializer<'de> {
+    /// Creates a key deserializer from a slice
+    #[inline]
+    pub fn from_slice(bytes: &'de [u8]) -> Self {
+        Self {
+            input: bytes,
+            index: 0,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn finished(&self) -> bool {
+        self.index >= self.input.len()
+    }
+
+    #[inline]
+    fn next(&mut self) -> Result<u8> {
+        match self.input.get(self.index) {
+            Some(&byte) => {
+                self.index += 1;
+
+                Ok(byte)
+            }
+            None => Err(Error::Eof),
+        }
+    }
+
+    #[inline]
+    fn read(&mut self, len: usize) -> Result<&'de [u8]> {
+        if self.input.len() - self.index < len {
+            return Err(Error::Eof);
+        }
+
+        let bytes = &self.input[self.index..self.index + len];
+
+        self.index += len;
+
+        Ok(bytes)
+    }
+
+    // Reads `len` big endian bytes into the low bytes of a number, with the rest of the bytes
+    //   set to `fill`
+    #[inline]
+    fn read_be(&mut self, len: usize, fill: u8) -> Result<u64> {
+        let mut buf = [fill; 8];
+
+        buf[8 - len..].copy_from_slice(self.read(len)?);
+
+        Ok(u64::from_be_bytes(buf))
+    }
+
+    fn get_uint(&mut self) -> Result<u64> {
+        match self.next()? {
+            len @ 0..=8 => self.read_be(len as usize, 0),
+            byte => Err(Error::InvalidKeyByte(byte)),
+        }
+    }
+
+    fn get_int(&mut self) -> Result<i64> {
+        match self.next()? {
+            byte @ 0x80..=0x88 => Ok(self.read_be((byte - 0x80) as usize, 0)? as i64),
+            byte @ 0x78..=0x7f => Ok(self.read_be((0x80 - byte) as usize, 0xff)? as i64),
+            byte => Err(Error::InvalidKeyByte(byte)),
+        }
+    }
+
+    fn get_float(&mut self) -> Result<f64> {
+        let bits = self.read_be(8, 0)?;
+
+        let bits = if bits >> 63 == 1 {
+            bits ^ (1 << 63)
+        } else {
+            !bits
+        };
+
+        Ok(f64::from_bits(bits))
+    }
+
+    // Reads a `0x00` or `0x01` marker byte
+    #[inline]
+    fn get_marker(&mut self) -> Result<bool> {
+        match self.next()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            byte => Err(Error::InvalidKeyByte(byte)),
+        }
+    }
+}
+
+impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, _visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>
+    {
+        Err(Error::NotSelfDescribing)
+    }
+
+    #[inline]
+    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>
+    {
+        visitor.visit_bool(self.get_marker()?)
+    }
+
+    #[inline]
+    fn deserialize_i8<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>
+    {
+        self.deserialize_i64(visitor)
+    }
+
+    #[inline]
+    fn deserialize_i16<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>
+    {
+        self.deserialize_i64(visitor)
+    }
+
+    #[inline]
+    fn deserialize_i32<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>
+    {
+        self.deserialize_i64(visitor)
+    }
+
+    #[inline]
+    fn deserialize_i64<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>
+    {
+        visitor.visit_i64(self.get_int()?)
+    }
+
+    #[inline]
+    fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>
+    {
+        self.deserialize_u64(visitor)
+    }
+
+    #[inline]
+    fn deserialize_u16<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>
+    {
+        self.deserialize_u64(visitor)
+    }
+
+    #[inline]
+    fn deserialize_u32<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>
+    {
+        self.deserialize_u64(visitor)
+    }
+
+    #[inline]
+    fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>
+    {
+        visitor.visit_u64(self.get_uint()?)
+    }
+
+    #[inline]
+    fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>
+    {
+        // Only ever written from an `f32`, so this doesn't lose anything
+        visitor.visit_f32(self.get_float()? as f32)
+    }
+
+    #[inline]
+    fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>
+    {
+        visitor.visit_f64(self.get_float()?)
+    }
+
+    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>
+    {
+        let code = self.get_uint()?;
+
+        if code > u32::MAX as u64 {
+            return Err(Error::FailedToParseChar);
+        }
+
+        visitor.visit_char(core::char::from_u32(code as u32).ok_or(Error::FailedToParseChar)?)
+    }
+
+    #[inline]
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>
+    {
+        self.deserialize_bytes(visitor)
+    }
+
+    #[inline]
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>
+    {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>
+    {
+        let start = self.index;
+        let mut unescaped: Option<Vec<u8>> = None;
+
+        loop {
+            let byte = self.next()?;
+
+            if byte != 0 {
+                if let Some(ref mut buf) = unescaped {
+                    buf.push(byte);
+                }
+
+                continue;
+            }
+
+            match self.next()? {
+                0x01 => break,
+                0xff => {
+                    // Escaped zero, which means the bytes can't be borrowed anymore
+                    unescaped.get_or_insert_with(|| self.input[start..self.index - 2].to_vec())
+                        .push(0);
+                }
+                byte => return Err(Error::InvalidKeyByte(byte)),
+            }
+        }
+
+        match unescaped {
+            Some(buf) => visitor.visit_byte_buf(buf),
+            None => visitor.visit_borrowed_bytes(&self.input[start..self.index - 2]),
+        }
+    }
+
+    #[inline]
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>
+    {
+        self.deserialize_bytes(visitor)
+    }
+
+    #[inline]
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>
+    {
+        if self.get_marker()? {
+            visitor.visit_some(self)
+        } else {
+            visitor.visit_none()
+        }
+    }
+
+    #[inline]
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>
+    {
+        visitor.visit_unit()
+    }
+
+    #[inline]
+    fn deserialize_unit_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>
+    {
+        visitor.visit_unit()
+    }
+
+    #[inline]
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    #[inline]
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>
+    {
+        visitor.visit_seq(MarkedVisitor::new(self))
+    }
+
+    #[inline]
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>
+    {
+        visitor.visit_seq(FixedVisitor::new(self, len))
+    }
+
+    #[inline]
+    fn deserialize_tuple_struct<V>(self, _name: &'static str, len: usize, visitor: V)
+        -> Result<V::Value>
+    where
+        V: Visitor<'de>
+    {
+        visitor.visit_seq(FixedVisitor::new(self, len))
+    }
+
+    #[inline]
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>
+    {
+        visitor.visit_map(MarkedVisitor::new(self))
+    }
+
+    #[inline]
+    fn deserialize_struct<V>(self, _name: &'static str, fields: &'static [&'static str],
+        visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>
+    {
+        visitor.visit_seq(FixedVisitor::new(self, fields.len()))
+    }
+
+    #[inline]
+    fn deserialize_enum<V>(self, _name: &'static str, _variants: &'static [&'static str],
+        visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>
+    {
+        visitor.visit_enum(self)
+    }
+
+    #[inline]
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>
+    {
+        self.deserialize_u64(visitor)
+    }
+
+    fn deserialize_ignored_any<V>(self, _visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>
+    {
+        Err(Error::NotSelfDescribing)
+    }
+
+    #[inline]
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+// Visits a seq or map where every item has a marker in front of it
+struct MarkedVisitor<'a, 'de: 'a> {
+    de: &'a mut Deserializer<'de>,
+}
+
+impl<'a, 'de> MarkedVisitor<'a, 'de> {
+    #[inline]
+    fn new(de: &'a mut Deserializer<'de>) -> Self {
+        Self {
+            de,
+        }
+    }
+}
+
+impl<'a, 'de> SeqAccess<'de> for MarkedVisitor<'a, 'de> {
+    type Error = Error;
+
+    #[inline]
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: DeserializeSeed<'de>
+    {
+        if self.de.get_marker()? {
+            seed.deserialize(&mut *self.de).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl<'a, 'de> MapAccess<'de> for MarkedVisitor<'a, 'de> {
+    type Error = Error;
+
+    #[inline]
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    where
+        K: DeserializeSeed<'de>
+    {
+        if self.de.get_marker()? {
+            seed.deserialize(&mut *self.de).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    #[inline]
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+    where
+        V: DeserializeSeed<'de>
+    {
+        seed.deserialize(&mut *self.de)
+    }
+}
+
+// Visits a tuple or struct, where the number of fields is known ahead of time
+struct FixedVisitor<'a, 'de: 'a> {
+    de: &'a mut Deserializer<'de>,
+    len: usize,
+}
+
+impl<'a, 'de> FixedVisitor<'a, 'de> {
+    #[inline]
+    fn new(de: &'a mut Deserializer<'de>, len: usize) -> Self {
+        Self {
+            de,
+            len,
+        }
+    }
+}
+
+impl<'a, 'de> SeqAccess<'de> for FixedVisitor<'a, 'de> {
+    type Error = Error;
+
+    #[inline]
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: DeserializeSeed<'de>
+    {
+        if self.len == 0 {
+            return Ok(None);
+        }
+
+        self.len -= 1;
+
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.len)
+    }
+}
+
+impl<'de> EnumAccess<'de> for &mut Deserializer<'de> {
+    type Error = Error;
+    type Variant = Self;
+
+    #[inline]
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant)>
+    where
+        V: DeserializeSeed<'de>
+    {
+        let index = self.get_uint()?;
+
+        if index > u32::MAX as u64 {
+            return Err(Error::UsizeOverflow);
+        }
+
+        let value = seed.deserialize((index as u32).into_deserializer())?;
+
+        Ok((value, self))
+    }
+}
+
+impl<'de> VariantAccess<'de> for &mut Deserializer<'de> {
+    type Error = Error;
+
+    #[inline]
+    fn unit_variant(self) -> Result<()> {
+        Ok(())
+    }
+
+    #[inline]
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
+    where
+        T: DeserializeSeed<'de>
+    {
+        seed.deserialize(self)
+    }
+
+    #[inline]
+    fn tuple_variant<V>(self, len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>
+    {
+        visitor.visit_seq(FixedVisitor::new(self, len))
+    }
+
+    #[inline]
+    fn struct_variant<V>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>
+    {
+        visitor.visit_seq(FixedVisitor::new(self, fields.len()))
+    }
+}
@@ -0,0 +1,66 @@
+//! An order-preserving encoding for keys in ordered key-value stores
+//!
+//! Regular DBOR stores numbers in little endian and puts lengths in front of strings, so encoded
+//! values don't sort in the same order as the values themselves. Keys encoded with this module
+//! do: comparing two encoded keys byte by byte gives the same result as comparing the original
+//! values, as long as both keys have the same type. For tuples and structs that means comparing
+//! field by field, and for enums that means comparing the variant first, just like
+//! `#[derive(PartialOrd, Ord)]`.
+//!
+//! The encoding isn't self-describing, so keys can only be deserialized into the type they were
+//! written as (or one with the same shape). Integers of different sizes share an encoding, which
+//! means a `u16` field can be widened to a `u64` later on without changing any keys.
+//!
+//! - Unsigned integers (and `char`s) are a byte with the number of bytes that follow (`0-8`),
+//!   followed by the number in big endian without its leading zeros
+//! - Signed integers are written the same way with the count byte offset by `0x80`, where
+//!   negative numbers count downwards from `0x7f` and are stored relative to the smallest number
+//!   of that size, so `-1` is `0x7f 0xff`
+//! - Floats are always 8 bytes of big endian `f64`, with the sign bit flipped for positive
+//!   numbers and every bit flipped for negative numbers (so `-0.0` sorts before `0.0`, and NaNs
+//!   sort at either end)
+//! - Strings and bytes have every `0x00` byte replaced with `0x00 0xff`, and end with
+//!   `0x00 0x01`
+//! - `bool`s and `Option`s are `0x00` for false or `None`, and `0x01` for true or `Some` followed
+//!   by the value
+//! - Tuples, structs, and units are just their fields one after another, with nothing in between
+//! - Seqs and maps have `0x01` in front of every item (or entry) and end with `0x00`
+//! - Enums are the variant index as an unsigned integer followed by the variant's content
+
+use alloc::vec::Vec;
+
+use serde::{Serialize, Deserialize};
+
+mod ser;
+mod de;
+
+use error::*;
+
+pub use self::ser::Serializer;
+pub use self::de::Deserializer;
+
+
+/// Serialize the given data structure as an order-preserving key
+pub fn to_vec<T>(value: &T) -> Result<Vec<u8>>
+where
+    T: ?Sized + Serialize
+{
+    let mut serializer = Serializer::new();
+    value.serialize(&mut serializer)?;
+    Ok(serializer.into_vec())
+}
+
+/// Deserialize an instance of type T from an order-preserving key
+pub fn from_slice<'de, T>(bytes: &'de [u8]) -> Result<T>
+where
+    T: Deserialize<'de>
+{
+    let mut deserializer = Deserializer::from_slice(bytes);
+    let t = T::deserialize(&mut deserializer)?;
+
+    if deserializer.finished() {
+        Ok(t)
+    } else {
+        Err(Error::TrailingBytes)
+    }
+}
@@ -0,0 +1,392 @@
+use core::cmp;
+use alloc::vec::Vec;
+
+use serde::ser::{self, Serialize};
+
+use error::*;
+
+
+/// A structure for serializing Rust values into order-preserving keys
+#[derive(Default)]
+pub struct Serializer {
+    output: Vec<u8>,
+}
+
+impl Serializer {
+    /// Creates a key serializer with an empty output
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            output: Vec::new(),
+        }
+    }
+
+    /// Returns the key that has been written so far
+    #[inline]
+    pub fn into_vec(self) -> Vec<u8> {
+        self.output
+    }
+
+    // Writes the byte count and the big endian bytes of a number, skipping leading zeros
+    fn put_uint(&mut self, v: u64) {
+        let len = 8 - v.leading_zeros() as usize / 8;
+
+        self.output.push(len as u8);
+        self.output.extend_from_slice(&v.to_be_bytes()[8 - len..]);
+    }
+
+    fn put_int(&mut self, v: i64) {
+        if v >= 0 {
+            let len = 8 - v.leading_zeros() as usize / 8;
+
+            self.output.push(0x80 + len as u8);
+            self.output.extend_from_slice(&v.to_be_bytes()[8 - len..]);
+        } else {
+            // Negative numbers skip leading `0xff` bytes instead, but always keep at least one
+            //   byte so that `-1` doesn't look like `0`
+            let len = cmp::max(8 - (!v).leading_zeros() as usize / 8, 1);
+
+            self.output.push(0x80 - len as u8);
+            self.output.extend_from_slice(&v.to_be_bytes()[8 - len..]);
+        }
+    }
+}
+
+impl ser::Serializer for &mut Serializer {
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Self;
+    type SerializeMap = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Self;
+
+    #[inline]
+    fn serialize_bool(self, v: bool) -> Result<()> {
+        self.output.push(v as u8);
+        Ok(())
+    }
+
+    #[inline]
+    fn serialize_i8(self, v: i8) -> Result<()> {
+        self.serialize_i64(v as i64)
+    }
+
+    #[inline]
+    fn serialize_i16(self, v: i16) -> Result<()> {
+        self.serialize_i64(v as i64)
+    }
+
+    #[inline]
+    fn serialize_i32(self, v: i32) -> Result<()> {
+        self.serialize_i64(v as i64)
+    }
+
+    #[inline]
+    fn serialize_i64(self, v: i64) -> Result<()> {
+        self.put_int(v);
+        Ok(())
+    }
+
+    #[inline]
+    fn serialize_u8(self, v: u8) -> Result<()> {
+        self.serialize_u64(v as u64)
+    }
+
+    #[inline]
+    fn serialize_u16(self, v: u16) -> Result<()> {
+        self.serialize_u64(v as u64)
+    }
+
+    #[inline]
+    fn serialize_u32(self, v: u32) -> Result<()> {
+        self.serialize_u64(v as u64)
+    }
+
+    #[inline]
+    fn serialize_u64(self, v: u64) -> Result<()> {
+        self.put_uint(v);
+        Ok(())
+    }
+
+    #[inline]
+    fn serialize_f32(self, v: f32) -> Result<()> {
+        self.serialize_f64(v as f64)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<()> {
+        let bits = v.to_bits();
+
+        // Flipping the sign bit puts positive numbers above negative ones, and flipping the rest
+        //   of a negative number makes bigger magnitudes sort lower
+        let bits = if bits >> 63 == 0 {
+            bits ^ (1 << 63)
+        } else {
+            !bits
+        };
+
+        self.output.extend_from_slice(&bits.to_be_bytes());
+        Ok(())
+    }
+
+    #[inline]
+    fn serialize_char(self, v: char) -> Result<()> {
+        self.serialize_u64(v as u64)
+    }
+
+    #[inline]
+    fn serialize_str(self, v: &str) -> Result<()> {
+        self.serialize_bytes(v.as_bytes())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<()> {
+        self.output.reserve(v.len() + 2);
+
+        for &byte in v {
+            self.output.push(byte);
+
+            if byte == 0 {
+                self.output.push(0xff);
+            }
+        }
+
+        self.output.extend_from_slice(&[0x00, 0x01]);
+        Ok(())
+    }
+
+    #[inline]
+    fn serialize_none(self) -> Result<()> {
+        self.output.push(0);
+        Ok(())
+    }
+
+    #[inline]
+    fn serialize_some<T>(self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize
+    {
+        self.output.push(1);
+        value.serialize(self)
+    }
+
+    #[inline]
+    fn serialize_unit(self) -> Result<()> {
+        Ok(())
+    }
+
+    #[inline]
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        Ok(())
+    }
+
+    #[inline]
+    fn serialize_unit_variant(self, _name: &'static str, variant_index: u32,
+        _variant: &'static str) -> Result<()>
+    {
+        self.serialize_u64(variant_index as u64)
+    }
+
+    #[inline]
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize
+    {
+        value.serialize(self)
+    }
+
+    #[inline]
+    fn serialize_newtype_variant<T>(self, _name: &'static str, variant_index: u32,
+        _variant: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize
+    {
+        self.put_uint(variant_index as u64);
+        value.serialize(self)
+    }
+
+    #[inline]
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        // Seqs end with a marker instead of starting with a length, so the length doesn't have to
+        //   be known
+        Ok(self)
+    }
+
+    #[inline]
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
+        Ok(self)
+    }
+
+    #[inline]
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize)
+        -> Result<Self::SerializeTupleStruct>
+    {
+        Ok(self)
+    }
+
+    #[inline]
+    fn serialize_tuple_variant(self, _name: &'static str, variant_index: u32,
+        _variant: &'static str, _len: usize) -> Result<Self::SerializeTupleVariant>
+    {
+        self.put_uint(variant_index as u64);
+        Ok(self)
+    }
+
+    #[inline]
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Ok(self)
+    }
+
+    #[inline]
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        Ok(self)
+    }
+
+    #[inline]
+    fn serialize_struct_variant(self, _name: &'static str, variant_index: u32,
+        _variant: &'static str, _len: usize) -> Result<Self::SerializeStructVariant>
+    {
+        self.put_uint(variant_index as u64);
+        Ok(self)
+    }
+}
+
+impl ser::SerializeSeq for &mut Serializer {
+    type Ok = ();
+    type Error = Error;
+
+    #[inline]
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize
+    {
+        self.output.push(1);
+        value.serialize(&mut **self)
+    }
+
+    #[inline]
+    fn end(self) -> Result<()> {
+        self.output.push(0);
+        Ok(())
+    }
+}
+
+impl ser::SerializeTuple for &mut Serializer {
+    type Ok = ();
+    type Error = Error;
+
+    #[inline]
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize
+    {
+        value.serialize(&mut **self)
+    }
+
+    #[inline]
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeTupleStruct for &mut Serializer {
+    type Ok = ();
+    type Error = Error;
+
+    #[inline]
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize
+    {
+        value.serialize(&mut **self)
+    }
+
+    #[inline]
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeTupleVariant for &mut Serializer {
+    type Ok = ();
+    type Error = Error;
+
+    #[inline]
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize
+    {
+        value.serialize(&mut **self)
+    }
+
+    #[inline]
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeMap for &mut Serializer {
+    type Ok = ();
+    type Error = Error;
+
+    #[inline]
+    fn serialize_key<T>(&mut self, key: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize
+    {
+        self.output.push(1);
+        key.serialize(&mut **self)
+    }
+
+    #[inline]
+    fn serialize_value<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize
+    {
+        value.serialize(&mut **self)
+    }
+
+    #[inline]
+    fn end(self) -> Result<()> {
+        self.output.push(0);
+        Ok(())
+    }
+}
+
+impl ser::SerializeStruct for &mut Serializer {
+    type Ok = ();
+    type Error = Error;
+
+    #[inline]
+    fn serialize_field<T>(&mut self, _key: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize
+    {
+        value.serialize(&mut **self)
+    }
+
+    #[inline]
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeStructVariant for &mut Serializer {
+    type Ok = ();
+    type Error = Error;
+
+    #[inline]
+    fn serialize_field<T>(&mut self, _key: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize
+    {
+        value.serialize(&mut **self)
+    }
+
+    #[inline]
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
@@ -33,7 +33,7 @@
 //!     phones: Vec<String>
 //! }
 //!
-//! fn example<'a>(data: &'a [u8]) => Result<(), Error> {
+//! fn example<'a>(data: &'a [u8]) -> Result<(), Error> {
 //!     // Parse the data into a Person object.
 //!     let p: Person = serde_dbor::from_slice(data)?;
 //!
@@ -43,10 +43,25 @@
 //!     Ok(())
 //! }
 //! ```
+//!
+//! # `no_std`
+//! Turning off the default `std` feature makes the crate depend only on `core` and `alloc`.
+//! Reading from and writing to `io` streams, `SystemTime` support, and `Shared` need `std`, but
+//! `from_slice`, `to_vec`, `to_slice`, and everything else still work without it.
+
+#![cfg_attr(not(feature = "std"), no_std)]
 
+#[cfg(feature = "std")]
+extern crate core;
 #[macro_use]
-extern crate lazy_static;
+extern crate alloc;
 extern crate serde;
+#[cfg(feature = "chrono")]
+extern crate chrono as chrono_crate;
+#[cfg(feature = "time")]
+extern crate time as time_crate;
+#[cfg(feature = "derive")]
+extern crate serde_dbor_derive;
 
 /// When serializing or deserializing DBOR goes wrong
 mod error;
@@ -54,22 +69,52 @@ mod error;
 mod de;
 /// Serialize Rust data structure into DBOR data
 mod ser;
+/// Packed numeric arrays that can be borrowed without copying
+mod packed;
+/// Shared pointers that keep their sharing through serialization
+mod shared;
+/// Values marked with semantic tags
+mod tagged;
+/// Compact timestamps and durations
+mod datetime;
+/// Checksums that catch corrupted data
+mod checksum;
+/// Raw encoded values and variants that aren't known about
+mod raw;
+/// Fingerprints of the shapes of types
+mod fingerprint;
+/// Numeric IDs for struct fields
+mod field_ids;
+/// Headers that identify DBOR data and the options it was written with
+mod header;
+/// Helpers for code generated by `serde_dbor_derive`, which aren't part of the public API
+#[doc(hidden)]
+pub mod private;
+/// Order-preserving encoding for keys in ordered key-value stores
+pub mod key;
+/// Schemas that DBOR data can be checked against
+pub mod schema;
 
 pub use de::*;
 pub use ser::*;
 pub use error::*;
+pub use packed::*;
+#[cfg(feature = "std")]
+pub use shared::{Shared, SharedPointer};
+pub use tagged::{Tagged, tags};
+pub use raw::{RawDbor, UnknownVariant, VariantId};
+pub use datetime::{Timestamp, duration};
+pub use fingerprint::fingerprint;
+#[cfg(feature = "std")]
+pub use datetime::system_time;
+#[cfg(feature = "chrono")]
+pub use datetime::chrono;
+#[cfg(feature = "time")]
+pub use datetime::time;
+#[cfg(feature = "derive")]
+pub use serde_dbor_derive::{DborSerialize, DborDeserialize};
 
 
-lazy_static! {
-    static ref WRONG_ENDIANNESS: bool = {
-        let mut num: u16 = 0xff00;
-
-        let first_byte: u8 = unsafe {
-            *(&mut num as *mut u16 as *mut u8)
-        };
-
-        // Although sacrificing readability, x86 uses Little Endian, so it's faster to avoid
-        //   flipping endianness all of the time
-        first_byte == 0xff
-    };
-}
+// Although sacrificing readability, x86 uses Little Endian, so it's faster to avoid flipping
+//   endianness all of the time
+const WRONG_ENDIANNESS: bool = cfg!(target_endian = "big");
@@ -0,0 +1,266 @@
+use alloc::borrow::Cow;
+use alloc::vec::Vec;
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem;
+use core::slice;
+
+use serde::ser::{Serialize, Serializer};
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+
+use super::WRONG_ENDIANNESS;
+
+
+// Newtype names used to tell the DBOR serializer how far a packed array should be aligned. Other
+//   serializers just see a newtype struct wrapping a byte string.
+pub(crate) const ALIGN_2_TOKEN: &str = "$serde_dbor::private::Align2";
+pub(crate) const ALIGN_4_TOKEN: &str = "$serde_dbor::private::Align4";
+pub(crate) const ALIGN_8_TOKEN: &str = "$serde_dbor::private::Align8";
+
+#[inline]
+fn align_token<T>() -> &'static str {
+    match mem::align_of::<T>() {
+        2 => ALIGN_2_TOKEN,
+        4 => ALIGN_4_TOKEN,
+        _ => ALIGN_8_TOKEN,
+    }
+}
+
+mod private {
+    pub trait Sealed {}
+}
+
+/// A number that can be stored inside a packed little endian array
+///
+/// This is only implemented for primitive numbers, where every bit pattern is a valid value.
+pub trait LeNum: Copy + 'static + private::Sealed {
+    #[doc(hidden)]
+    fn from_le_slice(bytes: &[u8]) -> Self;
+
+    #[doc(hidden)]
+    fn extend_le(self, out: &mut Vec<u8>);
+}
+
+macro_rules! impl_le_num {
+    () => {};
+    ($ty:ident $($more:tt)*) => {
+        impl private::Sealed for $ty {}
+
+        impl LeNum for $ty {
+            #[inline]
+            fn from_le_slice(bytes: &[u8]) -> Self {
+                let mut buf = [0; mem::size_of::<$ty>()];
+
+                buf.copy_from_slice(bytes);
+
+                $ty::from_le_bytes(buf)
+            }
+
+            #[inline]
+            fn extend_le(self, out: &mut Vec<u8>) {
+                out.extend_from_slice(&self.to_le_bytes());
+            }
+        }
+
+        impl_le_num!($($more)*);
+    };
+}
+
+impl_le_num! {
+    u16 u32 u64
+    i16 i32 i64
+    f32 f64
+}
+
+
+/// A packed array of little endian numbers
+///
+/// A `LeSlice` is stored as a single DBOR byte string instead of as a seq of individually encoded
+/// numbers. When it is deserialized from a slice, the bytes are borrowed straight from the input,
+/// and if the host is little endian and the data is suitably aligned (see
+/// `SerializerOptions::align_packed`) they can be viewed as a `&[T]` without any copying.
+pub struct LeSlice<'a, T: LeNum> {
+    bytes: Cow<'a, [u8]>,
+    phantom: PhantomData<T>,
+}
+
+impl<'a, T: LeNum> LeSlice<'a, T> {
+    /// Creates a packed array from a slice of numbers, only copying them on big endian hosts
+    pub fn new(values: &'a [T]) -> Self {
+        let bytes = if WRONG_ENDIANNESS {
+            let mut buf = Vec::with_capacity(mem::size_of_val(values));
+
+            for value in values {
+                value.extend_le(&mut buf);
+            }
+
+            Cow::Owned(buf)
+        } else {
+            Cow::Borrowed(unsafe {
+                slice::from_raw_parts(values.as_ptr() as *const u8, mem::size_of_val(values))
+            })
+        };
+
+        Self {
+            bytes,
+            phantom: PhantomData,
+        }
+    }
+
+    /// The number of items in the array
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.bytes.len() / mem::size_of::<T>()
+    }
+
+    /// Whether or not the array has no items
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Gets a single item, no matter how the data is aligned
+    pub fn get(&self, index: usize) -> Option<T> {
+        let size = mem::size_of::<T>();
+
+        if index < self.len() {
+            Some(T::from_le_slice(&self.bytes[index * size..(index + 1) * size]))
+        } else {
+            None
+        }
+    }
+
+    /// Iterates over every item, no matter how the data is aligned
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        self.bytes.chunks_exact(mem::size_of::<T>()).map(T::from_le_slice)
+    }
+
+    /// Copies every item into a new vector
+    pub fn to_vec(&self) -> Vec<T> {
+        self.iter().collect()
+    }
+
+    /// The raw little endian bytes of the array
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Views the array as a native slice, which only works if the host is little endian and the
+    /// data is aligned for `T`
+    pub fn as_slice(&self) -> Option<&[T]> {
+        cast_slice(&self.bytes)
+    }
+
+    /// Views the array as a native slice borrowed straight from the deserializer's input, which
+    /// only works if the data was borrowed, the host is little endian, and the data is aligned
+    /// for `T`
+    pub fn as_borrowed_slice(&self) -> Option<&'a [T]> {
+        match self.bytes {
+            Cow::Borrowed(bytes) => cast_slice(bytes),
+            Cow::Owned(_) => None,
+        }
+    }
+}
+
+fn cast_slice<T: LeNum>(bytes: &[u8]) -> Option<&[T]> {
+    let size = mem::size_of::<T>();
+
+    if WRONG_ENDIANNESS
+        || !bytes.len().is_multiple_of(size)
+        || !(bytes.as_ptr() as usize).is_multiple_of(mem::align_of::<T>())
+    {
+        None
+    } else {
+        // Every `LeNum` is a primitive number, so any bit pattern is a valid value
+        Some(unsafe { slice::from_raw_parts(bytes.as_ptr() as *const T, bytes.len() / size) })
+    }
+}
+
+impl<'a, T: LeNum> Clone for LeSlice<'a, T> {
+    fn clone(&self) -> Self {
+        Self {
+            bytes: self.bytes.clone(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<'a, T: LeNum + fmt::Debug> fmt::Debug for LeSlice<'a, T> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.debug_list().entries(self.iter()).finish()
+    }
+}
+
+
+struct RawBytes<'a>(&'a [u8]);
+
+impl<'a> Serialize for RawBytes<'a> {
+    #[inline]
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+impl<'a, T: LeNum> Serialize for LeSlice<'a, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(align_token::<T>(), &RawBytes(&self.bytes))
+    }
+}
+
+struct LeSliceVisitor<'a, T> {
+    phantom: PhantomData<&'a [T]>,
+}
+
+impl<'de: 'a, 'a, T: LeNum> Visitor<'de> for LeSliceVisitor<'a, T> {
+    type Value = LeSlice<'a, T>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "a packed array of {}-byte numbers", mem::size_of::<T>())
+    }
+
+    fn visit_borrowed_bytes<E: de::Error>(self, bytes: &'de [u8]) -> Result<Self::Value, E> {
+        self.visit_cow(Cow::Borrowed(bytes))
+    }
+
+    fn visit_bytes<E: de::Error>(self, bytes: &[u8]) -> Result<Self::Value, E> {
+        self.visit_cow(Cow::Owned(bytes.to_vec()))
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, bytes: Vec<u8>) -> Result<Self::Value, E> {
+        self.visit_cow(Cow::Owned(bytes))
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>
+    {
+        deserializer.deserialize_bytes(self)
+    }
+}
+
+impl<'a, T: LeNum> LeSliceVisitor<'a, T> {
+    fn visit_cow<E: de::Error>(self, bytes: Cow<'a, [u8]>) -> Result<LeSlice<'a, T>, E> {
+        if !bytes.len().is_multiple_of(mem::size_of::<T>()) {
+            return Err(E::invalid_length(bytes.len(), &self));
+        }
+
+        Ok(LeSlice {
+            bytes,
+            phantom: PhantomData,
+        })
+    }
+}
+
+impl<'de: 'a, 'a, T: LeNum> Deserialize<'de> for LeSlice<'a, T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>
+    {
+        let visitor = LeSliceVisitor {
+            phantom: PhantomData,
+        };
+
+        deserializer.deserialize_newtype_struct(align_token::<T>(), visitor)
+    }
+}
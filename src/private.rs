@@ -0,0 +1,119 @@
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use serde::ser::{Serialize, Serializer};
+use serde::de::{self, Deserialize, Deserializer, Unexpected};
+
+use packed::{LeNum, LeSlice};
+use raw::VariantId;
+
+
+// Enum name used to tell DBOR to write a variant by its name instead of its index. Other
+//   serializers just see a strangely named enum.
+pub const NAMED_VARIANT_TOKEN: &str = "$serde_dbor::private::NamedVariant";
+
+// Newtype names used to turn on `intern_strings` or `shrink_floats` for a single value. Other
+//   serializers just see a newtype struct around the value.
+pub const INTERN_TOKEN: &str = "$serde_dbor::private::Intern";
+pub const SHRINK_FLOATS_TOKEN: &str = "$serde_dbor::private::ShrinkFloats";
+
+// Struct name prefix used to give fields their own IDs, which the derive has to spell out itself
+//   since it builds the whole name at compile time
+pub use field_ids::FIELD_IDS_TOKEN;
+
+
+// Writes a value with strings interned
+pub struct Interned<'a, T: ?Sized + 'a>(pub &'a T);
+
+impl<'a, T: ?Sized + Serialize> Serialize for Interned<'a, T> {
+    #[inline]
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(INTERN_TOKEN, self.0)
+    }
+}
+
+// Writes a value with floats shrunk
+pub struct ShrinkFloats<'a, T: ?Sized + 'a>(pub &'a T);
+
+impl<'a, T: ?Sized + Serialize> Serialize for ShrinkFloats<'a, T> {
+    #[inline]
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(SHRINK_FLOATS_TOKEN, self.0)
+    }
+}
+
+
+// A collection of numbers that can be written as a packed array
+pub trait PackedField: Sized {
+    type Num: LeNum;
+
+    fn as_nums(&self) -> &[Self::Num];
+    fn from_nums(nums: Vec<Self::Num>) -> Self;
+}
+
+impl<T: LeNum> PackedField for Vec<T> {
+    type Num = T;
+
+    #[inline]
+    fn as_nums(&self) -> &[T] {
+        self
+    }
+
+    #[inline]
+    fn from_nums(nums: Vec<T>) -> Self {
+        nums
+    }
+}
+
+impl<T: LeNum> PackedField for Box<[T]> {
+    type Num = T;
+
+    #[inline]
+    fn as_nums(&self) -> &[T] {
+        self
+    }
+
+    #[inline]
+    fn from_nums(nums: Vec<T>) -> Self {
+        nums.into_boxed_slice()
+    }
+}
+
+// Writes a collection of numbers as a `LeSlice`
+pub struct SerializePacked<'a, T: 'a>(pub &'a T);
+
+impl<'a, T: PackedField> Serialize for SerializePacked<'a, T> {
+    #[inline]
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        LeSlice::new(self.0.as_nums()).serialize(serializer)
+    }
+}
+
+// Reads a collection of numbers from a `LeSlice`
+pub struct DeserializePacked<T>(pub T);
+
+impl<'de, T: PackedField> Deserialize<'de> for DeserializePacked<T> {
+    #[inline]
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let nums = LeSlice::<'de, T::Num>::deserialize(deserializer)?;
+
+        Ok(DeserializePacked(T::from_nums(nums.to_vec())))
+    }
+}
+
+
+// The id of a variant that's caught by `#[dbor(other)]`
+pub fn unknown_index<E: de::Error>(index: u64) -> Result<VariantId, E> {
+    if index > u32::MAX as u64 {
+        return Err(E::invalid_value(Unexpected::Unsigned(index), &"a variant index"));
+    }
+
+    Ok(VariantId::Index(index as u32))
+}
+
+// The name of a variant that's caught by `#[dbor(other)]`
+#[inline]
+pub fn unknown_name(name: &str) -> VariantId {
+    VariantId::Name(String::from(name))
+}
@@ -0,0 +1,164 @@
+use core::fmt;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use serde::ser::{Serialize, Serializer, SerializeTupleStruct};
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+
+use error::Result as DborResult;
+use ser::to_vec;
+use de::from_slice;
+
+
+// Newtype name used to tell DBOR to keep the encoded bytes of a value as they are. Other
+//   serializers just see a newtype struct around a byte string.
+pub(crate) const RAW_TOKEN: &str = "$serde_dbor::private::Raw";
+
+// Tuple struct name used to tell DBOR that the first field is the id or name of a variant. Other
+//   serializers just see a tuple struct of the variant and its payload.
+pub(crate) const UNKNOWN_VARIANT_TOKEN: &str = "$serde_dbor::private::UnknownVariant";
+
+
+/// A single value that's kept as encoded DBOR instead of being deserialized
+///
+/// Deserializing a `RawDbor` skips over whatever value comes next and keeps a copy of its bytes,
+/// and serializing it writes those bytes back out exactly as they were. The bytes never refer to
+/// anything outside of themselves: interned strings and references to shared values are written
+/// out in full, and a reference to a value that was deserialized as a `Shared` (so that there's
+/// no copy of it to write out) is an `UnknownAnchor` error.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RawDbor {
+    bytes: Vec<u8>,
+}
+
+impl RawDbor {
+    /// Serializes a value into a `RawDbor`
+    #[inline]
+    pub fn from_value<T: Serialize>(value: &T) -> DborResult<Self> {
+        Ok(Self {
+            bytes: to_vec(value)?,
+        })
+    }
+
+    /// Deserializes the value held by this `RawDbor`
+    #[inline]
+    pub fn to_value<'de, T: Deserialize<'de>>(&'de self) -> DborResult<T> {
+        from_slice(&self.bytes)
+    }
+
+    /// The encoded bytes of the value
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Turns this `RawDbor` into the encoded bytes of the value
+    #[inline]
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+// Writes a byte string, which DBOR's serializer writes as-is when it comes from a `RawDbor`
+struct RawBytes<'a>(&'a [u8]);
+
+impl<'a> Serialize for RawBytes<'a> {
+    #[inline]
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+impl Serialize for RawDbor {
+    #[inline]
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(RAW_TOKEN, &RawBytes(&self.bytes))
+    }
+}
+
+struct RawVisitor;
+
+impl<'de> Visitor<'de> for RawVisitor {
+    type Value = RawDbor;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an encoded DBOR value")
+    }
+
+    #[inline]
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<RawDbor, D::Error>
+    where
+        D: Deserializer<'de>
+    {
+        deserializer.deserialize_byte_buf(self)
+    }
+
+    #[inline]
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<RawDbor, E> {
+        self.visit_byte_buf(v.to_vec())
+    }
+
+    #[inline]
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<RawDbor, E> {
+        Ok(RawDbor {
+            bytes: v,
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for RawDbor {
+    #[inline]
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>
+    {
+        deserializer.deserialize_newtype_struct(RAW_TOKEN, RawVisitor)
+    }
+}
+
+
+/// How a variant was written: by its index or by its name
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum VariantId {
+    /// The variant's index
+    Index(u32),
+
+    /// The variant's name, from `#[dbor(named)]`
+    Name(String),
+}
+
+impl Serialize for VariantId {
+    #[inline]
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match *self {
+            VariantId::Index(index) => serializer.serialize_u64(index as u64),
+            VariantId::Name(ref name) => serializer.serialize_str(name),
+        }
+    }
+}
+
+/// An enum variant that the type being deserialized doesn't know about, like one added by a newer
+/// version of the program that wrote it
+///
+/// Marking a newtype variant holding an `UnknownVariant` with `#[dbor(other)]` (see
+/// `serde_dbor_derive`) makes it catch every variant that isn't recognized, instead of failing.
+/// When it's serialized again it's written exactly like the variant it was read from, so data can
+/// pass through older programs without losing anything.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct UnknownVariant {
+    /// The index or name the variant was written with
+    pub id: VariantId,
+
+    /// The variant's payload, which is a unit for unit variants
+    pub payload: RawDbor,
+}
+
+impl Serialize for UnknownVariant {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut tuple = serializer.serialize_tuple_struct(UNKNOWN_VARIANT_TOKEN, 2)?;
+
+        tuple.serialize_field(&self.id)?;
+        tuple.serialize_field(&self.payload)?;
+        tuple.end()
+    }
+}
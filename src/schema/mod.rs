@@ -0,0 +1,384 @@
+//! Schemas that describe the shape of DBOR data, and a validator that checks data against them
+//!
+//! A `Schema` can be written out by hand, or built from a Rust type with `Schema::of`. Validating
+//! data against it reads the data the same way the deserializer skips over values it doesn't
+//! need, and every place where the data doesn't match the schema is reported along with the byte
+//! offset of the value, instead of stopping at the first one like deserializing would.
+//!
+//! Only data that can't be read at all (like a length that runs past the end of the input, or
+//! anything the `DeserializerOptions` reject) stops validation early, and is reported as the last
+//! violation. Validation recurses into nested values, so data that isn't trusted should be
+//! validated with a `DeserializerOptions::max_depth`.
+//!
+//! Shared values are checked where they're anchored, and a reference to one is only accepted
+//! where the schema expects the same item again.
+//!
+//! ```rust
+//! use serde_dbor::schema::{self, Schema, Item, Field, Len, ViolationKind};
+//!
+//! let schema = Schema::new(Item::Struct(vec![
+//!     Field::new("name", Item::Str(Len::ANY)),
+//!     Field::new("age", Item::Integer { min: 0, max: 150 }),
+//! ]));
+//!
+//! // `("Ferris", 200)`
+//! let data = [0x82, 0xa6, b'F', b'e', b'r', b'r', b'i', b's', 0x18, 200];
+//! let violations = schema::validate(&data, &schema);
+//!
+//! assert_eq!(violations.len(), 1);
+//! assert_eq!(violations[0].offset, 8);
+//! assert!(matches!(violations[0].kind, ViolationKind::OutOfRange));
+//! ```
+
+use core::fmt::{self, Display};
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::io::Read as IoRead;
+
+use serde::Deserialize;
+
+mod trace;
+
+use de::{self, DeserializerOptions, Type};
+use error::*;
+use raw::VariantId;
+
+
+/// A description of the shape of some DBOR data
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Schema {
+    /// The item that the data has to be
+    pub root: Item,
+
+    /// Items that can be referred to from anywhere in the schema with `Item::Ref`, which is how
+    /// enums and recursive types are described
+    pub definitions: Vec<Item>,
+}
+
+impl Schema {
+    /// Creates a schema for data that has to be `root`, without any definitions
+    #[inline]
+    pub fn new(root: Item) -> Self {
+        Self {
+            root,
+            definitions: Vec::new(),
+        }
+    }
+
+    /// Builds a schema for the data that type T's `Deserialize` implementation accepts
+    ///
+    /// The type is traced the same way as for `fingerprint`, with the same limits: types that
+    /// only work through `deserialize_any` (like untagged enums) can only be described as
+    /// `Item::Any`, and recursive enums need at least one variant that doesn't contain itself.
+    /// Every enum ends up as a definition, and so does every struct that contains itself.
+    ///
+    /// Tracing can't see `#[serde(default)]`, so every field of every struct is required, and
+    /// tuple structs (and tuple variants) are described as structs with fields named after their
+    /// positions.
+    pub fn of<'de, T: Deserialize<'de>>() -> Result<Self> {
+        trace::schema_of::<T>()
+    }
+}
+
+/// A single value in a schema
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Item {
+    /// Any value at all
+    Any,
+
+    /// A unit, `()`
+    Unit,
+
+    /// A `bool`
+    Bool,
+
+    /// An integer between `min` and `max` (inclusive), written as either a uint or an int
+    Integer {
+        /// The smallest value allowed
+        min: i128,
+
+        /// The largest value allowed
+        max: i128,
+    },
+
+    /// A float, or an integer (which floats can be deserialized from as well)
+    Float,
+
+    /// A `char`, written as its code point or as up to 4 bytes of UTF-8
+    Char,
+
+    /// A UTF-8 string with a length (in bytes) in this range
+    Str(Len),
+
+    /// A byte string with a length in this range
+    Bytes(Len),
+
+    /// Either a `None`, or the item itself
+    Option(Box<Item>),
+
+    /// A seq of items with a length in this range
+    Seq(Box<Item>, Len),
+
+    /// A seq with exactly these items, or a tag followed by a value for pairs
+    Tuple(Vec<Item>),
+
+    /// A map with entries that match `keys` and `values`
+    Map {
+        /// The item that every key has to be
+        keys: Box<Item>,
+
+        /// The item that every value has to be
+        values: Box<Item>,
+
+        /// The range that the number of entries has to be in
+        len: Len,
+
+        /// Keys that the map has to have, which can only be found when they're written as uints
+        /// or strings
+        required: Vec<MapKey>,
+    },
+
+    /// A struct, which is either a seq of its fields in order or a map keyed by their IDs
+    ///
+    /// Entries with IDs that aren't in the list are allowed (they're skipped when deserializing),
+    /// and so are surplus items at the end of the seq.
+    Struct(Vec<Field>),
+
+    /// An enum, with the variants in order of their indexes
+    Enum(Vec<Variant>),
+
+    /// A value marked with a semantic tag, like a `Tagged`
+    Tagged(Box<Item>),
+
+    /// The item at this index in `Schema::definitions`
+    Ref(usize),
+}
+
+/// A range of lengths, including both ends
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Len {
+    /// The shortest length allowed
+    pub min: usize,
+
+    /// The longest length allowed
+    pub max: usize,
+}
+
+impl Len {
+    /// Any length at all
+    pub const ANY: Len = Len {
+        min: 0,
+        max: usize::MAX,
+    };
+
+    /// Lengths from `min` to `max`
+    #[inline]
+    pub fn new(min: usize, max: usize) -> Self {
+        Self {
+            min,
+            max,
+        }
+    }
+
+    /// Exactly `len`
+    #[inline]
+    pub fn exactly(len: usize) -> Self {
+        Self::new(len, len)
+    }
+
+    /// Whether `len` is in the range
+    #[inline]
+    pub fn contains(&self, len: usize) -> bool {
+        self.min <= len && len <= self.max
+    }
+}
+
+impl Default for Len {
+    #[inline]
+    fn default() -> Self {
+        Len::ANY
+    }
+}
+
+/// A field of a struct
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Field {
+    /// The field's name, which is only used to report it missing
+    pub name: String,
+
+    /// The field's ID when written keyed by IDs (see `SerializerOptions::field_ids`), or `None`
+    /// to use its position
+    pub id: Option<u32>,
+
+    /// The item that the field has to be
+    pub item: Item,
+
+    /// Whether the field has to be there, rather than being filled in by a default
+    pub required: bool,
+}
+
+impl Field {
+    /// A required field that's identified by its position
+    #[inline]
+    pub fn new<S: Into<String>>(name: S, item: Item) -> Self {
+        Self {
+            name: name.into(),
+            id: None,
+            item,
+            required: true,
+        }
+    }
+}
+
+/// A variant of an enum
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Variant {
+    /// The variant's name, which it's looked up by when it's written by name
+    pub name: String,
+
+    /// The item that the variant's payload has to be, which is `Item::Unit` for unit variants
+    pub item: Item,
+}
+
+impl Variant {
+    /// A variant with a payload
+    #[inline]
+    pub fn new<S: Into<String>>(name: S, item: Item) -> Self {
+        Self {
+            name: name.into(),
+            item,
+        }
+    }
+}
+
+/// A map key that a map has to have
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum MapKey {
+    /// A key that's an unsigned integer
+    Uint(u64),
+
+    /// A key that's a string
+    Str(String),
+}
+
+impl Display for MapKey {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MapKey::Uint(key) => key.fmt(formatter),
+            MapKey::Str(ref key) => write!(formatter, "{:?}", key),
+        }
+    }
+}
+
+
+/// A place where the data doesn't match its schema
+#[derive(Debug)]
+pub struct Violation {
+    /// The offset of the first byte of the value that's wrong, or of the map or struct that's
+    /// missing something
+    pub offset: usize,
+
+    /// What's wrong with it
+    pub kind: ViolationKind,
+}
+
+/// What's wrong with a value
+#[derive(Debug)]
+pub enum ViolationKind {
+    /// Found a value of this type and parameter where the schema expects something else
+    UnexpectedType(Type, u8),
+
+    /// Found a number outside of the range that the schema allows
+    OutOfRange,
+
+    /// Found a string that isn't valid UTF-8
+    InvalidUtf8,
+
+    /// Found a number or string that isn't a valid `char`
+    InvalidChar,
+
+    /// Found a string, seq, map, or tuple with a length that the schema doesn't allow
+    UnexpectedLength(usize),
+
+    /// A map doesn't have a key that the schema requires
+    MissingKey(MapKey),
+
+    /// A struct doesn't have a field that the schema requires
+    MissingField(String),
+
+    /// Found a variant that isn't in the enum
+    UnknownVariant(VariantId),
+
+    /// Found an `Item::Ref` to a definition that doesn't exist (or only refers to itself)
+    UnknownDefinition(usize),
+
+    /// Found a reference to the shared value with this index, which was anchored where the schema
+    /// expects something else (or inside of a value that wasn't checked), so it isn't known to fit
+    UncheckedReference(usize),
+
+    /// The data couldn't be read any further, which ends validation
+    Malformed(Error),
+}
+
+impl Display for Violation {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "At byte {}: ", self.offset)?;
+
+        match self.kind {
+            ViolationKind::UnexpectedType(ref ty, value) => write!(formatter, "Didn't expect a \
+                value of type {:?} with parameter {}", ty, value),
+            ViolationKind::OutOfRange => formatter.write_str("Number is out of range"),
+            ViolationKind::InvalidUtf8 => formatter.write_str("String isn't valid UTF-8"),
+            ViolationKind::InvalidChar => formatter.write_str("Value isn't a valid char"),
+            ViolationKind::UnexpectedLength(len) => write!(formatter, "Length {} isn't allowed",
+                len),
+            ViolationKind::MissingKey(ref key) => write!(formatter, "Map is missing the key {}",
+                key),
+            ViolationKind::MissingField(ref name) => write!(formatter, "Struct is missing the \
+                field `{}`", name),
+            ViolationKind::UnknownVariant(VariantId::Index(index)) => write!(formatter, "Enum \
+                doesn't have a variant {}", index),
+            ViolationKind::UnknownVariant(VariantId::Name(ref name)) => write!(formatter, "Enum \
+                doesn't have a variant `{}`", name),
+            ViolationKind::UnknownDefinition(index) => write!(formatter, "Schema doesn't have a \
+                definition {}", index),
+            ViolationKind::UncheckedReference(index) => write!(formatter, "Shared value {} wasn't \
+                checked against what the schema expects here", index),
+            ViolationKind::Malformed(ref error) => error.fmt(formatter),
+        }
+    }
+}
+
+
+/// Checks DBOR data against a schema, and returns every violation in the order they were found
+///
+/// An empty list means the data matches the schema.
+pub fn validate<S: AsRef<[u8]> + ?Sized>(bytes: &S, schema: &Schema) -> Vec<Violation> {
+    validate_with_options(bytes, schema, DeserializerOptions::default())
+}
+
+/// Checks DBOR data against a schema, while rejecting whatever the options reject
+pub fn validate_with_options<S>(bytes: &S, schema: &Schema, options: DeserializerOptions)
+    -> Vec<Violation>
+where
+    S: AsRef<[u8]> + ?Sized
+{
+    de::validate::validate_slice(bytes.as_ref(), schema, options)
+}
+
+/// Checks DBOR data from an `io::Read` against a schema
+#[cfg(feature = "std")]
+pub fn validate_reader<R: IoRead>(reader: R, schema: &Schema) -> Vec<Violation> {
+    validate_reader_with_options(reader, schema, DeserializerOptions::default())
+}
+
+/// Checks DBOR data from an `io::Read` against a schema, while rejecting whatever the options
+/// reject
+#[cfg(feature = "std")]
+pub fn validate_reader_with_options<R: IoRead>(reader: R, schema: &Schema,
+    options: DeserializerOptions) -> Vec<Violation>
+{
+    de::validate::validate_reader(reader, schema, options)
+}
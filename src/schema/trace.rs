@@ -0,0 +1,284 @@
+use core::convert::TryInto;
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use serde::Deserialize;
+
+use error::*;
+use field_ids::{explicit_ids, id_of};
+use fingerprint::*;
+use raw::RAW_TOKEN;
+use tagged::TAGGED_TOKEN;
+use super::{Schema, Item, Len, Field, Variant};
+
+
+// Builds a schema out of the description of a type. Every enum becomes the definition at its own
+//   index, and containers that contain themselves get definitions after them.
+pub(crate) fn schema_of<'de, T: Deserialize<'de>>() -> Result<Schema> {
+    let trace = trace::<T>(true)?;
+    let mut builder = Builder {
+        containers: &trace.containers,
+        items: vec![None; trace.containers.len()],
+        definitions: vec![None; trace.containers.len()],
+        next_definition: trace.enums.len(),
+    };
+
+    let root = builder.item(&mut Tokens::new(&trace.main));
+    let mut definitions: Vec<Item> = trace.enums.iter()
+        .map(|&(names, ref contents)| {
+            let variants = names.iter().zip(contents)
+                .map(|(name, contents)| {
+                    Variant::new(*name, builder.variant(&mut Tokens::new(contents)))
+                })
+                .collect();
+
+            Item::Enum(variants)
+        })
+        .collect();
+
+    // Containers are only known to be recursive once everything has been built, so their
+    //   definitions are filled in last
+    let mut recursive: Vec<(usize, Item)> = builder.definitions.iter()
+        .zip(builder.items)
+        .filter_map(|(&definition, item)| Some((definition?, item.unwrap_or(Item::Any))))
+        .collect();
+
+    recursive.sort_by_key(|&(definition, _)| definition);
+    definitions.extend(recursive.into_iter().map(|(_, item)| item));
+
+    Ok(Schema {
+        root,
+        definitions,
+    })
+}
+
+
+// Reads tokens from a description, where running out early just gives zeros
+struct Tokens<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Tokens<'a> {
+    #[inline]
+    fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+        }
+    }
+
+    #[inline]
+    fn peek(&self) -> Option<u8> {
+        self.bytes.first().cloned()
+    }
+
+    fn token(&mut self) -> Option<u8> {
+        let token = self.peek()?;
+
+        self.bytes = &self.bytes[1..];
+
+        Some(token)
+    }
+
+    fn len(&mut self) -> usize {
+        match self.bytes.get(..4).and_then(|len| len.try_into().ok()) {
+            Some(len) => {
+                self.bytes = &self.bytes[4..];
+
+                u32::from_le_bytes(len) as usize
+            }
+            None => {
+                self.bytes = &[];
+
+                0
+            }
+        }
+    }
+
+    fn names(&mut self) -> Vec<String> {
+        let count = self.len();
+
+        (0..count)
+            .map(|_| {
+                let len = self.len().min(self.bytes.len());
+                let (name, rest) = self.bytes.split_at(len);
+
+                self.bytes = rest;
+
+                String::from_utf8_lossy(name).into_owned()
+            })
+            .collect()
+    }
+}
+
+struct Builder<'a> {
+    containers: &'a [(&'static str, &'static [&'static str])],
+    // The first item built for every container
+    items: Vec<Option<Item>>,
+    // The definitions of containers that contain themselves
+    definitions: Vec<Option<usize>>,
+    next_definition: usize,
+}
+
+impl<'a> Builder<'a> {
+    fn item(&mut self, tokens: &mut Tokens) -> Item {
+        let token = match tokens.token() {
+            Some(token) => token,
+            None => return Item::Any,
+        };
+
+        match token {
+            TOKEN_BOOL => Item::Bool,
+            TOKEN_I8 => integer(i8::MIN.into(), i8::MAX.into()),
+            TOKEN_I16 => integer(i16::MIN.into(), i16::MAX.into()),
+            TOKEN_I32 => integer(i32::MIN.into(), i32::MAX.into()),
+            TOKEN_I64 => integer(i64::MIN.into(), i64::MAX.into()),
+            TOKEN_I128 => integer(i128::MIN, i128::MAX),
+            TOKEN_U8 => integer(0, u8::MAX.into()),
+            TOKEN_U16 => integer(0, u16::MAX.into()),
+            TOKEN_U32 => integer(0, u32::MAX.into()),
+            TOKEN_U64 => integer(0, u64::MAX.into()),
+            TOKEN_U128 => integer(0, i128::MAX),
+            TOKEN_F32 | TOKEN_F64 => Item::Float,
+            TOKEN_CHAR => Item::Char,
+            TOKEN_STR => Item::Str(Len::ANY),
+            TOKEN_BYTES => Item::Bytes(Len::ANY),
+            TOKEN_OPTION => Item::Option(Box::new(self.item(tokens))),
+            TOKEN_UNIT => Item::Unit,
+            TOKEN_SEQ => Item::Seq(Box::new(self.item(tokens)), Len::ANY),
+            TOKEN_TUPLE => {
+                let len = tokens.len();
+
+                Item::Tuple((0..len).map(|_| self.item(tokens)).collect())
+            }
+            TOKEN_MAP => Item::Map {
+                keys: Box::new(self.item(tokens)),
+                values: Box::new(self.item(tokens)),
+                len: Len::ANY,
+                required: Vec::new(),
+            },
+            TOKEN_STRUCT => self.fields(tokens, None),
+            TOKEN_ENUM => {
+                let index = tokens.len();
+
+                // The enum's own container, which doesn't matter since it's a definition already
+                match tokens.token() {
+                    Some(TOKEN_CONTAINER) => {
+                        tokens.len();
+                    }
+                    Some(TOKEN_RECURSION) => {
+                        tokens.len();
+                        tokens.len();
+                    }
+                    _ => {}
+                }
+
+                Item::Ref(index)
+            }
+            TOKEN_RECURSION => {
+                tokens.len();
+
+                let key = tokens.len();
+
+                match self.definitions.get_mut(key) {
+                    Some(definition) => {
+                        let index = definition.unwrap_or(self.next_definition);
+
+                        if definition.is_none() {
+                            *definition = Some(index);
+                            self.next_definition += 1;
+                        }
+
+                        Item::Ref(index)
+                    }
+                    None => Item::Any,
+                }
+            }
+            TOKEN_CONTAINER => {
+                let key = tokens.len();
+
+                self.container(tokens, key)
+            }
+            _ => Item::Any,
+        }
+    }
+
+    // The struct, tuple struct, or newtype that follows a `TOKEN_CONTAINER`
+    fn container(&mut self, tokens: &mut Tokens, key: usize) -> Item {
+        let name = self.containers.get(key).map_or("", |&(name, _)| name);
+
+        let item = if tokens.peek() == Some(TOKEN_STRUCT) {
+            tokens.token();
+
+            self.fields(tokens, explicit_ids(name))
+        } else {
+            match self.item(tokens) {
+                // `RawDbor` looks like a byte string to the tracer, but it can hold anything
+                _ if name == RAW_TOKEN => Item::Any,
+                Item::Tuple(mut items) if name == TAGGED_TOKEN && items.len() == 2 => {
+                    Item::Tagged(Box::new(items.pop().unwrap_or(Item::Any)))
+                }
+                // Tuple structs are read the same way as structs, so they can gain fields later
+                Item::Tuple(items) if !name.is_empty() => positional(items),
+                item => item,
+            }
+        };
+
+        if let Some(slot) = self.items.get_mut(key) {
+            if slot.is_none() {
+                *slot = Some(item.clone());
+            }
+        }
+
+        item
+    }
+
+    // The fields of a struct or struct variant that follow its names
+    fn fields(&mut self, tokens: &mut Tokens, ids: Option<&str>) -> Item {
+        let names = tokens.names();
+
+        let fields = names.into_iter()
+            .map(|name| Field {
+                id: ids.and_then(|ids| id_of(ids, &name)),
+                item: self.item(tokens),
+                name,
+                required: true,
+            })
+            .collect();
+
+        Item::Struct(fields)
+    }
+
+    // The payload of an enum variant
+    fn variant(&mut self, tokens: &mut Tokens) -> Item {
+        match tokens.token() {
+            Some(TOKEN_UNIT_VARIANT) => Item::Unit,
+            Some(TOKEN_NEWTYPE_VARIANT) => self.item(tokens),
+            Some(TOKEN_TUPLE_VARIANT) => {
+                let len = tokens.len();
+
+                positional((0..len).map(|_| self.item(tokens)).collect())
+            }
+            Some(TOKEN_STRUCT_VARIANT) => self.fields(tokens, None),
+            _ => Item::Any,
+        }
+    }
+}
+
+#[inline]
+fn integer(min: i128, max: i128) -> Item {
+    Item::Integer {
+        min,
+        max,
+    }
+}
+
+// A struct with fields named after their positions
+fn positional(items: Vec<Item>) -> Item {
+    let fields = items.into_iter()
+        .enumerate()
+        .map(|(index, item)| Field::new(index.to_string(), item))
+        .collect();
+
+    Item::Struct(fields)
+}
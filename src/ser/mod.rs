@@ -1,3 +1,12 @@
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::convert::TryFrom;
+#[cfg(feature = "std")]
+use std::collections::HashMap as Map;
+#[cfg(not(feature = "std"))]
+use alloc::collections::BTreeMap as Map;
+#[cfg(feature = "std")]
 use std::io::Write as IoWrite;
 
 use serde::ser::{self, Serializer as SerdeSerializer, Serialize};
@@ -5,6 +14,13 @@ use serde::ser::{self, Serializer as SerdeSerializer, Serialize};
 mod write;
 
 use error::*;
+use field_ids::{explicit_ids, id_of};
+use header;
+use packed::{ALIGN_2_TOKEN, ALIGN_4_TOKEN, ALIGN_8_TOKEN};
+use private::{NAMED_VARIANT_TOKEN, INTERN_TOKEN, SHRINK_FLOATS_TOKEN};
+use raw::{RAW_TOKEN, UNKNOWN_VARIANT_TOKEN};
+use shared::{self, SHARED_TOKEN};
+use tagged::TAGGED_TOKEN;
 use self::write::*;
 use super::WRONG_ENDIANNESS;
 
@@ -16,13 +32,102 @@ const TYPE_VARIANT: u8 = 0b01100000;
 const TYPE_SEQ: u8 = 0b10000000;
 const TYPE_BYTES: u8 = 0b10100000;
 const TYPE_MAP: u8 = 0b11000000;
+const TYPE_EXT: u8 = 0b11100000;
 
 const VALUE_MASK: u8 = 0b00011111;
 
+// Shorter strings are never interned because a back-reference wouldn't be any smaller
+const MIN_INTERNED_LEN: usize = 3;
+
+
+/// Options that change how Rust values are encoded into DBOR
+///
+/// Every option is off by default, and data written with any combination of options can still be
+/// read by a plain `Deserializer`. Use `SerializerOptions::canonical()` when the same value always
+/// needs to turn into the same bytes.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct SerializerOptions {
+    /// Insert padding before packed arrays (like `LeSlice`) so that their contents are aligned
+    /// relative to the start of the output, which lets them be borrowed as native slices
+    pub align_packed: bool,
+
+    /// Write floats with the smallest precision (`f64`, `f32`, or `f16`) that can hold them
+    /// without losing any information
+    pub shrink_floats: bool,
+
+    /// Write strings that have already been written once as short back-references, which keeps a
+    /// copy of every distinct string in memory until serialization finishes
+    pub intern_strings: bool,
+
+    /// Write every value in exactly one way, so that equal values always produce equal bytes
+    ///
+    /// Map entries are sorted by the bytes of their encoded keys (and duplicate keys are an
+    /// error), floats are shrunk and every NaN is written the same way, and shared values are
+    /// written out in full every time. This turns off `align_packed` and `intern_strings`, since
+    /// both of them depend on where in the output a value ends up. Seqs are left in the order
+    /// they're given, so unordered collections like `HashSet` should be swapped for ordered ones.
+    pub canonical: bool,
+
+    /// Write a CRC-32C of the data as a 4 byte trailer after it, which can be checked with
+    /// `DeserializerOptions::checksum` to catch data that got corrupted in storage
+    ///
+    /// The checksum covers everything after the header (if there is one), so the trailer isn't
+    /// part of the value itself and has to be expected by whoever reads it.
+    pub checksum: bool,
+
+    /// A fingerprint of the type being serialized (from `serde_dbor::fingerprint`) to put in the
+    /// header, which `from_slice_with_header` and `from_reader_with_header` compare against the
+    /// type they're deserializing
+    ///
+    /// This is only written by `to_vec_with_header` and `to_writer_with_header`, since there's
+    /// nowhere to put it without a header.
+    pub fingerprint: Option<u64>,
+
+    /// Write structs as maps from numeric field IDs to values instead of as seqs of values, so
+    /// that fields can be added, removed, or reordered without breaking old data
+    ///
+    /// Fields are numbered in the order they're declared in, unless the struct gives them their
+    /// own IDs. The IDs still fit in the instruction byte up to `23`, so this only costs one byte
+    /// per field over writing seqs. Both kinds of struct can always be read back.
+    ///
+    /// Serializers aren't told about fields with serde's `skip_serializing` attribute, and serde's
+    /// derive lists aliases as extra fields, so both would throw the numbering off.
+    pub field_ids: bool,
+}
+
+impl SerializerOptions {
+    /// Options for canonical encoding, which can be hashed, signed, or compared byte-for-byte
+    #[inline]
+    pub fn canonical() -> Self {
+        Self {
+            canonical: true,
+            shrink_floats: true,
+            ..Self::default()
+        }
+    }
+}
 
 /// A structure for serializing Rust values into DBOR
 pub struct Serializer<W: Write> {
     output: W,
+    options: SerializerOptions,
+    align_next: usize,
+    interned: Map<Vec<u8>, usize>,
+    anchors: Map<usize, usize>,
+    // Clones of the anchored pointers, which keep their addresses from being reused
+    pointers: Vec<Rc<dyn Any>>,
+    tag_next: bool,
+    // Whether the next number or string is the index or name of an `UnknownVariant`
+    variant_next: bool,
+    // Whether the next byte string is the encoded bytes of a `RawDbor`
+    raw_next: bool,
+    // Buffers that output gets redirected into while canonical map entries are being written
+    captures: Vec<Vec<u8>>,
+    // Encoded keys and values of the canonical maps being written, innermost last
+    entries: Vec<Vec<(Vec<u8>, Vec<u8>)>>,
+    // The explicit field IDs (if any) and the next field number of the structs being written with
+    //   field IDs, innermost last
+    structs: Vec<(Option<&'static str>, u32)>,
 }
 
 /// Serialize the given data structure as a DBOR byte vector
@@ -30,39 +135,428 @@ pub fn to_vec<T>(value: &T) -> Result<Vec<u8>>
 where
     T: Serialize
 {
-    let mut serializer = Serializer {
-        output: VecWriter::new(),
-    };
+    to_vec_with_options(value, SerializerOptions::default())
+}
+
+/// Serialize the given data structure as a DBOR byte vector using specific options
+pub fn to_vec_with_options<T>(value: &T, options: SerializerOptions) -> Result<Vec<u8>>
+where
+    T: Serialize
+{
+    let mut vec = Vec::new();
+    to_vec_into_with_options(value, &mut vec, options)?;
+    Ok(vec)
+}
+
+/// Serialize the given data structure as DBOR onto the end of an existing byte vector
+///
+/// Nothing is appended if serialization fails, so a buffer can be cleared and reused for many
+/// values without allocating each time.
+pub fn to_vec_into<T>(value: &T, vec: &mut Vec<u8>) -> Result<()>
+where
+    T: Serialize
+{
+    to_vec_into_with_options(value, vec, SerializerOptions::default())
+}
+
+/// Serialize the given data structure as DBOR onto the end of an existing byte vector using
+/// specific options
+pub fn to_vec_into_with_options<T>(value: &T, vec: &mut Vec<u8>, options: SerializerOptions)
+    -> Result<()>
+where
+    T: Serialize
+{
+    let mut serializer = Serializer::new(VecWriter::new(vec), options);
+
+    match value.serialize(&mut serializer) {
+        Ok(()) => serializer.output.finish(),
+        Err(error) => {
+            serializer.output.undo();
+            Err(error)
+        }
+    }
+}
+
+/// Serialize the given data structure as a DBOR byte vector that starts with a header
+///
+/// The header holds the DBOR magic bytes, the format version, and which of `options` were used,
+/// so that `from_slice_with_header` can tell DBOR apart from other data and check it the right way.
+pub fn to_vec_with_header<T>(value: &T, options: SerializerOptions) -> Result<Vec<u8>>
+where
+    T: Serialize
+{
+    let mut vec = Vec::new();
+    let mut serializer = Serializer::new(VecWriter::new(&mut vec), options);
+    serializer.put_header()?;
+    value.serialize(&mut serializer)?;
+    serializer.output.finish()?;
+    Ok(vec)
+}
+
+/// Serialize the given data structure as DBOR into the start of a byte slice, returning the number
+/// of bytes written
+///
+/// Fails with `Error::BufferTooSmall` if the slice runs out of room, in which case the contents of
+/// the slice are unspecified.
+pub fn to_slice<T>(value: &T, slice: &mut [u8]) -> Result<usize>
+where
+    T: Serialize
+{
+    to_slice_with_options(value, slice, SerializerOptions::default())
+}
+
+/// Serialize the given data structure as DBOR into the start of a byte slice using specific
+/// options, returning the number of bytes written
+pub fn to_slice_with_options<T>(value: &T, slice: &mut [u8], options: SerializerOptions)
+    -> Result<usize>
+where
+    T: Serialize
+{
+    let mut serializer = Serializer::new(SliceWriter::new(slice), options);
     value.serialize(&mut serializer)?;
     serializer.output.finish()
 }
 
 /// Serialize the given data structure as DBOR into an IO stream
+#[cfg(feature = "std")]
 pub fn to_writer<T, W>(value: &T, writer: W) -> Result<W>
 where
     T: Serialize,
     W: IoWrite,
 {
-    let mut serializer = Serializer {
-        output: IoWriter::new(writer),
-    };
+    to_writer_with_options(value, writer, SerializerOptions::default())
+}
+
+/// Serialize the given data structure as DBOR into an IO stream using specific options
+#[cfg(feature = "std")]
+pub fn to_writer_with_options<T, W>(value: &T, writer: W, options: SerializerOptions) -> Result<W>
+where
+    T: Serialize,
+    W: IoWrite,
+{
+    to_writer_with_capacity_and_options(value, writer, DEFAULT_BUF_LEN, options)
+}
+
+/// Serialize the given data structure as DBOR into an IO stream, buffering writes in chunks of
+/// `capacity` bytes instead of the default 1024
+#[cfg(feature = "std")]
+pub fn to_writer_with_capacity<T, W>(value: &T, writer: W, capacity: usize) -> Result<W>
+where
+    T: Serialize,
+    W: IoWrite,
+{
+    to_writer_with_capacity_and_options(value, writer, capacity, SerializerOptions::default())
+}
+
+/// Serialize the given data structure as DBOR into an IO stream using specific options, buffering
+/// writes in chunks of `capacity` bytes
+#[cfg(feature = "std")]
+pub fn to_writer_with_capacity_and_options<T, W>(value: &T, writer: W, capacity: usize,
+    options: SerializerOptions) -> Result<W>
+where
+    T: Serialize,
+    W: IoWrite,
+{
+    let mut serializer = Serializer::new(IoWriter::with_capacity(writer, capacity), options);
     value.serialize(&mut serializer)?;
     serializer.output.finish()
 }
 
+/// Serialize the given data structure as DBOR into an IO stream, starting with a header that
+/// `from_reader_with_header` can check
+#[cfg(feature = "std")]
+pub fn to_writer_with_header<T, W>(value: &T, writer: W, options: SerializerOptions) -> Result<W>
+where
+    T: Serialize,
+    W: IoWrite,
+{
+    let mut serializer = Serializer::new(IoWriter::with_capacity(writer, DEFAULT_BUF_LEN),
+        options);
+    serializer.put_header()?;
+    value.serialize(&mut serializer)?;
+    serializer.output.finish()
+}
+
+/// Get the number of bytes that `to_vec` would write for the given data structure, without
+/// writing them anywhere
+pub fn serialized_size<T>(value: &T) -> Result<u64>
+where
+    T: Serialize
+{
+    serialized_size_with_options(value, SerializerOptions::default())
+}
+
+/// Get the number of bytes that `to_vec_with_options` would write for the given data structure
+/// using specific options, without writing them anywhere
+pub fn serialized_size_with_options<T>(value: &T, options: SerializerOptions) -> Result<u64>
+where
+    T: Serialize
+{
+    let mut serializer = Serializer::new(SizeWriter::new(), options);
+    value.serialize(&mut serializer)?;
+    serializer.output.finish().map(|len| len as u64)
+}
+
+// Narrow a single precision float into a half precision float, but only if it can be done
+//   without losing any information
+pub(crate) fn f32_to_f16(v: f32) -> Option<u16> {
+    let bits = v.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7fffff;
+
+    match exponent {
+        // Infinity or NaN, where NaN payloads have to fit in 10 bits
+        0xff => if mantissa & 0x1fff == 0 {
+            Some(sign | 0x7c00 | (mantissa >> 13) as u16)
+        } else {
+            None
+        },
+        // Zero, since single precision subnormals are too small for half precision
+        0 => if mantissa == 0 {
+            Some(sign)
+        } else {
+            None
+        },
+        _ => {
+            let exponent = exponent - 127;
+
+            if (-14..=15).contains(&exponent) {
+                // Normal half precision float
+                if mantissa & 0x1fff == 0 {
+                    Some(sign | ((exponent + 15) << 10) as u16 | (mantissa >> 13) as u16)
+                } else {
+                    None
+                }
+            } else if (-24..-14).contains(&exponent) {
+                // Subnormal half precision float, which is `mantissa * 2^-24`
+                let full = 0x800000 | mantissa;
+                let shift = -(exponent + 1);
+
+                if full & ((1 << shift) - 1) == 0 {
+                    Some(sign | (full >> shift) as u16)
+                } else {
+                    None
+                }
+            } else {
+                None
+            }
+        }
+    }
+}
+
 impl<W: Write> Serializer<W> {
+    #[inline]
+    fn new(mut output: W, mut options: SerializerOptions) -> Self {
+        if options.checksum {
+            output.start_checksum();
+        }
+
+        if options.canonical {
+            options.shrink_floats = true;
+            options.align_packed = false;
+            options.intern_strings = false;
+        }
+
+        Self {
+            output,
+            options,
+            align_next: 0,
+            interned: Map::new(),
+            anchors: Map::new(),
+            pointers: Vec::new(),
+            tag_next: false,
+            variant_next: false,
+            raw_next: false,
+            captures: Vec::new(),
+            entries: Vec::new(),
+            structs: Vec::new(),
+        }
+    }
+
+    // Writes the instruction byte that starts a variant, holding either its index or its name
+    fn put_variant(&mut self, name: &'static str, mut variant_index: u32, variant: &str)
+        -> Result<()>
+    {
+        if name == NAMED_VARIANT_TOKEN {
+            self.put_byte(TYPE_VARIANT | 27)?;
+            self.put_extended_param(variant.len())?;
+
+            return self.put_bytes(variant.as_bytes(), false);
+        }
+
+        match variant_index {
+            0..=23 => {
+                self.put_byte(TYPE_VARIANT | (variant_index as u8 & VALUE_MASK))?;
+            }
+            24..=0xff => {
+                self.put_byte(TYPE_VARIANT | 24)?;
+                self.put_byte(variant_index as u8)?;
+            }
+            0x100..=0xffff => {
+                self.put_byte(TYPE_VARIANT | 25)?;
+                self.put_bytes(
+                    unsafe { &mut *(&mut (variant_index as u16) as *mut u16 as *mut [u8; 2]) },
+                    true
+                )?;
+            }
+            0x10000..=0xffffffff => {
+                self.put_byte(TYPE_VARIANT | 26)?;
+                self.put_bytes(
+                    unsafe { &mut *(&mut variant_index as *mut u32 as *mut [u8; 4]) },
+                    true
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Writes a struct field as a map entry keyed by its ID
+    fn serialize_field_id<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize
+    {
+        let id = match self.structs.last_mut() {
+            Some(&mut (Some(ids), _)) => id_of(ids, key).ok_or(Error::MissingFieldId(key))?,
+            Some(&mut (None, ref mut next)) => {
+                *next += 1;
+                *next - 1
+            }
+            None => return Err(Error::MissingFieldId(key)),
+        };
+
+        ser::SerializeMap::serialize_key(&mut &mut *self, &id)?;
+        ser::SerializeMap::serialize_value(&mut &mut *self, value)
+    }
+
+    // Writes the header describing the options in use, which has to come before anything else
+    #[inline]
+    fn put_header(&mut self) -> Result<()> {
+        let header = header::encode(&self.options);
+        self.put_bytes(&header, false)?;
+
+        if let Some(fingerprint) = self.options.fingerprint {
+            self.put_bytes(&fingerprint.to_le_bytes(), false)?;
+        }
+
+        // The checksum only covers what comes after the header
+        if self.options.checksum {
+            self.output.start_checksum();
+        }
+
+        Ok(())
+    }
+
+    // Writes the instruction byte of a tag along with its tag number
+    fn put_tag(&mut self, mut tag: u64) -> Result<()> {
+        match tag {
+            0..=23 => self.put_byte(TYPE_EXT | tag as u8),
+            24..=0xff => {
+                self.put_byte(TYPE_EXT | 24)?;
+                self.put_byte(tag as u8)
+            }
+            0x100..=0xffff => {
+                self.put_byte(TYPE_EXT | 25)?;
+                self.put_bytes(
+                    unsafe { &mut *(&mut (tag as u16) as *mut u16 as *mut [u8; 2]) },
+                    true
+                )
+            }
+            0x10000..=0xffffffff => {
+                self.put_byte(TYPE_EXT | 26)?;
+                self.put_bytes(
+                    unsafe { &mut *(&mut (tag as u32) as *mut u32 as *mut [u8; 4]) },
+                    true
+                )
+            }
+            0x100000000..=0xffffffffffffffff => {
+                self.put_byte(TYPE_EXT | 27)?;
+                self.put_bytes(
+                    unsafe { &mut *(&mut tag as *mut u64 as *mut [u8; 8]) },
+                    true
+                )
+            }
+        }
+    }
+
+    // Writes a parameter that takes up a whole byte, like the length of a variant name
+    fn put_extended_param(&mut self, value: usize) -> Result<()> {
+        match value {
+            0..=247 => self.put_byte(value as u8),
+            248..=0xff => {
+                self.put_byte(248)?;
+                self.put_byte(value as u8)
+            }
+            0x0100..=0xffff => {
+                self.put_byte(249)?;
+                self.put_bytes(
+                    unsafe { &mut *(&mut (value as u16) as *mut u16 as *mut [u8; 2]) },
+                    true
+                )
+            }
+            0x00010000..=0xffffffff => {
+                self.put_byte(250)?;
+                self.put_bytes(
+                    unsafe { &mut *(&mut (value as u32) as *mut u32 as *mut [u8; 4]) },
+                    true
+                )
+            }
+            _ => {
+                self.put_byte(251)?;
+                self.put_bytes(
+                    unsafe { &mut *(&mut (value as u64) as *mut u64 as *mut [u8; 8]) },
+                    true
+                )
+            }
+        }
+    }
+
+    // Runs `f` with all output redirected into a new buffer, and returns that buffer
+    fn capture<F>(&mut self, f: F) -> Result<Vec<u8>>
+    where
+        F: FnOnce(&mut Self) -> Result<()>
+    {
+        self.captures.push(Vec::new());
+
+        let result = f(self);
+        let captured = self.captures.pop().unwrap_or_default();
+
+        result.map(|_| captured)
+    }
+
     #[inline]
     fn put_byte(&mut self, byte: u8) -> Result<()> {
-        self.output.put_byte(byte)
+        match self.captures.last_mut() {
+            Some(capture) => {
+                capture.push(byte);
+
+                Ok(())
+            }
+            None => self.output.put_byte(byte),
+        }
     }
 
     #[inline]
     fn put_bytes(&mut self, bytes: &[u8], should_flip: bool) -> Result<()> {
-        self.output.put_bytes(bytes, *WRONG_ENDIANNESS && should_flip)
+        let flipped = WRONG_ENDIANNESS && should_flip;
+
+        match self.captures.last_mut() {
+            Some(capture) => {
+                if flipped {
+                    capture.extend(bytes.iter().rev());
+                } else {
+                    capture.extend_from_slice(bytes);
+                }
+
+                Ok(())
+            }
+            None => self.output.put_bytes(bytes, flipped),
+        }
     }
 }
 
-impl<'a, W: Write> SerdeSerializer for &'a mut Serializer<W> {
+impl<W: Write> SerdeSerializer for &mut Serializer<W> {
     type Ok = ();
     type Error = Error;
 
@@ -82,9 +576,9 @@ impl<'a, W: Write> SerdeSerializer for &'a mut Serializer<W> {
     #[inline]
     fn serialize_i8(self, mut v: i8) -> Result<()> {
         match v {
-            0...15 => self.put_byte(TYPE_INT | (v as u8 & VALUE_MASK)),
-            -8...-1 => self.put_byte(TYPE_INT | ((v + 24) as u8 & VALUE_MASK)),
-            -0x80...-8 | 16...0x7f | _ => {
+            0..=15 => self.put_byte(TYPE_INT | (v as u8 & VALUE_MASK)),
+            -8..=-1 => self.put_byte(TYPE_INT | ((v + 24) as u8 & VALUE_MASK)),
+            -0x80..=-9 | 16..=0x7f => {
                 self.put_byte(TYPE_INT | 24)?;
                 self.put_byte(
                     unsafe { *(&mut v as *mut i8 as *mut u8) }
@@ -96,15 +590,15 @@ impl<'a, W: Write> SerdeSerializer for &'a mut Serializer<W> {
     // #[inline]
     fn serialize_i16(self, mut v: i16) -> Result<()> {
         match v {
-            0...15 => self.put_byte(TYPE_INT | (v as u8 & VALUE_MASK)),
-            -8...-1 => self.put_byte(TYPE_INT | ((v + 24) as u8 & VALUE_MASK)),
-            -0x80...-8 | 16...0x7f => {
+            0..=15 => self.put_byte(TYPE_INT | (v as u8 & VALUE_MASK)),
+            -8..=-1 => self.put_byte(TYPE_INT | ((v + 24) as u8 & VALUE_MASK)),
+            -0x80..=-9 | 16..=0x7f => {
                 self.put_byte(TYPE_INT | 24)?;
                 self.put_byte(
                     unsafe { *(&mut (v as i8) as *mut i8 as *mut u8) }
                 )
             }
-            -0x8000...-0x81 | 0x80...0x7fff | _ => {
+            -0x8000..=-0x81 | 0x80..=0x7fff => {
                 self.put_byte(TYPE_INT | 25)?;
                 self.put_bytes(
                     unsafe { &mut *(&mut v as *mut i16 as *mut [u8; 2]) },
@@ -117,22 +611,22 @@ impl<'a, W: Write> SerdeSerializer for &'a mut Serializer<W> {
     #[inline]
     fn serialize_i32(self, mut v: i32) -> Result<()> {
         match v {
-            0...15 => self.put_byte(TYPE_INT | (v as u8 & VALUE_MASK)),
-            -8...-1 => self.put_byte(TYPE_INT | ((v + 24) as u8 & VALUE_MASK)),
-            -0x80...-8 | 16...0x7f => {
+            0..=15 => self.put_byte(TYPE_INT | (v as u8 & VALUE_MASK)),
+            -8..=-1 => self.put_byte(TYPE_INT | ((v + 24) as u8 & VALUE_MASK)),
+            -0x80..=-9 | 16..=0x7f => {
                 self.put_byte(TYPE_INT | 24)?;
                 self.put_byte(
                     unsafe { *(&mut (v as i8) as *mut i8 as *mut u8) }
                 )
             }
-            -0x8000...-0x81 | 0x80...0x7fff => {
+            -0x8000..=-0x81 | 0x80..=0x7fff => {
                 self.put_byte(TYPE_INT | 25)?;
                 self.put_bytes(
                     unsafe { &mut *(&mut (v as i16) as *mut i16 as *mut [u8; 2]) },
                     true
                 )
             }
-            -0x80000000...-0x8001 | 0x8000...0x7fffffff | _ => {
+            -0x80000000..=-0x8001 | 0x8000..=0x7fffffff => {
                 self.put_byte(TYPE_INT | 26)?;
                 self.put_bytes(
                     unsafe { &mut *(&mut v as *mut i32 as *mut [u8; 4]) },
@@ -145,29 +639,29 @@ impl<'a, W: Write> SerdeSerializer for &'a mut Serializer<W> {
     // #[inline]
     fn serialize_i64(self, mut v: i64) -> Result<()> {
         match v {
-            0...15 => self.put_byte(TYPE_INT | (v as u8 & VALUE_MASK)),
-            -8...-1 => self.put_byte(TYPE_INT | ((v + 24) as u8 & VALUE_MASK)),
-            -0x80...-8 | 16...0x7f => {
+            0..=15 => self.put_byte(TYPE_INT | (v as u8 & VALUE_MASK)),
+            -8..=-1 => self.put_byte(TYPE_INT | ((v + 24) as u8 & VALUE_MASK)),
+            -0x80..=-9 | 16..=0x7f => {
                 self.put_byte(TYPE_INT | 24)?;
                 self.put_byte(
                     unsafe { *(&mut (v as i8) as *mut i8 as *mut u8) }
                 )
             }
-            -0x8000...-0x81 | 0x80...0x7fff => {
+            -0x8000..=-0x81 | 0x80..=0x7fff => {
                 self.put_byte(TYPE_INT | 25)?;
                 self.put_bytes(
                     unsafe { &mut *(&mut (v as i16) as *mut i16 as *mut [u8; 2]) },
                     true
                 )
             }
-            -0x80000000...-0x8001 | 0x8000...0x7fffffff => {
+            -0x80000000..=-0x8001 | 0x8000..=0x7fffffff => {
                 self.put_byte(TYPE_INT | 26)?;
                 self.put_bytes(
                     unsafe { &mut *(&mut (v as i32) as *mut i32 as *mut [u8; 4]) },
                     true
                 )
             }
-            -0x8000000000000000...-0x80000001 | 0x80000000...0x7fffffffffffffff | _ => {
+            -0x8000000000000000..=-0x80000001 | 0x80000000..=0x7fffffffffffffff => {
                 self.put_byte(TYPE_INT | 27)?;
                 self.put_bytes(
                     unsafe { &mut *(&mut v as *mut i64 as *mut [u8; 8]) },
@@ -180,8 +674,8 @@ impl<'a, W: Write> SerdeSerializer for &'a mut Serializer<W> {
     #[inline]
     fn serialize_u8(self, v: u8) -> Result<()> {
         match v {
-            0...23 => self.put_byte(TYPE_UINT | (v as u8 & VALUE_MASK)),
-            24...0xff | _ => {
+            0..=23 => self.put_byte(TYPE_UINT | (v & VALUE_MASK)),
+            24..=0xff => {
                 self.put_byte(TYPE_UINT | 24)?;
                 self.put_byte(v)
             }
@@ -191,12 +685,12 @@ impl<'a, W: Write> SerdeSerializer for &'a mut Serializer<W> {
     #[inline]
     fn serialize_u16(self, mut v: u16) -> Result<()> {
         match v {
-            0...23 => self.put_byte(TYPE_UINT | (v as u8 & VALUE_MASK)),
-            24...0xff => {
+            0..=23 => self.put_byte(TYPE_UINT | (v as u8 & VALUE_MASK)),
+            24..=0xff => {
                 self.put_byte(TYPE_UINT | 24)?;
                 self.put_byte(v as u8)
             }
-            0x100...0xffff | _ => {
+            0x100..=0xffff => {
                 self.put_byte(TYPE_UINT | 25)?;
                 self.put_bytes(
                     unsafe { &mut *(&mut v as *mut u16 as *mut [u8; 2]) },
@@ -209,19 +703,19 @@ impl<'a, W: Write> SerdeSerializer for &'a mut Serializer<W> {
     // #[inline]
     fn serialize_u32(self, mut v: u32) -> Result<()> {
         match v {
-            0...23 => self.put_byte(TYPE_UINT | (v as u8 & VALUE_MASK)),
-            24...0xff => {
+            0..=23 => self.put_byte(TYPE_UINT | (v as u8 & VALUE_MASK)),
+            24..=0xff => {
                 self.put_byte(TYPE_UINT | 24)?;
                 self.put_byte(v as u8)
             }
-            0x100...0xffff => {
+            0x100..=0xffff => {
                 self.put_byte(TYPE_UINT | 25)?;
                 self.put_bytes(
                     unsafe { &mut *(&mut (v as u16) as *mut u16 as *mut [u8; 2]) },
                     true
                 )
             }
-            0x10000...0xffffffff | _ => {
+            0x10000..=0xffffffff => {
                 self.put_byte(TYPE_UINT | 26)?;
                 self.put_bytes(
                     unsafe { &mut *(&mut v as *mut u32 as *mut [u8; 4]) },
@@ -233,27 +727,44 @@ impl<'a, W: Write> SerdeSerializer for &'a mut Serializer<W> {
 
     // #[inline]
     fn serialize_u64(self, mut v: u64) -> Result<()> {
+        if self.tag_next {
+            // First field of a `Tagged`
+            self.tag_next = false;
+
+            return self.put_tag(v);
+        }
+
+        if self.variant_next {
+            // First field of an `UnknownVariant`, which is always a `u32`
+            self.variant_next = false;
+
+            let index = u32::try_from(v)
+                .map_err(|_| ser::Error::custom("Variant index doesn't fit in a u32"))?;
+
+            return self.put_variant("", index, "");
+        }
+
         match v {
-            0...23 => self.put_byte(TYPE_UINT | (v as u8 & VALUE_MASK)),
-            24...0xff => {
+            0..=23 => self.put_byte(TYPE_UINT | (v as u8 & VALUE_MASK)),
+            24..=0xff => {
                 self.put_byte(TYPE_UINT | 24)?;
                 self.put_byte(v as u8)
             }
-            0x100...0xffff => {
+            0x100..=0xffff => {
                 self.put_byte(TYPE_UINT | 25)?;
                 self.put_bytes(
                     unsafe { &mut *(&mut (v as u16) as *mut u16 as *mut [u8; 2]) },
                     true
                 )
             }
-            0x10000...0xffffffff => {
+            0x10000..=0xffffffff => {
                 self.put_byte(TYPE_UINT | 26)?;
                 self.put_bytes(
                     unsafe { &mut *(&mut (v as u32) as *mut u32 as *mut [u8; 4]) },
                     true
                 )
             }
-            0x100000000...0xffffffffffffffff | _ => {
+            0x100000000..=0xffffffffffffffff => {
                 self.put_byte(TYPE_UINT | 27)?;
                 self.put_bytes(
                     unsafe { &mut *(&mut v as *mut u64 as *mut [u8; 8]) },
@@ -265,6 +776,21 @@ impl<'a, W: Write> SerdeSerializer for &'a mut Serializer<W> {
 
     #[inline]
     fn serialize_f32(self, mut v: f32) -> Result<()> {
+        if self.options.canonical && v.is_nan() {
+            v = f32::NAN;
+        }
+
+        if self.options.shrink_floats {
+            if let Some(mut half) = f32_to_f16(v) {
+                self.put_byte(TYPE_MISC | 6)?;
+
+                return self.put_bytes(
+                    unsafe { &mut *(&mut half as *mut u16 as *mut [u8; 2]) },
+                    true
+                );
+            }
+        }
+
         self.put_byte(TYPE_MISC | 4)?;
         self.put_bytes(
             unsafe { &mut *(&mut v as *mut f32 as *mut [u8; 4]) },
@@ -274,6 +800,14 @@ impl<'a, W: Write> SerdeSerializer for &'a mut Serializer<W> {
 
     #[inline]
     fn serialize_f64(self, mut v: f64) -> Result<()> {
+        if self.options.canonical && v.is_nan() {
+            return self.serialize_f32(f32::NAN);
+        }
+
+        if self.options.shrink_floats && (v as f32 as f64).to_bits() == v.to_bits() {
+            return self.serialize_f32(v as f32);
+        }
+
         self.put_byte(TYPE_MISC | 5)?;
         self.put_bytes(
             unsafe { &mut *(&mut v as *mut f64 as *mut [u8; 8]) },
@@ -295,45 +829,85 @@ impl<'a, W: Write> SerdeSerializer for &'a mut Serializer<W> {
 
     #[inline]
     fn serialize_str(self, v: &str) -> Result<()> {
+        if self.variant_next {
+            // First field of an `UnknownVariant` that was written by name
+            self.variant_next = false;
+
+            return self.put_variant(NAMED_VARIANT_TOKEN, 0, v);
+        }
+
         self.serialize_bytes(v.as_bytes())
     }
 
     // #[inline]
     fn serialize_bytes(self, v: &[u8]) -> Result<()> {
+        if self.raw_next {
+            self.raw_next = false;
+
+            return self.put_bytes(v, false);
+        }
+
         let len = v.len();
+        let align = self.align_next;
+
+        self.align_next = 0;
+
+        if align > 1 {
+            // Length of the inner instruction byte and its parameter
+            let header_len = match len {
+                0..=23 => 1,
+                24..=0xff => 2,
+                0x0100..=0xffff => 3,
+                0x00010000..=0xffffffff => 5,
+                _ => 9,
+            };
+            let padding = (align - (self.output.position() + 2 + header_len) % align) % align;
+
+            self.put_byte(TYPE_BYTES | 28)?;
+            self.put_byte(padding as u8)?;
+            self.put_bytes(&[0; 8][..padding], false)?;
+        } else if self.options.intern_strings && len >= MIN_INTERNED_LEN {
+            if let Some(&index) = self.interned.get(v) {
+                self.put_byte(TYPE_BYTES | 30)?;
+
+                return self.put_extended_param(index);
+            }
 
-        match len {
-            0...23 => {
+            let index = self.interned.len();
+
+            self.interned.insert(v.to_vec(), index);
+            self.put_byte(TYPE_BYTES | 29)?;
+        }
+
+        match len as u64 {
+            0..=23 => {
                 self.put_byte(TYPE_BYTES | len as u8)?;
             }
-            24...0xff => {
+            24..=0xff => {
                 self.put_byte(TYPE_BYTES | 24)?;
                 self.put_byte(len as u8)?;
             }
-            0x0100...0xffff => {
+            0x0100..=0xffff => {
                 self.put_byte(TYPE_BYTES | 25)?;
                 self.put_bytes(
                     unsafe { &mut *(&mut (len as u16) as *mut u16 as *mut [u8; 2]) },
                     true
                 )?;
             }
-            0x00010000...0xffffffff => {
+            0x00010000..=0xffffffff => {
                 self.put_byte(TYPE_BYTES | 26)?;
                 self.put_bytes(
                     unsafe { &mut *(&mut (len as u32) as *mut u32 as *mut [u8; 4]) },
                     true
                 )?;
             }
-            #[cfg(target_pointer_width = "64")]
-            0x0000000100000000...0xffffffffffffffff | _ => {
+            _ => {
                 self.put_byte(TYPE_BYTES | 27)?;
                 self.put_bytes(
                     unsafe { &mut *(&mut (len as u64) as *mut u64 as *mut [u8; 8]) },
                     true
                 )?;
             }
-            #[cfg(not(target_pointer_width = "64"))]
-            _ => Err(Error::UsizeOverflow), // Too many bytes to load for this current machine
         }
 
         self.put_bytes(v, false)
@@ -363,49 +937,82 @@ impl<'a, W: Write> SerdeSerializer for &'a mut Serializer<W> {
     }
 
     #[inline]
-    fn serialize_unit_variant(self, _name: &'static str, variant_index: u32,
-        _variant: &'static str) -> Result<()>
+    fn serialize_unit_variant(self, name: &'static str, variant_index: u32,
+        variant: &'static str) -> Result<()>
     {
-        self.serialize_u32(variant_index)
+        // Unit variants are written like every other variant, with a unit as their payload, so that
+        //   they can't be mistaken for numbers
+        self.put_variant(name, variant_index, variant)?;
+
+        self.put_byte(TYPE_MISC | 2)
     }
 
     #[inline]
-    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<()>
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<()>
     where
         T: ?Sized + Serialize
     {
+        if name == SHARED_TOKEN && !self.options.canonical {
+            // Shared pointers are told apart by the address of the value they point to
+            let address = value as *const T as *const () as usize;
+            let pointer = shared::take_serialized();
+
+            if let Some(&index) = self.anchors.get(&address) {
+                self.put_byte(TYPE_EXT | 29)?;
+
+                return self.put_extended_param(index);
+            }
+
+            let index = self.anchors.len();
+
+            self.anchors.insert(address, index);
+            self.pointers.extend(pointer);
+            self.put_byte(TYPE_EXT | 28)?;
+
+            return value.serialize(self);
+        }
+
+        if name == RAW_TOKEN {
+            // The bytes of a `RawDbor` are already encoded, so they're written without a length
+            self.raw_next = true;
+
+            return value.serialize(self);
+        }
+
+        if name == INTERN_TOKEN || name == SHRINK_FLOATS_TOKEN {
+            let options = self.options;
+
+            if name == SHRINK_FLOATS_TOKEN {
+                self.options.shrink_floats = true;
+            } else if !self.options.canonical {
+                self.options.intern_strings = true;
+            }
+
+            let result = value.serialize(&mut *self);
+            self.options = options;
+
+            return result;
+        }
+
+        if self.options.align_packed {
+            self.align_next = match name {
+                ALIGN_2_TOKEN => 2,
+                ALIGN_4_TOKEN => 4,
+                ALIGN_8_TOKEN => 8,
+                _ => 0,
+            };
+        }
+
         value.serialize(self)
     }
 
     // #[inline]
-    fn serialize_newtype_variant<T>(self, _name: &'static str, mut variant_index: u32,
-        _variant: &'static str, value: &T) -> Result<()>
+    fn serialize_newtype_variant<T>(self, name: &'static str, variant_index: u32,
+        variant: &'static str, value: &T) -> Result<()>
     where
         T: ?Sized + Serialize
     {
-        match variant_index {
-            0...23 => {
-                self.put_byte(TYPE_VARIANT | (variant_index as u8 & VALUE_MASK))?;
-            }
-            24...0xff => {
-                self.put_byte(TYPE_VARIANT | 24)?;
-                self.put_byte(variant_index as u8)?;
-            }
-            0x100...0xffff => {
-                self.put_byte(TYPE_VARIANT | 25)?;
-                self.put_bytes(
-                    unsafe { &mut *(&mut (variant_index as u16) as *mut u16 as *mut [u8; 2]) },
-                    true
-                )?;
-            }
-            0x10000...0xffffffff | _ => {
-                self.put_byte(TYPE_VARIANT | 26)?;
-                self.put_bytes(
-                    unsafe { &mut *(&mut variant_index as *mut u32 as *mut [u8; 4]) },
-                    true
-                )?;
-            }
-        }
+        self.put_variant(name, variant_index, variant)?;
 
         value.serialize(&mut *self)?;
 
@@ -415,38 +1022,35 @@ impl<'a, W: Write> SerdeSerializer for &'a mut Serializer<W> {
     // #[inline]
     fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq> {
         match len {
-            Some(len) => match len {
-                0...23 => {
+            Some(len) => match len as u64 {
+                0..=23 => {
                     self.put_byte(TYPE_SEQ | len as u8)?;
                 }
-                24...0xff => {
+                24..=0xff => {
                     self.put_byte(TYPE_SEQ | 24)?;
                     self.put_byte(len as u8)?;
                 }
-                0x0100...0xffff => {
+                0x0100..=0xffff => {
                     self.put_byte(TYPE_SEQ | 25)?;
                     self.put_bytes(
                         unsafe { &mut *(&mut (len as u16) as *mut u16 as *mut [u8; 2]) },
                         true
                     )?;
                 }
-                0x00010000...0xffffffff => {
+                0x00010000..=0xffffffff => {
                     self.put_byte(TYPE_SEQ | 26)?;
                     self.put_bytes(
                         unsafe { &mut *(&mut (len as u32) as *mut u32 as *mut [u8; 4]) },
                         true
                     )?;
                 }
-                #[cfg(target_pointer_width = "64")]
-                0x0000000100000000...0xffffffffffffffff | _ => {
+                _ => {
                     self.put_byte(TYPE_SEQ | 27)?;
                     self.put_bytes(
                         unsafe { &mut *(&mut (len as u64) as *mut u64 as *mut [u8; 8]) },
                         true
                     )?;
                 }
-                #[cfg(not(target_pointer_width = "64"))]
-                _ => Err(Error::UsizeOverflow), // Too many bytes to load for this current machine
             }
             None => return Err(Error::MustKnowItemSize), // Must know the size of an array ahead of time
         }
@@ -460,72 +1064,62 @@ impl<'a, W: Write> SerdeSerializer for &'a mut Serializer<W> {
     }
 
     #[inline]
-    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<Self::SerializeTuple> {
+    fn serialize_tuple_struct(self, name: &'static str, len: usize) -> Result<Self::SerializeTuple> {
+        if name == TAGGED_TOKEN {
+            // The tag number is written by `serialize_u64`, so there's no seq around the value
+            self.tag_next = true;
+
+            return Ok(self);
+        }
+
+        if name == UNKNOWN_VARIANT_TOKEN {
+            // The variant instruction byte is written by `serialize_u64` or `serialize_str`, and
+            //   the payload follows it
+            self.variant_next = true;
+
+            return Ok(self);
+        }
+
         self.serialize_seq(Some(len))
     }
 
     // #[inline]
-    fn serialize_tuple_variant(self, _name: &'static str, mut variant_index: u32,
-        _variant: &'static str, len: usize) -> Result<Self::SerializeTupleVariant>
+    fn serialize_tuple_variant(self, name: &'static str, variant_index: u32,
+        variant: &'static str, len: usize) -> Result<Self::SerializeTupleVariant>
     {
         // Start variant
-        match variant_index {
-            0...23 => {
-                self.put_byte(TYPE_VARIANT | (variant_index as u8 & VALUE_MASK))?;
-            }
-            24...0xff => {
-                self.put_byte(TYPE_VARIANT | 24)?;
-                self.put_byte(variant_index as u8)?;
-            }
-            0x100...0xffff => {
-                self.put_byte(TYPE_VARIANT | 25)?;
-                self.put_bytes(
-                    unsafe { &mut *(&mut (variant_index as u16) as *mut u16 as *mut [u8; 2]) },
-                    true
-                )?;
-            }
-            0x10000...0xffffffff | _ => {
-                self.put_byte(TYPE_VARIANT | 26)?;
-                self.put_bytes(
-                    unsafe { &mut *(&mut variant_index as *mut u32 as *mut [u8; 4]) },
-                    true
-                )?;
-            }
-        }
+        self.put_variant(name, variant_index, variant)?;
 
         // Start seq
-        match len {
-            0...23 => {
+        match len as u64 {
+            0..=23 => {
                 self.put_byte(TYPE_SEQ | len as u8)?;
             }
-            24...0xff => {
+            24..=0xff => {
                 self.put_byte(TYPE_SEQ | 24)?;
                 self.put_byte(len as u8)?;
             }
-            0x0100...0xffff => {
+            0x0100..=0xffff => {
                 self.put_byte(TYPE_SEQ | 25)?;
                 self.put_bytes(
                     unsafe { &mut *(&mut (len as u16) as *mut u16 as *mut [u8; 2]) },
                     true
                 )?;
             }
-            0x00010000...0xffffffff => {
+            0x00010000..=0xffffffff => {
                 self.put_byte(TYPE_SEQ | 26)?;
                 self.put_bytes(
                     unsafe { &mut *(&mut (len as u32) as *mut u32 as *mut [u8; 4]) },
                     true
                 )?;
             }
-            #[cfg(target_pointer_width = "64")]
-            0x0000000100000000...0xffffffffffffffff | _ => {
+            _ => {
                 self.put_byte(TYPE_SEQ | 27)?;
                 self.put_bytes(
                     unsafe { &mut *(&mut (len as u64) as *mut u64 as *mut [u8; 8]) },
                     true
                 )?;
             }
-            #[cfg(not(target_pointer_width = "64"))]
-            _ => Err(Error::UsizeOverflow), // Too many bytes to load for this current machine
         }
 
         Ok(self)
@@ -535,119 +1129,107 @@ impl<'a, W: Write> SerdeSerializer for &'a mut Serializer<W> {
     fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeTupleVariant>
     {
         match len {
-            Some(len) => match len {
-                0...23 => {
+            Some(len) => match len as u64 {
+                0..=23 => {
                     self.put_byte(TYPE_MAP | len as u8)?;
                 }
-                24...0xff => {
+                24..=0xff => {
                     self.put_byte(TYPE_MAP | 24)?;
                     self.put_byte(len as u8)?;
                 }
-                0x0100...0xffff => {
+                0x0100..=0xffff => {
                     self.put_byte(TYPE_MAP | 25)?;
                     self.put_bytes(
                         unsafe { &mut *(&mut (len as u16) as *mut u16 as *mut [u8; 2]) },
                         true
                     )?;
                 }
-                0x00010000...0xffffffff => {
+                0x00010000..=0xffffffff => {
                     self.put_byte(TYPE_MAP | 26)?;
                     self.put_bytes(
                         unsafe { &mut *(&mut (len as u32) as *mut u32 as *mut [u8; 4]) },
                         true
                     )?;
                 }
-                #[cfg(target_pointer_width = "64")]
-                0x0000000100000000...0xffffffffffffffff | _ => {
+                _ => {
                     self.put_byte(TYPE_MAP | 27)?;
                     self.put_bytes(
                         unsafe { &mut *(&mut (len as u64) as *mut u64 as *mut [u8; 8]) },
                         true
                     )?;
                 }
-                #[cfg(not(target_pointer_width = "64"))]
-                _ => Err(Error::UsizeOverflow), // Too many bytes to load for this current machine
             }
             None => return Err(Error::MustKnowItemSize), // Must know the size of an array ahead of time
         }
 
+        if self.options.canonical {
+            self.entries.push(Vec::new());
+        }
+
         Ok(self)
     }
 
     #[inline]
-    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Self::SerializeStruct> {
+    fn serialize_struct(self, name: &'static str, len: usize) -> Result<Self::SerializeStruct> {
+        if self.options.field_ids {
+            self.structs.push((explicit_ids(name), 0));
+
+            return self.serialize_map(Some(len));
+        }
+
         self.serialize_seq(Some(len))
     }
 
     // #[inline]
-    fn serialize_struct_variant(self, _name: &'static str, mut variant_index: u32,
-        _variant: &'static str, len: usize) -> Result<Self::SerializeStructVariant>
+    fn serialize_struct_variant(self, name: &'static str, variant_index: u32,
+        variant: &'static str, len: usize) -> Result<Self::SerializeStructVariant>
     {
         // Start variant
-        match variant_index {
-            0...23 => {
-                self.put_byte(TYPE_VARIANT | (variant_index as u8 & VALUE_MASK))?;
-            }
-            24...0xff => {
-                self.put_byte(TYPE_VARIANT | 24)?;
-                self.put_byte(variant_index as u8)?;
-            }
-            0x100...0xffff => {
-                self.put_byte(TYPE_VARIANT | 25)?;
-                self.put_bytes(
-                    unsafe { &mut *(&mut (variant_index as u16) as *mut u16 as *mut [u8; 2]) },
-                    true
-                )?;
-            }
-            0x10000...0xffffffff | _ => {
-                self.put_byte(TYPE_VARIANT | 26)?;
-                self.put_bytes(
-                    unsafe { &mut *(&mut variant_index as *mut u32 as *mut [u8; 4]) },
-                    true
-                )?;
-            }
+        self.put_variant(name, variant_index, variant)?;
+
+        if self.options.field_ids {
+            self.structs.push((None, 0));
+
+            return self.serialize_map(Some(len));
         }
 
         // Start seq
-        match len {
-            0...23 => {
+        match len as u64 {
+            0..=23 => {
                 self.put_byte(TYPE_SEQ | len as u8)?;
             }
-            24...0xff => {
+            24..=0xff => {
                 self.put_byte(TYPE_SEQ | 24)?;
                 self.put_byte(len as u8)?;
             }
-            0x0100...0xffff => {
+            0x0100..=0xffff => {
                 self.put_byte(TYPE_SEQ | 25)?;
                 self.put_bytes(
                     unsafe { &mut *(&mut (len as u16) as *mut u16 as *mut [u8; 2]) },
                     true
                 )?;
             }
-            0x00010000...0xffffffff => {
+            0x00010000..=0xffffffff => {
                 self.put_byte(TYPE_SEQ | 26)?;
                 self.put_bytes(
                     unsafe { &mut *(&mut (len as u32) as *mut u32 as *mut [u8; 4]) },
                     true
                 )?;
             }
-            #[cfg(target_pointer_width = "64")]
-            0x0000000100000000...0xffffffffffffffff | _ => {
+            _ => {
                 self.put_byte(TYPE_SEQ | 27)?;
                 self.put_bytes(
                     unsafe { &mut *(&mut (len as u64) as *mut u64 as *mut [u8; 8]) },
                     true
                 )?;
             }
-            #[cfg(not(target_pointer_width = "64"))]
-            _ => Err(Error::UsizeOverflow), // Too many bytes to load for this current machine
         }
 
         Ok(self)
     }
 }
 
-impl<'a, W: Write> ser::SerializeSeq for &'a mut Serializer<W> {
+impl<W: Write> ser::SerializeSeq for &mut Serializer<W> {
     type Ok = ();
     type Error = Error;
 
@@ -665,7 +1247,7 @@ impl<'a, W: Write> ser::SerializeSeq for &'a mut Serializer<W> {
     }
 }
 
-impl<'a, W: Write> ser::SerializeTuple for &'a mut Serializer<W> {
+impl<W: Write> ser::SerializeTuple for &mut Serializer<W> {
     type Ok = ();
     type Error = Error;
 
@@ -683,7 +1265,7 @@ impl<'a, W: Write> ser::SerializeTuple for &'a mut Serializer<W> {
     }
 }
 
-impl<'a, W: Write> ser::SerializeTupleStruct for &'a mut Serializer<W> {
+impl<W: Write> ser::SerializeTupleStruct for &mut Serializer<W> {
     type Ok = ();
     type Error = Error;
 
@@ -701,7 +1283,7 @@ impl<'a, W: Write> ser::SerializeTupleStruct for &'a mut Serializer<W> {
     }
 }
 
-impl<'a, W: Write> ser::SerializeTupleVariant for &'a mut Serializer<W> {
+impl<W: Write> ser::SerializeTupleVariant for &mut Serializer<W> {
     type Ok = ();
     type Error = Error;
 
@@ -719,7 +1301,7 @@ impl<'a, W: Write> ser::SerializeTupleVariant for &'a mut Serializer<W> {
     }
 }
 
-impl<'a, W: Write> ser::SerializeMap for &'a mut Serializer<W> {
+impl<W: Write> ser::SerializeMap for &mut Serializer<W> {
     type Ok = ();
     type Error = Error;
 
@@ -728,6 +1310,16 @@ impl<'a, W: Write> ser::SerializeMap for &'a mut Serializer<W> {
     where
         T: ?Sized + Serialize
     {
+        if self.options.canonical {
+            let key = self.capture(|ser| key.serialize(ser))?;
+
+            if let Some(entries) = self.entries.last_mut() {
+                entries.push((key, Vec::new()));
+            }
+
+            return Ok(());
+        }
+
         key.serialize(&mut **self)
     }
 
@@ -736,49 +1328,115 @@ impl<'a, W: Write> ser::SerializeMap for &'a mut Serializer<W> {
     where
         T: ?Sized + Serialize
     {
+        if self.options.canonical {
+            let value = self.capture(|ser| value.serialize(ser))?;
+
+            if let Some(entry) = self.entries.last_mut().and_then(|entries| entries.last_mut()) {
+                entry.1 = value;
+            }
+
+            return Ok(());
+        }
+
         value.serialize(&mut **self)
     }
 
-    #[inline]
     fn end(self) -> Result<()> {
+        if self.options.canonical {
+            let mut entries = self.entries.pop().unwrap_or_default();
+
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+            for pair in entries.windows(2) {
+                if pair[0].0 == pair[1].0 {
+                    return Err(Error::DuplicateMapKey(pair[0].0.clone()));
+                }
+            }
+
+            for (key, value) in entries {
+                self.put_bytes(&key, false)?;
+                self.put_bytes(&value, false)?;
+            }
+        }
+
         Ok(())
     }
 }
 
-impl<'a, W: Write> ser::SerializeStruct for &'a mut Serializer<W> {
+impl<W: Write> ser::SerializeStruct for &mut Serializer<W> {
     type Ok = ();
     type Error = Error;
 
     #[inline]
-    fn serialize_field<T>(&mut self, _key: &'static str, value: &T) -> Result<()>
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
     where
         T: ?Sized + Serialize
     {
+        if self.options.field_ids {
+            return self.serialize_field_id(key, value);
+        }
+
         // Treat struct as a seq
         value.serialize(&mut **self)
     }
 
+    #[inline]
+    fn skip_field(&mut self, _key: &'static str) -> Result<()> {
+        // Fields that are skipped still use up a number
+        if let Some(&mut (_, ref mut next)) = self.structs.last_mut() {
+            *next += 1;
+        }
+
+        Ok(())
+    }
+
     #[inline]
     fn end(self) -> Result<()> {
+        if self.options.field_ids {
+            self.structs.pop();
+
+            return ser::SerializeMap::end(self);
+        }
+
         Ok(())
     }
 }
 
-impl<'a, W: Write> ser::SerializeStructVariant for &'a mut Serializer<W> {
+impl<W: Write> ser::SerializeStructVariant for &mut Serializer<W> {
     type Ok = ();
     type Error = Error;
 
     #[inline]
-    fn serialize_field<T>(&mut self, _key: &'static str, value: &T) -> Result<()>
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
     where
         T: ?Sized + Serialize
     {
+        if self.options.field_ids {
+            return self.serialize_field_id(key, value);
+        }
+
         // Treat struct as a seq
         value.serialize(&mut **self)
     }
 
+    #[inline]
+    fn skip_field(&mut self, _key: &'static str) -> Result<()> {
+        // Fields that are skipped still use up a number
+        if let Some(&mut (_, ref mut next)) = self.structs.last_mut() {
+            *next += 1;
+        }
+
+        Ok(())
+    }
+
     #[inline]
     fn end(self) -> Result<()> {
+        if self.options.field_ids {
+            self.structs.pop();
+
+            return ser::SerializeMap::end(self);
+        }
+
         Ok(())
     }
 }
@@ -1,5 +1,14 @@
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use core::cmp;
+#[cfg(feature = "std")]
+use alloc::boxed::Box;
+#[cfg(feature = "std")]
 use std::io::{Write as IoWrite, ErrorKind as IoErrorKind};
 
+#[cfg(feature = "std")]
+use checksum::Crc32c;
+use checksum::{CHECKSUM_LEN, crc32c};
 use error::*;
 
 
@@ -8,32 +17,49 @@ pub trait Write {
 
     fn put_byte(&mut self, byte: u8) -> Result<()>;
     fn put_bytes(&mut self, bytes: &[u8], flipped: bool) -> Result<()>;
+    fn position(&self) -> usize;
+    // Starts (or restarts) a checksum of every byte written from here on, which `finish` writes
+    //   out as a trailer
+    fn start_checksum(&mut self);
     fn finish(self) -> Result<Self::Output>;
 }
 
-const MAX_BUF_LEN: usize = 1024;
+// The size of the buffer used by `to_writer`
+#[cfg(feature = "std")]
+pub(crate) const DEFAULT_BUF_LEN: usize = 1024;
 
+#[cfg(feature = "std")]
 pub(crate) struct IoWriter<T: IoWrite> {
     internal: T,
-    buffer: [u8; MAX_BUF_LEN],
+    buffer: Box<[u8]>,
     buf_len: usize,
+    flushed: usize,
+    checksum: Option<Crc32c>,
 }
 
+#[cfg(feature = "std")]
 impl<T: IoWrite> IoWriter<T> {
-    pub fn new(writer: T) -> Self {
+    pub fn with_capacity(writer: T, capacity: usize) -> Self {
         Self {
             internal: writer,
-            buffer: [0; 1024],
-            buf_len: 0
+            buffer: vec![0; cmp::max(capacity, 1)].into_boxed_slice(),
+            buf_len: 0,
+            flushed: 0,
+            checksum: None,
         }
     }
 }
 
+#[cfg(feature = "std")]
 impl<T: IoWrite> Write for IoWriter<T> {
     type Output = T;
 
     fn put_byte(&mut self, byte: u8) -> Result<()> {
-        if self.buf_len < MAX_BUF_LEN {
+        if let Some(ref mut checksum) = self.checksum {
+            checksum.update(&[byte]);
+        }
+
+        if self.buf_len < self.buffer.len() {
             self.buffer[self.buf_len] = byte;
             self.buf_len += 1;
             Ok(())
@@ -49,6 +75,8 @@ impl<T: IoWrite> Write for IoWriter<T> {
                     })?;
             }
 
+            self.flushed += self.buf_len;
+
             // Write new byte to buffer
             self.buf_len = 1;
             self.buffer[0] = byte;
@@ -69,7 +97,11 @@ impl<T: IoWrite> Write for IoWriter<T> {
             bytes = &bytes_copy[..];
         }
 
-        if self.buf_len + bytes.len() <= MAX_BUF_LEN {
+        if let Some(ref mut checksum) = self.checksum {
+            checksum.update(bytes);
+        }
+
+        if self.buf_len + bytes.len() <= self.buffer.len() {
             self.buffer[self.buf_len..self.buf_len + bytes.len()].copy_from_slice(bytes);
             self.buf_len += bytes.len();
             Ok(())
@@ -85,10 +117,12 @@ impl<T: IoWrite> Write for IoWriter<T> {
                     })?;
             }
 
+            self.flushed += self.buf_len;
+
             // Write as many bytes as possible while the remaining bytes don't fit in the buffer
             bytes_written = 0;
 
-            while bytes.len() - bytes_written >= MAX_BUF_LEN {
+            while bytes.len() - bytes_written >= self.buffer.len() {
                 bytes_written += self.internal.write(&bytes[bytes_written..])
                     .or_else(|io_error| match io_error.kind() {
                         IoErrorKind::Interrupted => Ok(0),
@@ -96,6 +130,8 @@ impl<T: IoWrite> Write for IoWriter<T> {
                     })?;
             }
 
+            self.flushed += bytes_written;
+
             // Write remaining new data to buffer
             self.buf_len = bytes.len() - bytes_written;
             self.buffer[..bytes.len() - bytes_written].copy_from_slice(&bytes[bytes_written..]);
@@ -104,7 +140,21 @@ impl<T: IoWrite> Write for IoWriter<T> {
         }
     }
 
+    #[inline]
+    fn position(&self) -> usize {
+        self.flushed + self.buf_len
+    }
+
+    #[inline]
+    fn start_checksum(&mut self) {
+        self.checksum = Some(Crc32c::new());
+    }
+
     fn finish(mut self) -> Result<Self::Output> {
+        if let Some(checksum) = self.checksum.take() {
+            self.put_bytes(&checksum.finish().to_le_bytes(), false)?;
+        }
+
         let mut bytes_written = 0;
 
         while bytes_written < self.buf_len {
@@ -120,20 +170,31 @@ impl<T: IoWrite> Write for IoWriter<T> {
 }
 
 
-pub(crate) struct VecWriter {
-    internal: Vec<u8>,
+// Appends to a vec, with positions counted from wherever the vec ended when writing started
+pub(crate) struct VecWriter<'a> {
+    internal: &'a mut Vec<u8>,
+    start: usize,
+    // Where the checksummed bytes start, which are all checksummed at once when writing finishes
+    checksum_start: Option<usize>,
 }
 
-impl VecWriter {
-    pub fn new() -> Self {
+impl<'a> VecWriter<'a> {
+    pub fn new(vec: &'a mut Vec<u8>) -> Self {
         Self {
-            internal: Vec::new(),
+            start: vec.len(),
+            internal: vec,
+            checksum_start: None,
         }
     }
+
+    // Removes everything that was appended, for when serialization fails halfway through
+    pub fn undo(self) {
+        self.internal.truncate(self.start);
+    }
 }
 
-impl Write for VecWriter {
-    type Output = Vec<u8>;
+impl<'a> Write for VecWriter<'a> {
+    type Output = ();
 
     #[inline]
     fn put_byte(&mut self, byte: u8) -> Result<()> {
@@ -144,20 +205,161 @@ impl Write for VecWriter {
 
     fn put_bytes(&mut self, bytes: &[u8], flipped: bool) -> Result<()> {
         if flipped {
-            let mut bytes_copy = Vec::from(bytes);
+            self.internal.extend(bytes.iter().rev());
+        } else {
+            self.internal.extend_from_slice(bytes);
+        }
 
-            bytes_copy.reverse();
+        Ok(())
+    }
+
+    #[inline]
+    fn position(&self) -> usize {
+        self.internal.len() - self.start
+    }
+
+    #[inline]
+    fn start_checksum(&mut self) {
+        self.checksum_start = Some(self.internal.len());
+    }
+
+    #[inline]
+    fn finish(self) -> Result<Self::Output> {
+        if let Some(start) = self.checksum_start {
+            let checksum = crc32c(&self.internal[start..]);
+
+            self.internal.extend_from_slice(&checksum.to_le_bytes());
+        }
+
+        Ok(())
+    }
+}
+
+
+pub(crate) struct SliceWriter<'a> {
+    internal: &'a mut [u8],
+    len: usize,
+    checksum_start: Option<usize>,
+}
+
+impl<'a> SliceWriter<'a> {
+    pub fn new(slice: &'a mut [u8]) -> Self {
+        Self {
+            internal: slice,
+            len: 0,
+            checksum_start: None,
+        }
+    }
+}
+
+impl<'a> Write for SliceWriter<'a> {
+    type Output = usize;
+
+    #[inline]
+    fn put_byte(&mut self, byte: u8) -> Result<()> {
+        match self.internal.get_mut(self.len) {
+            Some(slot) => {
+                *slot = byte;
+                self.len += 1;
+
+                Ok(())
+            }
+            None => Err(Error::BufferTooSmall),
+        }
+    }
 
-            self.internal.append(&mut bytes_copy);
+    fn put_bytes(&mut self, bytes: &[u8], flipped: bool) -> Result<()> {
+        if self.internal.len() - self.len < bytes.len() {
+            return Err(Error::BufferTooSmall);
+        }
+
+        let dest = &mut self.internal[self.len..self.len + bytes.len()];
+
+        if flipped {
+            for (slot, &byte) in dest.iter_mut().zip(bytes.iter().rev()) {
+                *slot = byte;
+            }
         } else {
-            self.internal.extend_from_slice(bytes);
+            dest.copy_from_slice(bytes);
         }
 
+        self.len += bytes.len();
+
         Ok(())
     }
 
+    #[inline]
+    fn position(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    fn start_checksum(&mut self) {
+        self.checksum_start = Some(self.len);
+    }
+
+    #[inline]
+    fn finish(mut self) -> Result<Self::Output> {
+        if let Some(start) = self.checksum_start {
+            let checksum = crc32c(&self.internal[start..self.len]);
+
+            self.put_bytes(&checksum.to_le_bytes(), false)?;
+        }
+
+        Ok(self.len)
+    }
+}
+
+
+// Counts bytes without storing them, which keeps `position` (and so alignment padding) exactly the
+//   same as it would be in a `VecWriter`
+pub(crate) struct SizeWriter {
+    len: usize,
+    checksum: bool,
+}
+
+impl SizeWriter {
+    pub fn new() -> Self {
+        Self {
+            len: 0,
+            checksum: false,
+        }
+    }
+}
+
+impl Write for SizeWriter {
+    type Output = usize;
+
+    #[inline]
+    fn put_byte(&mut self, _byte: u8) -> Result<()> {
+        self.len += 1;
+
+        Ok(())
+    }
+
+    #[inline]
+    fn put_bytes(&mut self, bytes: &[u8], _flipped: bool) -> Result<()> {
+        self.len += bytes.len();
+
+        Ok(())
+    }
+
+    #[inline]
+    fn position(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    fn start_checksum(&mut self) {
+        self.checksum = true;
+    }
+
     #[inline]
     fn finish(self) -> Result<Self::Output> {
-        Ok(self.internal)
+        if self.checksum {
+            Ok(self.len + CHECKSUM_LEN)
+        } else {
+            Ok(self.len)
+        }
     }
 }
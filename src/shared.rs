@@ -0,0 +1,308 @@
+use core::any::Any;
+#[cfg(feature = "std")]
+use core::cell::RefCell;
+#[cfg(feature = "std")]
+use core::fmt;
+#[cfg(feature = "std")]
+use core::marker::PhantomData;
+#[cfg(feature = "std")]
+use core::ops::Deref;
+use alloc::rc::Rc;
+#[cfg(feature = "std")]
+use alloc::sync::Arc;
+
+#[cfg(feature = "std")]
+use serde::ser::{Serialize, Serializer};
+#[cfg(feature = "std")]
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+
+
+// Newtype name used to tell DBOR that a value lives behind a shared pointer. Other serializers just
+//   see a newtype struct wrapping the value.
+pub(crate) const SHARED_TOKEN: &str = "$serde_dbor::private::Shared";
+
+// How shared pointers get handed between the deserializer and `Shared`'s visitor, which otherwise
+//   have no way of talking to each other
+#[cfg(feature = "std")]
+struct Slot {
+    // How many anchored values are currently being deserialized
+    anchoring: usize,
+    value: Option<Rc<dyn Any>>,
+    // The pointer that's currently being serialized
+    serialized: Option<Rc<dyn Any>>,
+}
+
+#[cfg(feature = "std")]
+thread_local! {
+    static SLOT: RefCell<Slot> = const {
+        RefCell::new(Slot {
+            anchoring: 0,
+            value: None,
+            serialized: None,
+        })
+    };
+}
+
+// Called by the deserializer before deserializing an anchored value
+#[cfg(feature = "std")]
+pub(crate) fn begin_anchor() {
+    SLOT.with(|slot| slot.borrow_mut().anchoring += 1);
+}
+
+// Called by the deserializer after deserializing an anchored value, returns the pointer that was
+//   created for it (if any)
+#[cfg(feature = "std")]
+pub(crate) fn finish_anchor() -> Option<Rc<dyn Any>> {
+    SLOT.with(|slot| {
+        let mut slot = slot.borrow_mut();
+
+        slot.anchoring -= 1;
+        slot.value.take()
+    })
+}
+
+// Called by the deserializer right before telling the visitor about a reference
+#[cfg(feature = "std")]
+pub(crate) fn provide_reference(value: Rc<dyn Any>) {
+    SLOT.with(|slot| slot.borrow_mut().value = Some(value));
+}
+
+// Called by `Shared`'s visitor for every pointer it creates
+#[cfg(feature = "std")]
+fn offer_pointer(value: Rc<dyn Any>) {
+    SLOT.with(|slot| {
+        let mut slot = slot.borrow_mut();
+
+        if slot.anchoring > 0 {
+            slot.value = Some(value);
+        }
+    });
+}
+
+// Called by `Shared`'s visitor when it's given a reference
+#[cfg(feature = "std")]
+fn take_reference() -> Option<Rc<dyn Any>> {
+    SLOT.with(|slot| slot.borrow_mut().value.take())
+}
+
+// Called by the serializer when it's given a shared value, returns a clone of its pointer
+#[cfg(feature = "std")]
+pub(crate) fn take_serialized() -> Option<Rc<dyn Any>> {
+    SLOT.with(|slot| slot.borrow_mut().serialized.take())
+}
+
+// Without thread locals there's nowhere safe to keep the slot, so `Shared` needs `std`. Data
+//   with shared values in it can still be deserialized into plain types, since anchors and
+//   references are resolved by the deserializer itself.
+#[cfg(not(feature = "std"))]
+pub(crate) fn begin_anchor() {}
+
+#[cfg(not(feature = "std"))]
+pub(crate) fn finish_anchor() -> Option<Rc<dyn Any>> {
+    None
+}
+
+#[cfg(not(feature = "std"))]
+pub(crate) fn provide_reference(_value: Rc<dyn Any>) {}
+
+#[cfg(not(feature = "std"))]
+pub(crate) fn take_serialized() -> Option<Rc<dyn Any>> {
+    None
+}
+
+
+/// A shared pointer that keeps its sharing when serialized as DBOR
+///
+/// The first time a pointer is serialized its value is written out in full and marked as an
+/// anchor, and every other copy of the same pointer is written as a short reference to that
+/// anchor. When deserializing, every reference is turned back into a clone of the pointer created
+/// for its anchor, so a DAG of `Rc`s comes back as the same DAG instead of as a tree.
+///
+/// Other formats see `Shared` as a plain newtype around the pointed-to value.
+///
+/// The first copy of a value doesn't have to be deserialized as a `Shared`: if it's skipped (like
+/// with `IgnoredAny`) the deserializer keeps a copy of its bytes, and the first reference to it
+/// creates the pointer instead.
+///
+/// Cycles (through `RefCell`s for example) can be serialized, since a pointer that is still being
+/// written out becomes a reference, but they can't be deserialized because the pointer doesn't
+/// exist yet, and an `Error::UnknownAnchor` is returned instead.
+///
+/// Deserializing shared values relies on thread locals, so `Shared` is only available with the
+/// `std` feature.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Shared<P>(pub P);
+
+#[cfg(feature = "std")]
+impl<P> Shared<P> {
+    /// Unwraps the shared pointer
+    #[inline]
+    pub fn into_inner(self) -> P {
+        self.0
+    }
+}
+
+#[cfg(feature = "std")]
+impl<P> Deref for Shared<P> {
+    type Target = P;
+
+    #[inline]
+    fn deref(&self) -> &P {
+        &self.0
+    }
+}
+
+#[cfg(feature = "std")]
+impl<P> From<P> for Shared<P> {
+    #[inline]
+    fn from(pointer: P) -> Self {
+        Shared(pointer)
+    }
+}
+
+/// A pointer type that can be wrapped by `Shared`
+#[cfg(feature = "std")]
+pub trait SharedPointer: Clone + Sized {
+    /// The type being pointed to
+    type Target;
+
+    #[doc(hidden)]
+    fn get(&self) -> &Self::Target;
+
+    #[doc(hidden)]
+    fn new(value: Self::Target) -> Self;
+
+    #[doc(hidden)]
+    fn to_any(&self) -> Rc<dyn Any>;
+
+    #[doc(hidden)]
+    fn from_any(any: Rc<dyn Any>) -> Option<Self>;
+}
+
+#[cfg(feature = "std")]
+impl<T: 'static> SharedPointer for Rc<T> {
+    type Target = T;
+
+    #[inline]
+    fn get(&self) -> &T {
+        self
+    }
+
+    #[inline]
+    fn new(value: T) -> Self {
+        Rc::new(value)
+    }
+
+    #[inline]
+    fn to_any(&self) -> Rc<dyn Any> {
+        self.clone()
+    }
+
+    #[inline]
+    fn from_any(any: Rc<dyn Any>) -> Option<Self> {
+        any.downcast().ok()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: 'static> SharedPointer for Arc<T> {
+    type Target = T;
+
+    #[inline]
+    fn get(&self) -> &T {
+        self
+    }
+
+    #[inline]
+    fn new(value: T) -> Self {
+        Arc::new(value)
+    }
+
+    #[inline]
+    fn to_any(&self) -> Rc<dyn Any> {
+        Rc::new(self.clone())
+    }
+
+    #[inline]
+    fn from_any(any: Rc<dyn Any>) -> Option<Self> {
+        any.downcast_ref::<Self>().cloned()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<P> Serialize for Shared<P>
+where
+    P: SharedPointer,
+    P::Target: Serialize,
+{
+    #[inline]
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // The address of the value is used by the serializer to tell pointers apart, and it keeps
+        //   a clone of the pointer so that the address can't be reused by another value while it's
+        //   still serializing
+        SLOT.with(|slot| slot.borrow_mut().serialized = Some(self.0.to_any()));
+
+        let result = serializer.serialize_newtype_struct(SHARED_TOKEN, self.0.get());
+
+        SLOT.with(|slot| slot.borrow_mut().serialized = None);
+
+        result
+    }
+}
+
+#[cfg(feature = "std")]
+struct SharedVisitor<P> {
+    phantom: PhantomData<P>,
+}
+
+#[cfg(feature = "std")]
+impl<'de, P> Visitor<'de> for SharedVisitor<P>
+where
+    P: SharedPointer,
+    P::Target: Deserialize<'de>,
+{
+    type Value = Shared<P>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a shared value")
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>
+    {
+        let pointer = P::new(P::Target::deserialize(deserializer)?);
+
+        offer_pointer(pointer.to_any());
+
+        Ok(Shared(pointer))
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        // A reference to a value that has already been deserialized
+        match take_reference() {
+            Some(any) => match P::from_any(any) {
+                Some(pointer) => Ok(Shared(pointer)),
+                None => Err(E::custom("shared reference points to a value of a different type")),
+            },
+            None => Err(E::invalid_type(de::Unexpected::Unit, &self)),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'de, P> Deserialize<'de> for Shared<P>
+where
+    P: SharedPointer,
+    P::Target: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>
+    {
+        deserializer.deserialize_newtype_struct(SHARED_TOKEN, SharedVisitor {
+            phantom: PhantomData,
+        })
+    }
+}
@@ -0,0 +1,125 @@
+use core::fmt;
+use core::marker::PhantomData;
+
+use serde::ser::{Serialize, Serializer, SerializeTupleStruct};
+use serde::de::{self, Deserialize, Deserializer, Visitor, SeqAccess};
+
+
+// Tuple struct name used to tell DBOR that the first field is a tag number. Other serializers just
+//   see a tuple struct of the tag and the value.
+pub(crate) const TAGGED_TOKEN: &str = "$serde_dbor::private::Tagged";
+
+/// Well-known tag numbers, which are shared with CBOR wherever CBOR already defines one
+///
+/// Tags `0-23` fit inside the instruction byte itself, so they only cost a single byte.
+pub mod tags {
+    /// A date/time string in RFC 3339 format
+    pub const DATE_TIME_STRING: u64 = 0;
+
+    /// A date/time as a number of seconds since the Unix epoch
+    pub const EPOCH_TIME: u64 = 1;
+
+    /// A big unsigned integer stored as big endian bytes
+    pub const POSITIVE_BIGNUM: u64 = 2;
+
+    /// A big negative integer (`-1 - n`) stored as big endian bytes
+    pub const NEGATIVE_BIGNUM: u64 = 3;
+
+    /// A decimal fraction stored as a seq of an exponent and a mantissa (`m * 10^e`)
+    pub const DECIMAL_FRACTION: u64 = 4;
+
+    /// A binary fraction stored as a seq of an exponent and a mantissa (`m * 2^e`)
+    pub const BIGFLOAT: u64 = 5;
+
+    /// A date/time as a number of nanoseconds since the Unix epoch, or as a seq of seconds and
+    /// nanoseconds if that doesn't fit in an `i64` (DBOR only)
+    pub const EPOCH_TIME_NANOS: u64 = 6;
+
+    /// A duration as a number of seconds (DBOR only)
+    pub const DURATION: u64 = 7;
+
+    /// A duration as a number of nanoseconds, or as a seq of seconds and nanoseconds if that
+    /// doesn't fit in a `u64` (DBOR only)
+    pub const DURATION_NANOS: u64 = 8;
+
+    /// A URI string as defined in RFC 3986
+    pub const URI: u64 = 32;
+
+    /// A regular expression string
+    pub const REGEX: u64 = 35;
+
+    /// A MIME message string
+    pub const MIME_MESSAGE: u64 = 36;
+
+    /// A UUID stored as 16 bytes
+    pub const UUID: u64 = 37;
+}
+
+
+/// A value marked with a semantic tag, like a timestamp, UUID, or URI
+///
+/// In DBOR the tag is written as an extension instruction byte in front of the value. Other
+/// formats see a `Tagged` as a tuple struct of the tag number and the value. See the `tags` module
+/// for well-known tag numbers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Tagged<T> {
+    /// The tag number
+    pub tag: u64,
+
+    /// The tagged value
+    pub value: T,
+}
+
+impl<T> Tagged<T> {
+    /// Tags a value
+    #[inline]
+    pub fn new(tag: u64, value: T) -> Self {
+        Self {
+            tag,
+            value,
+        }
+    }
+}
+
+impl<T: Serialize> Serialize for Tagged<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut tuple = serializer.serialize_tuple_struct(TAGGED_TOKEN, 2)?;
+
+        tuple.serialize_field(&self.tag)?;
+        tuple.serialize_field(&self.value)?;
+        tuple.end()
+    }
+}
+
+struct TaggedVisitor<T> {
+    phantom: PhantomData<T>,
+}
+
+impl<'de, T: Deserialize<'de>> Visitor<'de> for TaggedVisitor<T> {
+    type Value = Tagged<T>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a tagged value")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>
+    {
+        let tag = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let value = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(1, &self))?;
+
+        Ok(Tagged::new(tag, value))
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Tagged<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>
+    {
+        deserializer.deserialize_tuple_struct(TAGGED_TOKEN, 2, TaggedVisitor {
+            phantom: PhantomData,
+        })
+    }
+}
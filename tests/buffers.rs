@@ -0,0 +1,80 @@
+extern crate serde;
+extern crate serde_dbor;
+
+use serde::ser::{Error as SerError, Serialize, SerializeTuple, Serializer};
+
+use serde_dbor::{to_slice, to_vec, to_vec_into, from_slice, Error};
+
+
+#[test]
+fn to_slice_writes_into_the_start() {
+    let value = (7u8, "text", vec![1i32, -2, 300]);
+    let expected = to_vec(&value).unwrap();
+    let mut buffer = [0xaa; 64];
+    let len = to_slice(&value, &mut buffer).unwrap();
+
+    assert_eq!(len, expected.len());
+    assert_eq!(&buffer[..len], &expected[..]);
+    assert!(buffer[len..].iter().all(|&byte| byte == 0xaa));
+
+    // A buffer that fits exactly
+    let mut buffer = vec![0; expected.len()];
+
+    assert_eq!(to_slice(&value, &mut buffer).unwrap(), expected.len());
+    assert_eq!(buffer, expected);
+}
+
+#[test]
+fn to_slice_fails_when_out_of_room() {
+    let value = vec![0u8; 100];
+    let len = to_vec(&value).unwrap().len();
+
+    for size in &[0, 1, len - 1] {
+        let mut buffer = vec![0; *size];
+
+        match to_slice(&value, &mut buffer) {
+            Err(Error::BufferTooSmall) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+}
+
+#[test]
+fn to_vec_into_appends() {
+    let mut buffer = vec![0xff];
+
+    to_vec_into(&5u8, &mut buffer).unwrap();
+    to_vec_into(&"ab", &mut buffer).unwrap();
+
+    assert_eq!(buffer, [0xff, 0x05, 0xa2, b'a', b'b']);
+    assert_eq!(from_slice::<_, String>(&buffer[2..]).unwrap(), "ab");
+
+    // Reusing a cleared buffer doesn't need to allocate again
+    let capacity = buffer.capacity();
+
+    buffer.clear();
+    to_vec_into(&"cd", &mut buffer).unwrap();
+
+    assert_eq!(buffer.capacity(), capacity);
+}
+
+// Writes part of a tuple and then fails
+struct Failing;
+
+impl Serialize for Failing {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut tuple = serializer.serialize_tuple(2)?;
+
+        tuple.serialize_element(&1u8)?;
+
+        Err(S::Error::custom("failed on purpose"))
+    }
+}
+
+#[test]
+fn to_vec_into_leaves_the_vec_alone_on_errors() {
+    let mut buffer = vec![1, 2, 3];
+
+    assert!(to_vec_into(&(5u8, Failing), &mut buffer).is_err());
+    assert_eq!(buffer, [1, 2, 3]);
+}
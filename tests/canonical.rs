@@ -0,0 +1,110 @@
+extern crate serde;
+extern crate serde_dbor;
+
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
+
+use serde::ser::{Serialize, Serializer};
+
+use serde_dbor::{from_slice, from_slice_with_options, to_vec_with_options, DeserializerOptions, Error,
+    SerializerOptions, Shared};
+
+
+// Writes its entries as a map, in whatever order they're given in
+struct Entries(Vec<(&'static str, u8)>);
+
+impl Serialize for Entries {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.0.iter().map(|&(key, value)| (key, value)))
+    }
+}
+
+#[test]
+fn map_order_does_not_matter() {
+    let forwards = Entries(vec![("b", 1), ("a", 2), ("ccc", 3)]);
+    let backwards = Entries(vec![("ccc", 3), ("a", 2), ("b", 1)]);
+    let bytes = to_vec_with_options(&forwards, SerializerOptions::canonical()).unwrap();
+
+    assert_eq!(bytes, to_vec_with_options(&backwards, SerializerOptions::canonical()).unwrap());
+    assert_eq!(bytes, [
+        0xc3,
+        0xa1, b'a', 0x02,
+        0xa1, b'b', 0x01,
+        0xa3, b'c', b'c', b'c', 0x03,
+    ]);
+
+    // Hash maps come out the same no matter how they were filled
+    let mut first = HashMap::new();
+    let mut second = HashMap::new();
+
+    for i in 0..100u32 {
+        first.insert(i, i * 3);
+        second.insert(99 - i, (99 - i) * 3);
+    }
+
+    let bytes = to_vec_with_options(&first, SerializerOptions::canonical()).unwrap();
+
+    assert_eq!(bytes, to_vec_with_options(&second, SerializerOptions::canonical()).unwrap());
+
+    let out: BTreeMap<u32, u32> = from_slice_with_options(&bytes, DeserializerOptions::canonical())
+        .unwrap();
+
+    assert_eq!(out.len(), 100);
+    assert_eq!(out[&7], 21);
+}
+
+#[test]
+fn nested_maps_are_sorted_too() {
+    let mut inner = BTreeMap::new();
+
+    inner.insert("z", vec![Entries(vec![("y", 1), ("x", 2)])]);
+
+    let bytes = to_vec_with_options(&inner, SerializerOptions::canonical()).unwrap();
+
+    assert_eq!(bytes, [0xc1, 0xa1, b'z', 0x81, 0xc2, 0xa1, b'x', 0x02, 0xa1, b'y', 0x01]);
+    assert!(from_slice_with_options::<_, serde::de::IgnoredAny>(&bytes,
+        DeserializerOptions::canonical()).is_ok());
+}
+
+#[test]
+fn duplicate_keys() {
+    let entries = Entries(vec![("a", 1), ("b", 2), ("a", 3)]);
+
+    match to_vec_with_options(&entries, SerializerOptions::canonical()) {
+        Err(Error::DuplicateMapKey(ref key)) if *key == [0xa1, b'a'] => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn floats_and_nans_have_one_form() {
+    let options = SerializerOptions::canonical();
+
+    assert_eq!(to_vec_with_options(&1.5f64, options).unwrap(), [0x46, 0x00, 0x3e]);
+    assert_eq!(to_vec_with_options(&std::f64::consts::PI, options).unwrap().len(), 9);
+    assert_eq!(to_vec_with_options(&f64::NAN, options).unwrap(), [0x46, 0x00, 0x7e]);
+    assert_eq!(to_vec_with_options(&-f32::NAN, options).unwrap(), [0x46, 0x00, 0x7e]);
+    assert_eq!(to_vec_with_options(&f64::from_bits(0x7ff0_0000_0000_0001), options).unwrap(),
+        [0x46, 0x00, 0x7e]);
+}
+
+#[test]
+fn position_dependent_options_are_ignored() {
+    let options = SerializerOptions {
+        intern_strings: true,
+        align_packed: true,
+        ..SerializerOptions::canonical()
+    };
+    let bytes = to_vec_with_options(&("repeated", "repeated"), options).unwrap();
+
+    assert_eq!(bytes, to_vec_with_options(&("repeated", "repeated"), SerializerOptions::canonical())
+        .unwrap());
+    assert!(!bytes.contains(&0xbd));
+
+    // Shared values are written out in full every time
+    let shared = Shared(Rc::new(5u8));
+    let bytes = to_vec_with_options(&(shared.clone(), shared), options).unwrap();
+
+    assert_eq!(bytes, [0x82, 0x05, 0x05]);
+    assert_eq!(from_slice::<_, (u8, u8)>(&bytes).unwrap(), (5, 5));
+}
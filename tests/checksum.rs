@@ -0,0 +1,104 @@
+extern crate serde_dbor;
+
+use serde_dbor::{from_reader_with_options, from_slice, from_slice_with_options, to_slice_with_options,
+    to_vec, to_vec_with_options, to_writer_with_options, DeserializerOptions, Error,
+    SerializerOptions};
+
+
+fn checksummed() -> SerializerOptions {
+    SerializerOptions {
+        checksum: true,
+        ..SerializerOptions::default()
+    }
+}
+
+fn checked() -> DeserializerOptions {
+    DeserializerOptions {
+        checksum: true,
+        ..DeserializerOptions::default()
+    }
+}
+
+// A slow bit by bit CRC-32C to check the trailer against
+fn crc32c(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+
+    for &byte in bytes {
+        crc ^= byte as u32;
+
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0x82f63b78 } else { crc >> 1 };
+        }
+    }
+
+    !crc
+}
+
+#[test]
+fn trailers_hold_a_crc32c() {
+    assert_eq!(crc32c(b"123456789"), 0xe3069283);
+
+    let value = ("some text", vec![1u64, 1 << 40]);
+    let plain = to_vec(&value).unwrap();
+    let bytes = to_vec_with_options(&value, checksummed()).unwrap();
+
+    assert_eq!(&bytes[..plain.len()], &plain[..]);
+    assert_eq!(&bytes[plain.len()..], &crc32c(&plain).to_le_bytes());
+
+    // Every way of writing agrees
+    let mut slice = [0; 64];
+    let len = to_slice_with_options(&value, &mut slice, checksummed()).unwrap();
+
+    assert_eq!(&slice[..len], &bytes[..]);
+    assert_eq!(to_writer_with_options(&value, Vec::new(), checksummed()).unwrap(), bytes);
+
+    let out: (String, Vec<u64>) = from_slice_with_options(&bytes, checked()).unwrap();
+
+    assert_eq!(out, ("some text".to_string(), vec![1, 1 << 40]));
+    assert_eq!(from_reader_with_options::<_, (String, Vec<u64>)>(&bytes[..], checked()).unwrap(),
+        out);
+}
+
+#[test]
+fn flipped_bits_are_caught() {
+    let bytes = to_vec_with_options(&(1000u32, "text"), checksummed()).unwrap();
+
+    for i in 0..bytes.len() * 8 {
+        let mut corrupted = bytes.clone();
+
+        corrupted[i / 8] ^= 1 << (i % 8);
+
+        assert!(from_slice_with_options::<_, (u32, String)>(&corrupted, checked()).is_err(),
+            "flipping bit {} went unnoticed", i);
+    }
+
+    // A flip in the value that still decodes fine
+    let mut corrupted = bytes.clone();
+
+    corrupted[2] ^= 1;
+
+    match from_slice_with_options::<_, (u32, String)>(&corrupted, checked()) {
+        Err(Error::ChecksumMismatch) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    match from_reader_with_options::<_, (u32, String)>(&corrupted[..], checked()) {
+        Err(Error::ChecksumMismatch) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn trailers_have_to_be_expected() {
+    let bytes = to_vec_with_options(&5u8, checksummed()).unwrap();
+
+    match from_slice::<_, u8>(&bytes) {
+        Err(Error::TrailingBytes) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    match from_slice_with_options::<_, u8>(&bytes[..3], checked()) {
+        Err(Error::Eof) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+}
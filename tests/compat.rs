@@ -0,0 +1,100 @@
+extern crate serde_dbor;
+#[macro_use]
+extern crate serde_derive;
+
+use serde_dbor::{from_reader, from_slice, to_vec};
+
+
+mod old {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    pub struct Inner {
+        pub a: u8,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    pub struct Outer {
+        pub inner: Inner,
+        pub after: String,
+    }
+}
+
+mod new {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    pub struct Inner {
+        pub a: u8,
+        #[serde(default)]
+        pub b: Vec<u32>,
+        #[serde(default)]
+        pub c: Option<String>,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    pub struct Outer {
+        pub inner: Inner,
+        pub after: String,
+    }
+}
+
+#[test]
+fn old_readers_skip_new_fields() {
+    let value = new::Outer {
+        inner: new::Inner {
+            a: 1,
+            b: vec![2, 3],
+            c: Some("c".into()),
+        },
+        after: "after".into(),
+    };
+    let bytes = to_vec(&value).unwrap();
+    let expected = old::Outer {
+        inner: old::Inner {
+            a: 1,
+        },
+        after: "after".into(),
+    };
+
+    assert_eq!(from_slice::<_, old::Outer>(&bytes).unwrap(), expected);
+    assert_eq!(from_reader::<_, old::Outer>(&bytes[..]).unwrap(), expected);
+
+    // Tuples always have to be the same length
+    assert!(from_slice::<_, (u8,)>(&to_vec(&(1u8, "extra")).unwrap()).is_err());
+}
+
+#[test]
+fn new_readers_fill_in_defaults() {
+    let value = old::Outer {
+        inner: old::Inner {
+            a: 1,
+        },
+        after: "after".into(),
+    };
+    let bytes = to_vec(&value).unwrap();
+    let expected = new::Outer {
+        inner: new::Inner {
+            a: 1,
+            b: Vec::new(),
+            c: None,
+        },
+        after: "after".into(),
+    };
+
+    assert_eq!(from_slice::<_, new::Outer>(&bytes).unwrap(), expected);
+    assert_eq!(from_reader::<_, new::Outer>(&bytes[..]).unwrap(), expected);
+}
+
+#[test]
+fn missing_fields_without_defaults() {
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Required {
+        a: u8,
+        b: u8,
+    }
+
+    let error = from_slice::<_, Required>(&to_vec(&(1u8,)).unwrap()).unwrap_err();
+
+    assert!(error.to_string().starts_with("invalid length 1"), "unexpected error {}", error);
+
+    // Elements that are there still have to be valid
+    assert!(from_slice::<_, old::Outer>(&to_vec(&((1u8, 2u8), 5u8)).unwrap()).is_err());
+}
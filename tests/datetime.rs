@@ -0,0 +1,147 @@
+extern crate serde_dbor;
+#[macro_use]
+extern crate serde_derive;
+#[cfg(feature = "chrono")]
+extern crate chrono;
+#[cfg(feature = "time")]
+extern crate time;
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde_dbor::{from_slice, to_vec, Tagged, Timestamp, tags};
+
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct Event {
+    #[serde(with = "serde_dbor::system_time")]
+    at: SystemTime,
+
+    #[serde(with = "serde_dbor::duration")]
+    took: Duration,
+}
+
+#[test]
+fn timestamps_shrink_to_fit() {
+    let whole = Timestamp::new(1_600_000_000, 0).unwrap();
+    let precise = Timestamp::new(1_600_000_000, 5).unwrap();
+
+    assert_eq!(to_vec(&whole).unwrap(), [0xe1, 0x3a, 0x00, 0x10, 0x5e, 0x5f]);
+    assert_eq!(to_vec(&Timestamp::UNIX_EPOCH).unwrap(), [0xe1, 0x20]);
+    assert_eq!(to_vec(&precise).unwrap().len(), 10);
+    assert_eq!(to_vec(&precise).unwrap()[0], 0xe6);
+}
+
+#[test]
+fn round_trip_timestamps() {
+    let timestamps = [
+        Timestamp::UNIX_EPOCH,
+        Timestamp::new(-1, 999_999_999).unwrap(),
+        Timestamp::new(1_600_000_000, 123_456_789).unwrap(),
+        Timestamp::new(i64::MAX, 999_999_999).unwrap(),
+        Timestamp::new(i64::MIN, 1).unwrap(),
+        Timestamp::from_nanos(-1).unwrap(),
+    ];
+
+    for timestamp in &timestamps {
+        let out: Timestamp = from_slice(&to_vec(timestamp).unwrap()).unwrap();
+
+        assert_eq!(out, *timestamp);
+    }
+
+    assert_eq!(Timestamp::from_nanos(-1).unwrap().secs(), -1);
+    assert_eq!(Timestamp::from_nanos(-1).unwrap().subsec_nanos(), 999_999_999);
+}
+
+#[test]
+fn round_trip_system_time_and_duration() {
+    let events = [
+        Event {
+            at: UNIX_EPOCH + Duration::new(1_600_000_000, 42),
+            took: Duration::new(3, 0),
+        },
+        Event {
+            at: UNIX_EPOCH - Duration::new(86_400, 1),
+            took: Duration::new(u64::MAX, 999_999_999),
+        },
+    ];
+
+    for event in &events {
+        let out: Event = from_slice(&to_vec(event).unwrap()).unwrap();
+
+        assert_eq!(out, *event);
+    }
+
+    let bytes = to_vec(&events[0]).unwrap();
+
+    // A whole number of seconds is written with the seconds tag
+    assert_eq!(&bytes[bytes.len() - 2..], [0xe7, 0x03]);
+}
+
+#[test]
+fn cbor_float_epoch_times_are_read() {
+    let bytes = to_vec(&Tagged::new(tags::EPOCH_TIME, 1.5f64)).unwrap();
+    let out: Timestamp = from_slice(&bytes).unwrap();
+
+    assert_eq!(out, Timestamp::new(1, 500_000_000).unwrap());
+}
+
+#[test]
+fn wrong_tags_and_values() {
+    // A URI isn't a timestamp
+    let bytes = to_vec(&Tagged::new(tags::URI, 5u8)).unwrap();
+
+    assert!(from_slice::<_, Timestamp>(&bytes).is_err());
+
+    // Nanoseconds have to be less than a second
+    let bytes = to_vec(&Tagged::new(tags::EPOCH_TIME_NANOS, (0u8, 1_000_000_000u32))).unwrap();
+
+    assert!(from_slice::<_, Timestamp>(&bytes).is_err());
+
+    // Infinity isn't a time
+    let bytes = to_vec(&Tagged::new(tags::EPOCH_TIME, f64::INFINITY)).unwrap();
+
+    assert!(from_slice::<_, Timestamp>(&bytes).is_err());
+
+    // Durations can't be negative
+    #[derive(Serialize)]
+    struct RawEvent(Timestamp, Tagged<i8>);
+
+    let bytes = to_vec(&RawEvent(Timestamp::UNIX_EPOCH, Tagged::new(tags::DURATION, -1))).unwrap();
+
+    assert!(from_slice::<_, Event>(&bytes).is_err());
+}
+
+#[cfg(feature = "chrono")]
+#[test]
+fn round_trip_chrono() {
+    use chrono::{DateTime, TimeZone, Utc};
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Wrapper(#[serde(with = "serde_dbor::chrono")] DateTime<Utc>);
+
+    let time = Wrapper(Utc.timestamp_opt(1_600_000_000, 7).unwrap());
+    let bytes = to_vec(&time).unwrap();
+
+    assert_eq!(bytes, to_vec(&Timestamp::new(1_600_000_000, 7).unwrap()).unwrap());
+    assert_eq!(from_slice::<_, Wrapper>(&bytes).unwrap(), time);
+}
+
+#[cfg(feature = "time")]
+#[test]
+fn round_trip_time() {
+    use time::OffsetDateTime;
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Wrapper(#[serde(with = "serde_dbor::time")] OffsetDateTime);
+
+    let time = Wrapper(OffsetDateTime::from_unix_timestamp_nanos(1_600_000_000_000_000_007).unwrap());
+    let bytes = to_vec(&time).unwrap();
+
+    assert_eq!(bytes, to_vec(&Timestamp::new(1_600_000_000, 7).unwrap()).unwrap());
+    assert_eq!(from_slice::<_, Wrapper>(&bytes).unwrap(), time);
+
+    // Timestamps outside of the `time` crate's range
+    let bytes = to_vec(&Timestamp::new(i64::MAX, 0).unwrap()).unwrap();
+
+    assert!(from_slice::<_, Wrapper>(&bytes).is_err());
+}
@@ -0,0 +1,221 @@
+#![cfg(feature = "derive")]
+
+extern crate serde;
+extern crate serde_dbor;
+#[macro_use]
+extern crate serde_derive;
+
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+
+use serde_dbor::{from_slice, to_vec, to_vec_with_options, DborDeserialize, DborSerialize, Error,
+    SerializerOptions};
+
+
+fn with_ids() -> SerializerOptions {
+    SerializerOptions {
+        field_ids: true,
+        ..SerializerOptions::default()
+    }
+}
+
+#[derive(DborSerialize, DborDeserialize, PartialEq, Debug)]
+struct Mesh {
+    #[dbor(id = 0, intern)]
+    name: String,
+    #[dbor(id = 5, packed)]
+    vertices: Vec<f32>,
+    #[dbor(id = 2, default)]
+    scale: f64,
+    #[dbor(id = 3, skip)]
+    cached: Option<u32>,
+}
+
+// The same struct, as serde's own derive sees it
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct PlainMesh {
+    name: String,
+    vertices: Vec<f32>,
+    scale: f64,
+}
+
+// A struct without explicit IDs, as both derives see it
+#[derive(DborSerialize, DborDeserialize, PartialEq, Debug)]
+struct Point {
+    x: u8,
+    #[dbor(skip)]
+    label: Option<String>,
+    y: u8,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct PlainPoint {
+    x: u8,
+    #[serde(skip)]
+    label: Option<String>,
+    y: u8,
+}
+
+#[derive(DborSerialize, DborDeserialize, PartialEq, Debug)]
+#[dbor(named)]
+enum Named {
+    First,
+    #[dbor(rename = "two")]
+    Second(u8),
+}
+
+#[derive(DborSerialize, DborDeserialize, PartialEq, Debug)]
+enum Partly {
+    Indexed(u8),
+    #[dbor(named)]
+    ByName { #[dbor(rename = "v")] value: u8 },
+}
+
+#[derive(DborSerialize, DborDeserialize, PartialEq, Debug)]
+enum Old {
+    A,
+    B(u8),
+    #[dbor(other)]
+    Unknown,
+}
+
+#[derive(Serialize, PartialEq, Debug)]
+enum New {
+    A,
+    B(u8),
+    Unknown,
+    C(String),
+}
+
+fn mesh() -> Mesh {
+    Mesh {
+        name: "mesh".into(),
+        vertices: vec![1.0, 2.0, 3.0],
+        scale: 2.0,
+        cached: Some(9),
+    }
+}
+
+#[test]
+fn round_trip_with_attributes() {
+    for &options in &[SerializerOptions::default(), with_ids()] {
+        let bytes = to_vec_with_options(&mesh(), options).unwrap();
+        let out: Mesh = from_slice(&bytes).unwrap();
+
+        // Skipped fields are never written
+        assert_eq!(out, Mesh { cached: None, ..mesh() });
+    }
+}
+
+#[test]
+fn explicit_ids_are_used_as_keys() {
+    let bytes = to_vec_with_options(&mesh(), with_ids()).unwrap();
+
+    assert_eq!(bytes[0], 0xc3);
+    assert_eq!(&bytes[1..3], [0x00, 0xbd]);
+
+    // Missing fields with `default` are filled in
+    let bytes = [0xc2, 0x05, 0xa0, 0x00, 0xa1, b'm'];
+
+    assert_eq!(from_slice::<_, Mesh>(&bytes[..]).unwrap(), Mesh {
+        name: "m".into(),
+        vertices: Vec::new(),
+        scale: 0.0,
+        cached: None,
+    });
+
+    // Empty strings at the very end have nothing left to read
+    assert_eq!(from_slice::<_, Mesh>(&[0xc2, 0x05, 0xa0, 0x00, 0xa0][..]).unwrap().name, "");
+
+    // But missing fields without it aren't
+    assert!(from_slice::<_, Mesh>(&[0xc1, 0x00, 0xa1, b'm'][..]).is_err());
+}
+
+#[test]
+fn ids_follow_declaration_order_in_both_derives() {
+    let point = Point { x: 1, label: None, y: 2 };
+    let plain = PlainPoint { x: 1, label: None, y: 2 };
+    let bytes = to_vec_with_options(&point, with_ids()).unwrap();
+
+    assert_eq!(bytes, [0xc2, 0x00, 0x01, 0x01, 0x02]);
+    assert_eq!(to_vec_with_options(&plain, with_ids()).unwrap(), bytes);
+    assert_eq!(from_slice::<_, PlainPoint>(&bytes).unwrap(), plain);
+    assert_eq!(from_slice::<_, Point>(&to_vec_with_options(&plain, with_ids()).unwrap()).unwrap(),
+        point);
+}
+
+#[test]
+fn attributes_change_the_encoding() {
+    let plain = PlainMesh {
+        name: "mesh".into(),
+        vertices: vec![1.0, 2.0, 3.0],
+        scale: 2.0,
+    };
+    let bytes = to_vec(&mesh()).unwrap();
+
+    // The name is interned and the vertices are packed
+    assert_eq!(&bytes[..7], [0x83, 0xbd, 0xa4, b'm', b'e', b's', b'h']);
+    assert_eq!(&bytes[7..12], [0xac, 0x00, 0x00, 0x80, 0x3f]);
+    assert_eq!(&bytes[20..], &to_vec(&2.0f64).unwrap()[..]);
+
+    // Interning alone doesn't stop other types from reading it
+    let plain_bytes = to_vec(&plain).unwrap();
+
+    assert_eq!(&plain_bytes[..6], [0x83, 0xa4, b'm', b'e', b's', b'h']);
+    assert!(from_slice::<_, PlainMesh>(&bytes).is_err());
+}
+
+#[test]
+fn named_variants() {
+    let bytes = to_vec(&Named::Second(4)).unwrap();
+
+    assert_eq!(bytes, [0x7b, 0x03, b't', b'w', b'o', 0x04]);
+    assert_eq!(from_slice::<_, Named>(&bytes).unwrap(), Named::Second(4));
+    assert_eq!(from_slice::<_, Named>(&to_vec(&Named::First).unwrap()).unwrap(), Named::First);
+
+    let values = vec![Partly::Indexed(1), Partly::ByName { value: 2 }];
+    let bytes = to_vec(&values).unwrap();
+
+    assert_eq!(&bytes[1..3], [0x60, 0x01]);
+    assert_eq!(&bytes[3..7], [0x7b, 0x06, b'B', b'y']);
+    assert_eq!(from_slice::<_, Vec<Partly>>(&bytes).unwrap(), values);
+
+    // Names that aren't known about
+    match from_slice::<_, Named>(&[0x7b, 0x05, b't', b'h', b'r', b'e', b'e', 0x42][..]) {
+        Err(Error::Message(ref message)) if message.contains("three") => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn other_catches_unknown_variants() {
+    let bytes = to_vec(&vec![New::A, New::C("new".into()), New::B(5)]).unwrap();
+
+    assert_eq!(from_slice::<_, Vec<Old>>(&bytes).unwrap(), [Old::A, Old::Unknown, Old::B(5)]);
+
+    // The catch-all is a variant of its own too, which has no payload
+    assert_eq!(from_slice::<_, Old>(&to_vec(&New::Unknown).unwrap()).unwrap(), Old::Unknown);
+    assert!(from_slice::<_, Old>(&[0x62, 0x05][..]).is_err());
+}
+
+#[test]
+fn hand_written_field_ids_have_to_cover_every_field() {
+    struct Partial;
+
+    impl Serialize for Partial {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut state = serializer.serialize_struct("$serde_dbor::private::FieldIds:a=0", 2)?;
+
+            state.serialize_field("a", &1u8)?;
+            state.serialize_field("b", &2u8)?;
+            state.end()
+        }
+    }
+
+    match to_vec_with_options(&Partial, with_ids()) {
+        Err(Error::MissingFieldId("b")) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    // Without field IDs it's a plain struct
+    assert_eq!(to_vec(&Partial).unwrap(), [0x82, 0x01, 0x02]);
+}
@@ -0,0 +1,41 @@
+extern crate serde_dbor;
+
+use std::error::Error as StdError;
+use std::io::{self, Write};
+
+use serde_dbor::{to_writer, Error};
+
+
+// A writer that refuses every write
+struct Broken;
+
+impl Write for Broken {
+    fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+        Err(io::Error::new(io::ErrorKind::PermissionDenied, "read only"))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn io_errors_are_passed_through() {
+    let error = to_writer(&"text", Broken).map(|_| ()).unwrap_err();
+
+    match error {
+        Error::Io(ref io_error) => assert_eq!(io_error.kind(), io::ErrorKind::PermissionDenied),
+        ref other => panic!("unexpected error {:?}", other),
+    }
+
+    assert_eq!(error.to_string(), "read only");
+    assert!(error.source().is_some());
+}
+
+#[test]
+fn other_errors_have_no_source() {
+    let error = serde_dbor::from_slice::<_, u8>(&[][..]).unwrap_err();
+
+    assert!(error.source().is_none());
+    assert_eq!(error.to_string(), "Unexpected end of input");
+}
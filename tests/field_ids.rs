@@ -0,0 +1,130 @@
+extern crate serde;
+extern crate serde_dbor;
+#[macro_use]
+extern crate serde_derive;
+
+use serde::de::IgnoredAny;
+
+use serde_dbor::{from_slice, from_slice_with_options, to_vec, to_vec_with_options,
+    DeserializerOptions, Error, SerializerOptions};
+
+
+fn with_ids() -> SerializerOptions {
+    SerializerOptions {
+        field_ids: true,
+        ..SerializerOptions::default()
+    }
+}
+
+mod v1 {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    pub struct User {
+        pub name: String,
+        pub age: u8,
+    }
+}
+
+mod v2 {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    pub struct User {
+        pub name: String,
+        pub age: u8,
+        #[serde(default)]
+        pub email: Option<String>,
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+enum Event {
+    Joined { user: String, at: u32 },
+}
+
+#[test]
+fn fields_are_numbered_in_order() {
+    let user = v1::User { name: "ann".into(), age: 30 };
+    let bytes = to_vec_with_options(&user, with_ids()).unwrap();
+
+    assert_eq!(bytes, [0xc2, 0x00, 0xa3, b'a', b'n', b'n', 0x01, 0x18, 30]);
+    assert_eq!(from_slice::<_, v1::User>(&bytes).unwrap(), user);
+
+    // Positional structs can still be read
+    assert_eq!(from_slice::<_, v1::User>(&to_vec(&user).unwrap()).unwrap(), user);
+}
+
+#[test]
+fn fields_can_be_added() {
+    let old = v1::User { name: "ann".into(), age: 30 };
+    let new: v2::User = from_slice(&to_vec_with_options(&old, with_ids()).unwrap()).unwrap();
+
+    assert_eq!(new, v2::User { name: "ann".into(), age: 30, email: None });
+
+    let new = v2::User { name: "bob".into(), age: 31, email: Some("a@b".into()) };
+    let old: v1::User = from_slice(&to_vec_with_options(&new, with_ids()).unwrap()).unwrap();
+
+    assert_eq!(old, v1::User { name: "bob".into(), age: 31 });
+
+    // Written out of order
+    let bytes = [0xc2, 0x01, 0x05, 0x00, 0xa1, b'c'];
+
+    assert_eq!(from_slice::<_, v1::User>(&bytes[..]).unwrap(), v1::User {
+        name: "c".into(),
+        age: 5,
+    });
+}
+
+#[test]
+fn skipped_fields() {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Skipping {
+        #[serde(skip)]
+        cache: u32,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        note: Option<String>,
+        z: u8,
+    }
+
+    // Fields skipped only some of the time still use up a number, ones that are always skipped
+    //   don't
+    let value = Skipping { cache: 7, note: None, z: 3 };
+    let bytes = to_vec_with_options(&value, with_ids()).unwrap();
+
+    assert_eq!(bytes, [0xc1, 0x01, 0x03]);
+    assert_eq!(from_slice::<_, Skipping>(&bytes).unwrap(), Skipping { cache: 0, note: None, z: 3 });
+
+    let value = Skipping { cache: 0, note: Some("note".into()), z: 4 };
+    let bytes = to_vec_with_options(&value, with_ids()).unwrap();
+
+    assert_eq!(from_slice::<_, Skipping>(&bytes).unwrap(), value);
+}
+
+#[test]
+fn struct_variants_are_keyed_too() {
+    let event = Event::Joined { user: "ann".into(), at: 5 };
+    let bytes = to_vec_with_options(&event, with_ids()).unwrap();
+
+    assert_eq!(bytes[1..3], [0xc2, 0x00]);
+    assert_eq!(from_slice::<_, Event>(&bytes).unwrap(), event);
+    assert_eq!(from_slice::<_, Event>(&to_vec(&event).unwrap()).unwrap(), event);
+}
+
+#[test]
+fn unknown_and_duplicate_ids() {
+    // An ID that no field has is skipped
+    let mut bytes = to_vec_with_options(&v1::User { name: "ann".into(), age: 30 }, with_ids())
+        .unwrap();
+
+    bytes[0] = 0xc3;
+    bytes.extend_from_slice(&[0x07, 0x83, 0x01, 0x02, 0x03]);
+
+    assert_eq!(from_slice::<_, v1::User>(&bytes).unwrap().age, 30);
+
+    // The same ID twice
+    let bytes = [0xc3, 0x00, 0xa1, b'a', 0x01, 0x01, 0x01, 0x02];
+
+    assert!(from_slice::<_, v1::User>(&bytes[..]).is_err());
+
+    match from_slice_with_options::<_, IgnoredAny>(&bytes[..], DeserializerOptions::strict()) {
+        Err(Error::DuplicateMapKey(..)) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+}
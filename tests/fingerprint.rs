@@ -0,0 +1,118 @@
+extern crate serde_dbor;
+#[macro_use]
+extern crate serde_derive;
+
+use serde_dbor::{fingerprint, from_reader_with_header, from_slice_with_header, to_vec_with_header,
+    Error, SerializerOptions};
+
+
+mod v1 {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    pub struct Point {
+        pub x: i32,
+        pub y: i32,
+    }
+}
+
+mod v2 {
+    // Only the name changed
+    #[derive(Serialize, Deserialize)]
+    pub struct Position {
+        pub x: i32,
+        pub y: i32,
+    }
+
+    // The fields switched places
+    #[derive(Serialize, Deserialize)]
+    pub struct Point {
+        pub y: i32,
+        pub x: i32,
+    }
+}
+
+mod v3 {
+    // A field changed type
+    #[derive(Serialize, Deserialize)]
+    pub struct Point {
+        pub x: i64,
+        pub y: i32,
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+enum Tree {
+    Leaf(u8),
+    Node(Box<Tree>, Box<Tree>),
+}
+
+#[derive(Serialize, Deserialize)]
+enum Shape {
+    Circle { radius: f64 },
+    Square(f64),
+}
+
+#[derive(Serialize, Deserialize)]
+enum MoreShapes {
+    Circle { radius: f64 },
+    Square(f64),
+    Point,
+}
+
+#[test]
+fn fingerprints_follow_the_shape() {
+    let point = fingerprint::<v1::Point>().unwrap();
+
+    assert_eq!(point, fingerprint::<v1::Point>().unwrap());
+    assert_eq!(point, fingerprint::<v2::Position>().unwrap());
+    assert_ne!(point, fingerprint::<v2::Point>().unwrap());
+    assert_ne!(point, fingerprint::<v3::Point>().unwrap());
+    assert_ne!(point, fingerprint::<(i32, i32)>().unwrap());
+
+    assert_ne!(fingerprint::<Shape>().unwrap(), fingerprint::<MoreShapes>().unwrap());
+    assert_ne!(fingerprint::<Vec<u8>>().unwrap(), fingerprint::<Vec<u16>>().unwrap());
+    assert_ne!(fingerprint::<Option<u8>>().unwrap(), fingerprint::<u8>().unwrap());
+}
+
+#[test]
+fn recursive_types() {
+    let tree = fingerprint::<Tree>().unwrap();
+
+    assert_eq!(tree, fingerprint::<Tree>().unwrap());
+    assert_ne!(tree, fingerprint::<Shape>().unwrap());
+    assert!(fingerprint::<Vec<Tree>>().is_ok());
+
+    // Every variant contains itself, so tracing never ends
+    #[derive(Serialize, Deserialize)]
+    enum Loop {
+        Next(Box<Loop>),
+    }
+
+    match fingerprint::<Loop>() {
+        Err(Error::UntraceableType("Loop")) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn headers_check_fingerprints() {
+    let options = SerializerOptions {
+        fingerprint: Some(fingerprint::<v1::Point>().unwrap()),
+        ..SerializerOptions::default()
+    };
+    let point = v1::Point { x: 1, y: -2 };
+    let bytes = to_vec_with_header(&point, options).unwrap();
+
+    assert_eq!(&bytes[6..14], &options.fingerprint.unwrap().to_le_bytes());
+    assert_eq!(from_slice_with_header::<_, v1::Point>(&bytes).unwrap(), point);
+    assert_eq!(from_reader_with_header::<_, v1::Point>(&bytes[..]).unwrap(), point);
+
+    // The same data would decode fine as a reordered struct, but with the fields swapped
+    match from_slice_with_header::<_, v2::Point>(&bytes) {
+        Err(Error::FingerprintMismatch(found, expected)) => {
+            assert_eq!(found, options.fingerprint.unwrap());
+            assert_eq!(expected, fingerprint::<v2::Point>().unwrap());
+        }
+        Err(error) => panic!("unexpected error {:?}", error),
+        Ok(_) => panic!("read a point with a different layout"),
+    }
+}
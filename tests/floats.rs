@@ -0,0 +1,87 @@
+extern crate serde_dbor;
+
+use serde_dbor::{from_slice, to_vec, to_vec_with_options, Error, SerializerOptions};
+
+
+fn shrink() -> SerializerOptions {
+    SerializerOptions {
+        shrink_floats: true,
+        ..SerializerOptions::default()
+    }
+}
+
+#[test]
+fn floats_are_full_size_by_default() {
+    assert_eq!(to_vec(&1.0f64).unwrap(), [0x45, 0, 0, 0, 0, 0, 0, 0xf0, 0x3f]);
+    assert_eq!(to_vec(&1.0f32).unwrap(), [0x44, 0, 0, 0x80, 0x3f]);
+}
+
+#[test]
+fn shrinking_is_lossless() {
+    let values = [
+        0.0,
+        -0.0,
+        1.0,
+        -2.5,
+        65504.0,
+        // Smallest subnormal f16
+        5.960464477539063e-8,
+        0.1,
+        1.0e-40,
+        1.0e300,
+        f64::INFINITY,
+        f64::NEG_INFINITY,
+        f64::MAX,
+        f64::MIN_POSITIVE,
+    ];
+
+    for &value in &values {
+        let bytes = to_vec_with_options(&value, shrink()).unwrap();
+        let out: f64 = from_slice(&bytes).unwrap();
+
+        assert_eq!(out.to_bits(), value.to_bits(), "{} came back as {}", value, out);
+        assert!(bytes.len() <= 9);
+    }
+
+    let nan: f64 = from_slice(&to_vec_with_options(&f64::NAN, shrink()).unwrap()).unwrap();
+
+    assert!(nan.is_nan());
+}
+
+#[test]
+fn shrinking_picks_the_smallest_size() {
+    // Fits in an f16
+    assert_eq!(to_vec_with_options(&1.5f64, shrink()).unwrap(), [0x46, 0x00, 0x3e]);
+    assert_eq!(to_vec_with_options(&-2.0f32, shrink()).unwrap(), [0x46, 0x00, 0xc0]);
+
+    // Fits in an f32, but not an f16
+    assert_eq!(to_vec_with_options(&65536.5f64, shrink()).unwrap().len(), 5);
+
+    // Needs the whole f64
+    assert_eq!(to_vec_with_options(&0.1f64, shrink()).unwrap().len(), 9);
+}
+
+#[test]
+fn half_floats_can_be_read_as_either_size() {
+    let bytes = [0x46, 0x00, 0x3c];
+
+    assert_eq!(from_slice::<_, f32>(&bytes[..]).unwrap(), 1.0);
+    assert_eq!(from_slice::<_, f64>(&bytes[..]).unwrap(), 1.0);
+
+    // Subnormal and infinite halves
+    assert_eq!(from_slice::<_, f32>(&[0x46, 0x01, 0x00][..]).unwrap(), 2.0f32.powi(-24));
+    assert_eq!(from_slice::<_, f32>(&[0x46, 0x00, 0xfc][..]).unwrap(), f32::NEG_INFINITY);
+}
+
+#[test]
+fn truncated_half_float() {
+    match from_slice::<_, f32>(&[0x46, 0x00][..]) {
+        Err(Error::Eof) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn floats_are_not_integers() {
+    assert!(from_slice::<_, u32>(&[0x46, 0x00, 0x3c][..]).is_err());
+}
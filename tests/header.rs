@@ -0,0 +1,138 @@
+extern crate serde;
+extern crate serde_dbor;
+#[macro_use]
+extern crate serde_derive;
+
+use serde::Deserialize;
+
+use serde_dbor::{from_reader_with_header, from_reader_with_header_and_options,
+    from_slice_with_header, from_slice_with_header_and_options, to_vec, to_vec_with_header,
+    to_writer_with_header, Deserializer, DeserializerOptions, Error, SerializerOptions};
+
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct Config {
+    name: String,
+    retries: u8,
+    ratio: f64,
+}
+
+fn config() -> Config {
+    Config {
+        name: "config".into(),
+        retries: 3,
+        ratio: 0.5,
+    }
+}
+
+#[test]
+fn headers_come_first() {
+    let bytes = to_vec_with_header(&config(), SerializerOptions::default()).unwrap();
+
+    assert_eq!(&bytes[..6], b"DBOR\x01\x00");
+    assert_eq!(&bytes[6..], &to_vec(&config()).unwrap()[..]);
+    assert_eq!(from_slice_with_header::<_, Config>(&bytes).unwrap(), config());
+    assert_eq!(from_reader_with_header::<_, Config>(&bytes[..]).unwrap(), config());
+
+    let written = to_writer_with_header(&config(), Vec::new(), SerializerOptions::default())
+        .unwrap();
+
+    assert_eq!(written, bytes);
+}
+
+#[test]
+fn headers_with_options() {
+    let bytes = to_vec_with_header(&config(), SerializerOptions::default()).unwrap();
+    let options = DeserializerOptions {
+        max_len: Some(4),
+        ..DeserializerOptions::default()
+    };
+
+    // The name is too long for the limit
+    match from_slice_with_header_and_options::<_, Config>(&bytes, options) {
+        Err(Error::LengthLimitExceeded(..)) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    match from_reader_with_header_and_options::<_, Config>(&bytes[..], options) {
+        Err(Error::LengthLimitExceeded(..)) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    assert_eq!(from_slice_with_header_and_options::<_, Config>(&bytes,
+        DeserializerOptions::default()).unwrap(), config());
+}
+
+#[test]
+fn headers_record_the_options() {
+    let options = SerializerOptions {
+        intern_strings: true,
+        shrink_floats: true,
+        checksum: true,
+        field_ids: true,
+        ..SerializerOptions::default()
+    };
+    let bytes = to_vec_with_header(&config(), options).unwrap();
+    let mut deserializer = Deserializer::from_slice(&bytes);
+
+    assert_eq!(deserializer.read_header().unwrap(), options);
+    assert_eq!(Config::deserialize(&mut deserializer).unwrap(), config());
+    deserializer.end().unwrap();
+
+    // Canonical data turns off the options that don't apply to it
+    let bytes = to_vec_with_header(&config(), SerializerOptions {
+        intern_strings: true,
+        ..SerializerOptions::canonical()
+    }).unwrap();
+    let mut deserializer = Deserializer::from_slice(&bytes);
+
+    assert_eq!(deserializer.read_header().unwrap(), SerializerOptions::canonical());
+}
+
+#[test]
+fn the_deserializer_follows_the_header() {
+    // A canonical header followed by a 5 that's longer than it needs to be
+    let bytes = [b'D', b'B', b'O', b'R', 1, 0b00001010, 0x18, 0x05];
+
+    match from_slice_with_header::<_, u8>(&bytes[..]) {
+        Err(Error::NonMinimalInt(..)) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    // A header that promises a checksum that isn't there
+    let bytes = [b'D', b'B', b'O', b'R', 1, 0b00010000, 0x05];
+
+    match from_slice_with_header::<_, u8>(&bytes[..]) {
+        Err(Error::Eof) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn bad_headers() {
+    match from_slice_with_header::<_, Config>(&b"{\"name\": \"config\"}"[..]) {
+        Err(Error::InvalidMagic) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    // Data without a header
+    match from_slice_with_header::<_, Config>(&to_vec(&config()).unwrap()) {
+        Err(Error::InvalidMagic) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    match from_reader_with_header::<_, u8>(&b"DBOR\x02\x00\x05"[..]) {
+        Err(Error::UnsupportedVersion(2)) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    match from_slice_with_header::<_, u8>(&b"DBOR\x01\x80\x05"[..]) {
+        Err(Error::UnknownHeaderFlags(0x80)) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    match from_slice_with_header::<_, u8>(&b"DBO"[..]) {
+        Err(Error::Eof) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+}
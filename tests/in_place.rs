@@ -0,0 +1,64 @@
+extern crate serde_dbor;
+
+use serde_dbor::{from_slice_in_place, from_slice_in_place_with_options, to_vec, DeserializerOptions,
+    Error};
+
+
+#[test]
+fn buffers_are_reused() {
+    let mut place: (Vec<String>, String, Vec<u8>) = (
+        vec![String::with_capacity(64), String::with_capacity(64)],
+        String::with_capacity(64),
+        Vec::with_capacity(64),
+    );
+    let pointers = (place.0.as_ptr(), place.0[0].as_ptr(), place.1.as_ptr(), place.2.as_ptr());
+
+    for i in 0..3u8 {
+        let value = (vec![format!("first {}", i), format!("second {}", i)], format!("{}", i),
+            vec![i; 10]);
+
+        from_slice_in_place(&to_vec(&value).unwrap(), &mut place).unwrap();
+
+        assert_eq!(place, value);
+        assert_eq!((place.0.as_ptr(), place.0[0].as_ptr(), place.1.as_ptr(), place.2.as_ptr()),
+            pointers);
+    }
+}
+
+#[test]
+fn collections_can_change_length() {
+    let mut place = vec![1u32, 2, 3];
+
+    from_slice_in_place(&to_vec(&vec![4u32]).unwrap(), &mut place).unwrap();
+    assert_eq!(place, [4]);
+
+    from_slice_in_place(&to_vec(&vec![5u32, 6, 7, 8]).unwrap(), &mut place).unwrap();
+    assert_eq!(place, [5, 6, 7, 8]);
+
+    let mut place = Some(String::from("before"));
+
+    from_slice_in_place(&to_vec(&None::<String>).unwrap(), &mut place).unwrap();
+    assert_eq!(place, None);
+}
+
+#[test]
+fn errors_are_still_reported() {
+    let mut place = vec![0u8; 4];
+
+    match from_slice_in_place(&[0x82, 0x01][..], &mut place) {
+        Err(Error::Eof) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    match from_slice_in_place(&[0x81, 0x01, 0x02][..], &mut place) {
+        Err(Error::TrailingBytes) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    match from_slice_in_place_with_options(&[0x81, 0x18, 0x01][..], &mut place,
+        DeserializerOptions::strict())
+    {
+        Err(Error::NonMinimalInt(..)) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+}
@@ -0,0 +1,111 @@
+extern crate serde;
+extern crate serde_dbor;
+#[macro_use]
+extern crate serde_derive;
+
+use std::collections::BTreeMap;
+
+use serde::de::IgnoredAny;
+
+use serde_dbor::{from_reader, from_slice, to_vec, to_vec_with_options, Error, SerializerOptions};
+
+
+fn interned() -> SerializerOptions {
+    SerializerOptions {
+        intern_strings: true,
+        ..SerializerOptions::default()
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct Record {
+    kind: String,
+    tags: BTreeMap<String, u32>,
+}
+
+#[test]
+fn repeated_strings_become_references() {
+    let values = vec!["apple", "apple", "pear", "apple"];
+    let bytes = to_vec_with_options(&values, interned()).unwrap();
+
+    assert_eq!(bytes, [
+        0x84,
+        0xbd, 0xa5, b'a', b'p', b'p', b'l', b'e',
+        0xbe, 0x00,
+        0xbd, 0xa4, b'p', b'e', b'a', b'r',
+        0xbe, 0x00,
+    ]);
+
+    let out: Vec<String> = from_slice(&bytes).unwrap();
+
+    assert_eq!(out, values);
+
+    // References can still be borrowed from the input
+    let out: Vec<&str> = from_slice(&bytes).unwrap();
+
+    assert_eq!(out, values);
+}
+
+#[test]
+fn short_strings_are_not_interned() {
+    let bytes = to_vec_with_options(&("ab", "ab"), interned()).unwrap();
+
+    assert_eq!(bytes, to_vec(&("ab", "ab")).unwrap());
+}
+
+#[test]
+fn round_trip_keys_and_values() {
+    let records: Vec<Record> = (0..4)
+        .map(|i| Record {
+            kind: if i % 2 == 0 { "even".into() } else { "odd number".into() },
+            tags: vec![("weight".to_string(), i), ("height".to_string(), i * 2)]
+                .into_iter()
+                .collect(),
+        })
+        .collect();
+
+    let plain = to_vec(&records).unwrap();
+    let bytes = to_vec_with_options(&records, interned()).unwrap();
+
+    assert!(bytes.len() < plain.len());
+    assert_eq!(from_slice::<_, Vec<Record>>(&bytes).unwrap(), records);
+    assert_eq!(from_reader::<_, Vec<Record>>(&bytes[..]).unwrap(), records);
+}
+
+#[test]
+fn skipped_strings_are_still_remembered() {
+    let bytes = to_vec_with_options(&("skipped", "skipped"), interned()).unwrap();
+    let (_, out): (IgnoredAny, String) = from_slice(&bytes).unwrap();
+
+    assert_eq!(out, "skipped");
+}
+
+#[test]
+fn unknown_reference() {
+    let bytes = [0x82, 0xbd, 0xa3, b'a', b'b', b'c', 0xbe, 0x01];
+
+    match from_slice::<_, (String, String)>(&bytes[..]) {
+        Err(Error::UnknownInternedString(1)) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    match from_slice::<_, IgnoredAny>(&bytes[..]) {
+        Err(Error::UnknownInternedString(1)) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn definitions_must_hold_plain_bytes() {
+    // A definition of a definition
+    match from_slice::<_, String>(&[0xbd, 0xbd, 0xa3, b'a', b'b', b'c'][..]) {
+        Err(Error::UnexpectedValue(..)) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    // A definition of a uint
+    match from_slice::<_, String>(&[0xbd, 0x01][..]) {
+        Err(Error::ExpectedType(..)) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+}
@@ -0,0 +1,100 @@
+extern crate serde;
+extern crate serde_dbor;
+
+use std::io::{BufReader, Cursor};
+
+use serde::Deserialize;
+
+use serde_dbor::{from_bufread, from_reader_with_capacity, from_reader_with_capacity_and_options,
+    to_vec, to_vec_with_options, to_writer_with_capacity, to_writer_with_capacity_and_options,
+    Deserializer, DeserializerOptions, Error, SerializerOptions};
+
+
+fn value() -> (String, Vec<u32>) {
+    ("x".repeat(100), (0..200).collect())
+}
+
+#[test]
+fn any_capacity_writes_the_same_bytes() {
+    let expected = to_vec(&value()).unwrap();
+
+    for &capacity in &[0, 1, 7, 64, 4096] {
+        let out = to_writer_with_capacity(&value(), Vec::new(), capacity).unwrap();
+
+        assert_eq!(out, expected, "capacity {}", capacity);
+        assert_eq!(from_reader_with_capacity::<_, (String, Vec<u32>)>(&out[..], capacity).unwrap(),
+            value());
+    }
+}
+
+#[test]
+fn capacity_with_options() {
+    let options = SerializerOptions {
+        checksum: true,
+        ..SerializerOptions::canonical()
+    };
+    let out = to_writer_with_capacity_and_options(&value(), Vec::new(), 16, options).unwrap();
+
+    assert_eq!(out, to_vec_with_options(&value(), options).unwrap());
+
+    let read_options = DeserializerOptions {
+        checksum: true,
+        ..DeserializerOptions::canonical()
+    };
+    let read: (String, Vec<u32>) = from_reader_with_capacity_and_options(&out[..], 16, read_options)
+        .unwrap();
+
+    assert_eq!(read, value());
+
+    // The options are actually used
+    match from_reader_with_capacity_and_options::<_, u64>(&[0x18, 0x05][..], 16,
+        DeserializerOptions::strict())
+    {
+        Err(Error::NonMinimalInt(..)) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    let mut corrupted = out.clone();
+    let last = corrupted.len() - 1;
+
+    corrupted[last] ^= 1;
+
+    match from_reader_with_capacity_and_options::<_, (String, Vec<u32>)>(&corrupted[..], 16,
+        read_options)
+    {
+        Err(Error::ChecksumMismatch) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn bufread_leaves_the_rest_of_the_stream() {
+    let mut bytes = to_vec(&value()).unwrap();
+
+    bytes.extend(to_vec(&5u8).unwrap());
+
+    let mut reader = BufReader::with_capacity(8, Cursor::new(bytes));
+    let first: (String, Vec<u32>) = {
+        let mut deserializer = Deserializer::from_bufread(&mut reader);
+
+        Deserialize::deserialize(&mut deserializer).unwrap()
+    };
+
+    assert_eq!(first, value());
+    assert_eq!(from_bufread::<_, u8>(&mut reader).unwrap(), 5);
+}
+
+#[test]
+fn truncated_stream() {
+    let bytes = to_vec(&value()).unwrap();
+
+    match from_reader_with_capacity::<_, (String, Vec<u32>)>(&bytes[..bytes.len() - 1], 16) {
+        Err(Error::Eof) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    match from_bufread::<_, (String, Vec<u32>)>(&bytes[..bytes.len() - 1]) {
+        Err(Error::Eof) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+}
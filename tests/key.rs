@@ -0,0 +1,135 @@
+extern crate serde;
+extern crate serde_dbor;
+#[macro_use]
+extern crate serde_derive;
+
+use std::fmt::Debug;
+
+use serde::de::{DeserializeOwned, IgnoredAny};
+use serde::Serialize;
+
+use serde_dbor::{key, Error};
+
+
+#[derive(Serialize, Deserialize, PartialEq, PartialOrd, Debug, Clone)]
+enum Kind {
+    Empty,
+    Named(String),
+    Pair(i32, u8),
+}
+
+#[derive(Serialize, Deserialize, PartialEq, PartialOrd, Debug, Clone)]
+struct Row {
+    table: u16,
+    kind: Kind,
+    id: Option<i64>,
+}
+
+// Checks that the values are already sorted and that their keys sort the same way
+fn assert_sorted<T>(values: &[T])
+where
+    T: Serialize + DeserializeOwned + PartialOrd + Debug
+{
+    let keys: Vec<Vec<u8>> = values.iter().map(|value| key::to_vec(value).unwrap()).collect();
+
+    for (pair, keys) in values.windows(2).zip(keys.windows(2)) {
+        assert!(pair[0] < pair[1], "{:?} isn't less than {:?}", pair[0], pair[1]);
+        assert!(keys[0] < keys[1], "key of {:?} isn't less than key of {:?}", pair[0], pair[1]);
+    }
+
+    for (value, key) in values.iter().zip(&keys) {
+        assert_eq!(key::from_slice::<T>(key).unwrap(), *value);
+    }
+}
+
+#[test]
+fn numbers_sort_by_value() {
+    assert_sorted(&[0u64, 1, 0xff, 0x100, 0xffff_ffff, u64::MAX]);
+    assert_sorted(&[i64::MIN, -0x100, -0x81, -0x80, -1, 0, 1, 0x7f, 0x80, i64::MAX]);
+    assert_sorted(&[f64::NEG_INFINITY, -1.5, 0.0, f64::MIN_POSITIVE, 2.0, f64::INFINITY]);
+    assert!(key::to_vec(&-0.0f64).unwrap() < key::to_vec(&0.0f64).unwrap());
+    assert_sorted(&['\0', 'a', 'é', '\u{10ffff}']);
+
+    assert_eq!(key::to_vec(&5u8).unwrap(), [0x01, 0x05]);
+    assert_eq!(key::to_vec(&-1i32).unwrap(), [0x7f, 0xff]);
+}
+
+#[test]
+fn integer_sizes_share_an_encoding() {
+    assert_eq!(key::to_vec(&300u16).unwrap(), key::to_vec(&300u64).unwrap());
+    assert_eq!(key::to_vec(&-300i16).unwrap(), key::to_vec(&-300i64).unwrap());
+    assert_eq!(key::from_slice::<u64>(&key::to_vec(&300u16).unwrap()).unwrap(), 300);
+}
+
+#[test]
+fn strings_sort_bytewise() {
+    assert_sorted(&[
+        String::new(),
+        "\0".into(),
+        "\0\0".into(),
+        "\0a".into(),
+        "a".into(),
+        "a\0".into(),
+        "ab".into(),
+        "b".into(),
+    ]);
+
+    assert_eq!(key::to_vec("a\0").unwrap(), [b'a', 0x00, 0xff, 0x00, 0x01]);
+
+    // Borrowed strings only work without escaped zeros
+    assert_eq!(key::from_slice::<&str>(&key::to_vec("abc").unwrap()).unwrap(), "abc");
+}
+
+#[test]
+fn compound_values_sort_field_by_field() {
+    assert_sorted(&[
+        (1u8, String::from("b")),
+        (2, String::new()),
+        (2, String::from("a")),
+    ]);
+    assert_sorted(&[vec![], vec![0u32], vec![0, 0], vec![1]]);
+    assert_sorted(&[None, Some(false), Some(true)]);
+    assert_sorted(&[
+        Row { table: 1, kind: Kind::Pair(0, 0), id: Some(10) },
+        Row { table: 2, kind: Kind::Empty, id: Some(-5) },
+        Row { table: 2, kind: Kind::Named("x".into()), id: None },
+        Row { table: 2, kind: Kind::Named("x".into()), id: Some(i64::MIN) },
+        Row { table: 2, kind: Kind::Pair(-1, 9), id: None },
+        Row { table: 2, kind: Kind::Pair(0, 0), id: None },
+    ]);
+}
+
+#[test]
+fn keys_are_not_self_describing() {
+    let bytes = key::to_vec(&5u8).unwrap();
+
+    match key::from_slice::<IgnoredAny>(&bytes) {
+        Err(Error::NotSelfDescribing) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn malformed_keys() {
+    // An unsigned integer that's 9 bytes long
+    match key::from_slice::<u64>(&[0x09, 0, 0, 0, 0, 0, 0, 0, 0, 0]) {
+        Err(Error::InvalidKeyByte(0x09)) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    // A zero byte that isn't escaped or a terminator
+    match key::from_slice::<String>(&[b'a', 0x00, 0x02]) {
+        Err(Error::InvalidKeyByte(0x02)) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    match key::from_slice::<String>(b"a") {
+        Err(Error::Eof) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    match key::from_slice::<u8>(&[0x01, 0x05, 0x00]) {
+        Err(Error::TrailingBytes) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+}
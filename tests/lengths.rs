@@ -0,0 +1,121 @@
+extern crate serde;
+extern crate serde_dbor;
+
+use std::collections::BTreeMap;
+
+use serde::de::IgnoredAny;
+
+use serde_dbor::{from_reader, from_reader_with_options, from_slice, from_slice_with_options,
+    DeserializerOptions, Error, Type};
+
+
+fn limited(max_len: usize) -> DeserializerOptions {
+    DeserializerOptions {
+        max_len: Some(max_len),
+        ..DeserializerOptions::default()
+    }
+}
+
+#[test]
+fn every_width_is_read_the_same_way() {
+    let lengths: [&[u8]; 4] = [
+        &[0x98, 0x02],
+        &[0x99, 0x02, 0x00],
+        &[0x9a, 0x02, 0x00, 0x00, 0x00],
+        &[0x9b, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    ];
+
+    for header in &lengths {
+        let mut bytes = header.to_vec();
+
+        bytes.extend_from_slice(&[0x05, 0x06]);
+
+        assert_eq!(from_slice::<_, Vec<u8>>(&bytes).unwrap(), [5, 6]);
+        assert_eq!(from_reader::<_, Vec<u8>>(&bytes[..]).unwrap(), [5, 6]);
+    }
+}
+
+#[test]
+fn lengths_longer_than_the_input() {
+    // Bytes, seqs, and maps that claim to be much longer than the input
+    let inputs: [&[u8]; 4] = [
+        &[0xbb, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, b'a'],
+        &[0x9b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0x01],
+        &[0x9a, 0x04, 0x00, 0x00, 0x00, 0x01, 0x02, 0x03],
+        // Two entries need at least 4 bytes
+        &[0xc2, 0x01, 0x02, 0x03],
+    ];
+
+    for bytes in &inputs {
+        match from_slice::<_, IgnoredAny>(bytes) {
+            Err(Error::Eof) => {}
+            other => panic!("unexpected result {:?} for {:x?}", other, bytes),
+        }
+    }
+
+    match from_slice::<_, Vec<u64>>(inputs[1]) {
+        Err(Error::Eof) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    match from_slice::<_, BTreeMap<u8, u8>>(inputs[3]) {
+        Err(Error::Eof) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    // Streams find out once they run dry, without allocating the whole length first
+    match from_reader::<_, String>(inputs[0]) {
+        Err(Error::Eof) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn length_limits() {
+    let bytes = [0x83, 0x01, 0x02, 0x03];
+
+    assert_eq!(from_slice_with_options::<_, Vec<u8>>(&bytes, limited(3)).unwrap(), [1, 2, 3]);
+
+    match from_slice_with_options::<_, Vec<u8>>(&bytes, limited(2)) {
+        Err(Error::LengthLimitExceeded(Type::Seq, 3)) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    // Streams check the limit before reading any of the value
+    let bytes = [0xbb, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00];
+
+    match from_reader_with_options::<_, String>(&bytes[..], limited(1 << 20)) {
+        Err(Error::LengthLimitExceeded(Type::Bytes, 0x100000000)) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    match from_reader_with_options::<_, IgnoredAny>(&[0xc9][..], limited(8)) {
+        Err(Error::LengthLimitExceeded(Type::Map, 9)) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn empty_items_at_the_end_of_the_input() {
+    let bytes = [0x82, 0x05, 0xa0];
+
+    assert_eq!(from_slice::<_, (u8, String)>(&bytes).unwrap(), (5, String::new()));
+    assert_eq!(from_reader::<_, (u8, String)>(&bytes[..]).unwrap(), (5, String::new()));
+    assert!(from_slice::<_, IgnoredAny>(&bytes).is_ok());
+    assert!(from_reader::<_, IgnoredAny>(&bytes[..]).is_ok());
+
+    // But a string that's cut short still runs out
+    let bytes = [0x82, 0x05, 0xa3, b'a'];
+
+    for result in &[from_slice::<_, (u8, String)>(&bytes), from_reader(&bytes[..])] {
+        match *result {
+            Err(Error::Eof) => {}
+            ref other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    match from_reader::<_, IgnoredAny>(&bytes[..]) {
+        Err(Error::Eof) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+}
@@ -0,0 +1,102 @@
+extern crate serde;
+extern crate serde_dbor;
+
+use serde::de::IgnoredAny;
+
+use serde_dbor::{from_slice, to_vec, to_vec_with_options, Error, LeSlice, SerializerOptions};
+
+
+fn aligned() -> SerializerOptions {
+    SerializerOptions {
+        align_packed: true,
+        ..SerializerOptions::default()
+    }
+}
+
+#[test]
+fn round_trip() {
+    let values = [1u32, 0xdeadbeef, 7];
+    let bytes = to_vec(&LeSlice::new(&values)).unwrap();
+
+    // A plain byte string of the little endian numbers
+    assert_eq!(bytes[0], 0xa0 | 12);
+    assert_eq!(&bytes[1..5], &[1, 0, 0, 0]);
+
+    let slice: LeSlice<u32> = from_slice(&bytes).unwrap();
+
+    assert_eq!(slice.len(), 3);
+    assert_eq!(slice.get(1), Some(0xdeadbeef));
+    assert_eq!(slice.get(3), None);
+    assert_eq!(slice.to_vec(), values);
+}
+
+#[test]
+fn round_trip_floats_and_signed() {
+    let floats = [1.5f64, -0.0, f64::INFINITY];
+    let signed = [-1i16, 300, -300];
+    let bytes = to_vec(&(LeSlice::new(&floats), LeSlice::new(&signed))).unwrap();
+    let (floats_out, signed_out): (LeSlice<f64>, LeSlice<i16>) = from_slice(&bytes).unwrap();
+
+    assert_eq!(floats_out.to_vec(), floats);
+    assert_eq!(signed_out.to_vec(), signed);
+}
+
+#[test]
+fn aligned_slices_borrow_the_input() {
+    let values = [1u64, 2, 3];
+    let bytes = to_vec_with_options(&(true, LeSlice::new(&values)), aligned()).unwrap();
+
+    // The bool pushes the array off of its alignment, so padding has to be added
+    assert_eq!(bytes[2], 0xa0 | 28);
+
+    // Copy the output somewhere that's aligned for `u64`, since alignment is relative to the
+    //   start of the data
+    let mut storage = vec![0u64; bytes.len() / 8 + 1];
+    let buffer = unsafe {
+        ::std::slice::from_raw_parts_mut(storage.as_mut_ptr() as *mut u8, bytes.len())
+    };
+
+    buffer.copy_from_slice(&bytes);
+
+    let (flag, slice): (bool, LeSlice<u64>) = from_slice(&buffer[..]).unwrap();
+
+    assert!(flag);
+    assert_eq!(slice.to_vec(), values);
+
+    if cfg!(target_endian = "little") {
+        assert_eq!(slice.as_borrowed_slice(), Some(&values[..]));
+    }
+}
+
+#[test]
+fn aligned_bytes_can_be_skipped() {
+    let values = [1u32, 2];
+    let bytes = to_vec_with_options(&(7u8, LeSlice::new(&values), 9u8), aligned()).unwrap();
+    let (first, _, last): (u8, IgnoredAny, u8) = from_slice(&bytes).unwrap();
+
+    assert_eq!((first, last), (7, 9));
+}
+
+#[test]
+fn wrong_length_is_an_error() {
+    // 5 bytes can't hold a whole number of `u32`s
+    let bytes = [0xa5, 1, 2, 3, 4, 5];
+
+    assert!(from_slice::<_, LeSlice<u32>>(&bytes[..]).is_err());
+}
+
+#[test]
+fn padding_must_be_followed_by_plain_bytes() {
+    // Aligned bytes with one byte of padding, followed by a uint instead of a byte string
+    let bytes = [0xa0 | 28, 1, 0, 0x03];
+
+    match from_slice::<_, LeSlice<u16>>(&bytes[..]) {
+        Err(Error::ExpectedType(..)) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    // Aligned bytes can't be nested
+    let bytes = [0xa0 | 28, 0, 0xa0 | 28, 0, 0xa0];
+
+    assert!(from_slice::<_, LeSlice<u16>>(&bytes[..]).is_err());
+}
@@ -0,0 +1,126 @@
+extern crate serde_dbor;
+#[macro_use]
+extern crate serde_derive;
+
+use serde_dbor::{from_reader, from_slice, to_vec, to_vec_with_options, Error, RawDbor,
+    SerializerOptions};
+
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+enum Old {
+    A,
+    B(u8),
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Serialize, PartialEq, Debug)]
+enum New {
+    A,
+    B(u8),
+    Unknown,
+    C(String),
+    D,
+}
+
+#[test]
+fn raw_values_keep_their_bytes() {
+    let raw = RawDbor::from_value(&(1u8, "text")).unwrap();
+
+    assert_eq!(raw.as_bytes(), &to_vec(&(1u8, "text")).unwrap()[..]);
+    assert_eq!(raw.to_value::<(u8, String)>().unwrap(), (1, "text".to_string()));
+
+    // Written back out as they are
+    let bytes = to_vec(&(&raw, 2u8)).unwrap();
+    let (out, two): (RawDbor, u8) = from_slice(&bytes).unwrap();
+
+    assert_eq!(out, raw);
+    assert_eq!(two, 2);
+    assert_eq!(from_reader::<_, (RawDbor, u8)>(&bytes[..]).unwrap().0, raw);
+}
+
+#[test]
+fn captures_are_self_contained() {
+    let options = SerializerOptions {
+        intern_strings: true,
+        ..SerializerOptions::default()
+    };
+    let bytes = to_vec_with_options(&("word", "word"), options).unwrap();
+
+    assert_eq!(bytes[7..], [0xbe, 0x00]);
+
+    // Interned strings are written out in full, wherever they were first seen
+    for (first, second) in &[
+        from_slice::<_, (RawDbor, RawDbor)>(&bytes).unwrap(),
+        from_reader::<_, (RawDbor, RawDbor)>(&bytes[..]).unwrap(),
+    ] {
+        assert_eq!(first.as_bytes(), &to_vec(&"word").unwrap()[..]);
+        assert_eq!(second, first);
+    }
+
+    // And so are anchored values
+    let bytes = [0x82, 0xfc, 0x81, 0x05, 0xfd, 0x00];
+    let (first, second): (RawDbor, RawDbor) = from_slice(&bytes[..]).unwrap();
+
+    assert_eq!(first.as_bytes(), [0x81, 0x05]);
+    assert_eq!(second, first);
+    assert_eq!(second.to_value::<Vec<u8>>().unwrap(), [5]);
+
+    // A value that refers to itself can't be written out
+    match from_slice::<_, RawDbor>(&[0xfc, 0x81, 0xfd, 0x00][..]) {
+        Err(Error::UnknownAnchor(0)) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    match from_slice::<_, RawDbor>(&[0xbe, 0x00][..]) {
+        Err(Error::UnknownInternedString(0)) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn unknown_variants_skip_their_payload() {
+    let bytes = to_vec(&vec![New::C("new".into()), New::A, New::D, New::B(5)]).unwrap();
+
+    assert_eq!(from_slice::<_, Vec<Old>>(&bytes).unwrap(), [Old::Unknown, Old::A, Old::Unknown,
+        Old::B(5)]);
+    assert_eq!(from_reader::<_, Vec<Old>>(&bytes[..]).unwrap(), [Old::Unknown, Old::A,
+        Old::Unknown, Old::B(5)]);
+}
+
+#[test]
+fn known_unit_variants_have_no_payload() {
+    assert_eq!(to_vec(&Old::A).unwrap(), [0x60, 0x42]);
+
+    // Unit variants used to be written as just their index
+    assert_eq!(from_slice::<_, Old>(&[0x00][..]).unwrap(), Old::A);
+
+    match from_slice::<_, Old>(&[0x60, 0x05][..]) {
+        Err(Error::ExpectedType(..)) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    // Even when the variant it's caught by is a known one
+    match from_slice::<_, Old>(&[0x62, 0x05][..]) {
+        Err(Error::ExpectedType(..)) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    assert_eq!(from_slice::<_, Old>(&to_vec(&New::Unknown).unwrap()).unwrap(), Old::Unknown);
+}
+
+#[test]
+fn truncated_variant_names_run_out() {
+    // The name claims ten bytes, but there's only one
+    let bytes = [0x7b, 10, b'a'];
+
+    for result in &[from_slice::<_, Old>(&bytes[..]), from_reader(&bytes[..])] {
+        match *result {
+            Err(Error::Eof) => {}
+            ref other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    assert!(from_slice::<_, RawDbor>(&bytes[..]).is_err());
+    assert!(from_reader::<_, RawDbor>(&bytes[..]).is_err());
+}
@@ -0,0 +1,200 @@
+extern crate serde_dbor;
+#[macro_use]
+extern crate serde_derive;
+
+use std::collections::BTreeMap;
+
+use serde_dbor::schema::{self, Field, Item, Len, MapKey, Schema, Variant, Violation,
+    ViolationKind};
+use serde_dbor::{from_slice_with_options, to_vec, DeserializerOptions, Error, RawDbor, VariantId};
+
+
+#[derive(Serialize, Deserialize)]
+struct Order {
+    id: u32,
+    items: Vec<Line>,
+    status: Status,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Line {
+    name: String,
+    count: u8,
+}
+
+#[derive(Serialize, Deserialize)]
+enum Status {
+    Open,
+    Shipped { tracking: String },
+}
+
+fn order() -> Order {
+    Order {
+        id: 7,
+        items: vec![Line { name: "nut".into(), count: 3 }],
+        status: Status::Shipped { tracking: "1Z".into() },
+    }
+}
+
+fn kinds(violations: &[Violation]) -> Vec<&ViolationKind> {
+    violations.iter().map(|violation| &violation.kind).collect()
+}
+
+#[test]
+fn schemas_of_types_accept_their_data() {
+    let schema = Schema::of::<Order>().unwrap();
+    let bytes = to_vec(&order()).unwrap();
+
+    assert!(schema::validate(&bytes, &schema).is_empty());
+    assert!(schema::validate_reader(&bytes[..], &schema).is_empty());
+    assert!(schema::validate(&to_vec(&Order { status: Status::Open, ..order() }).unwrap(), &schema)
+        .is_empty());
+
+    // The wrong type of data is caught
+    let violations = schema::validate(&to_vec(&("text", 5u8)).unwrap(), &schema);
+
+    assert!(!violations.is_empty());
+}
+
+#[test]
+fn every_violation_is_reported() {
+    let schema = Schema::new(Item::Seq(Box::new(Item::Struct(vec![
+        Field::new("name", Item::Str(Len::new(1, 8))),
+        Field::new("count", Item::Integer { min: 1, max: 10 }),
+    ])), Len::ANY));
+    let bytes = to_vec(&vec![("", 0u8), ("nut", 3), ("bolt", 20)]).unwrap();
+    let violations = schema::validate(&bytes, &schema);
+
+    assert_eq!(violations.len(), 3);
+    assert!(matches!(violations[0].kind, ViolationKind::UnexpectedLength(0)));
+    assert_eq!(violations[0].offset, 2);
+    assert!(matches!(violations[1].kind, ViolationKind::OutOfRange));
+    assert_eq!(violations[1].offset, 3);
+    assert!(matches!(violations[2].kind, ViolationKind::OutOfRange));
+    assert_eq!(bytes[violations[2].offset..], [0x14]);
+}
+
+#[test]
+fn maps_and_enums() {
+    let schema = Schema::new(Item::Map {
+        keys: Box::new(Item::Str(Len::ANY)),
+        values: Box::new(Item::Enum(vec![
+            Variant::new("Off", Item::Unit),
+            Variant::new("On", Item::Integer { min: 0, max: 255 }),
+        ])),
+        len: Len::ANY,
+        required: vec![MapKey::Str("main".into())],
+    });
+
+    #[derive(Serialize)]
+    enum Light {
+        Off,
+        On(u8),
+        Blinking,
+    }
+
+    let mut lights = BTreeMap::new();
+
+    lights.insert("main", Light::On(100));
+    lights.insert("hall", Light::Off);
+
+    assert!(schema::validate(&to_vec(&lights).unwrap(), &schema).is_empty());
+
+    lights.remove("main");
+    lights.insert("porch", Light::Blinking);
+
+    let violations = schema::validate(&to_vec(&lights).unwrap(), &schema);
+
+    assert!(matches!(kinds(&violations)[..], [
+        ViolationKind::UnknownVariant(VariantId::Index(2)),
+        ViolationKind::MissingKey(MapKey::Str(ref key)),
+    ] if key == "main"));
+    assert_eq!(violations[1].offset, 0);
+}
+
+#[test]
+fn references_have_to_fit_where_they_are() {
+    let schema = Schema::new(Item::Tuple(vec![Item::Any, Item::Integer { min: 0, max: 10 }]));
+
+    // The string was anchored where anything goes, so it isn't known to be an integer
+    let bytes = [0x82, 0xfc, 0xa1, b'x', 0xfd, 0x00];
+    let violations = schema::validate(&bytes[..], &schema);
+
+    assert!(matches!(kinds(&violations)[..], [ViolationKind::UncheckedReference(0)]));
+    assert_eq!(violations[0].offset, 4);
+
+    // But a reference can go where the same thing is expected
+    let schema = Schema::new(Item::Tuple(vec![Item::Str(Len::ANY), Item::Str(Len::ANY)]));
+
+    assert!(schema::validate(&bytes[..], &schema).is_empty());
+
+    // And where anything goes
+    let schema = Schema::new(Item::Tuple(vec![Item::Str(Len::ANY), Item::Any]));
+
+    assert!(schema::validate(&bytes[..], &schema).is_empty());
+
+    // Anchors that were skipped over were never checked
+    let schema = Schema::new(Item::Tuple(vec![
+        Item::Any,
+        Item::Seq(Box::new(Item::Str(Len::ANY)), Len::ANY),
+    ]));
+    let bytes = [0x82, 0x81, 0xfc, 0x01, 0x81, 0xfd, 0x00];
+
+    assert!(matches!(kinds(&schema::validate(&bytes[..], &schema))[..],
+        [ViolationKind::UncheckedReference(0)]));
+
+    // References to anchors that don't exist can't be read
+    let violations = schema::validate(&[0x82, 0x01, 0xfd, 0x00][..], &schema);
+
+    assert!(matches!(kinds(&violations)[..], [ViolationKind::Malformed(Error::UnknownAnchor(0))]));
+}
+
+#[test]
+fn nesting_can_be_limited() {
+    let options = DeserializerOptions {
+        max_depth: Some(64),
+        ..DeserializerOptions::default()
+    };
+
+    // Deep enough to overflow the stack without a limit
+    let bytes = vec![0x81; 200_000];
+
+    for item in &[Item::Any, Item::Seq(Box::new(Item::Ref(0)), Len::ANY)] {
+        let schema = Schema {
+            root: item.clone(),
+            definitions: vec![Item::Seq(Box::new(Item::Ref(0)), Len::ANY)],
+        };
+        let violations = schema::validate_with_options(&bytes, &schema, options);
+
+        assert!(matches!(kinds(&violations)[..],
+            [ViolationKind::Malformed(Error::DepthLimitExceeded)]));
+    }
+
+    // Anything up to the limit is fine
+    let mut bytes = vec![0x81; 64];
+
+    bytes[63] = 0x80;
+
+    let schema = Schema::new(Item::Any);
+
+    assert!(schema::validate_with_options(&bytes, &schema, options).is_empty());
+
+    // And the deserializer stops at the same depth
+    assert!(from_slice_with_options::<_, RawDbor>(&bytes, options).is_ok());
+
+    match from_slice_with_options::<_, RawDbor>(&[0x81; 66][..], options) {
+        Err(Error::DepthLimitExceeded) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    // Which counts tags and anchors too
+    let options = DeserializerOptions {
+        max_depth: Some(2),
+        ..DeserializerOptions::default()
+    };
+
+    for bytes in &[&[0x81, 0xc1, 0xe5, 0x00, 0x00][..], &[0x81, 0x81, 0xfc, 0x00][..]] {
+        assert!(matches!(kinds(&schema::validate_with_options(bytes, &schema, options))[..],
+            [ViolationKind::Malformed(Error::DepthLimitExceeded)]));
+    }
+}
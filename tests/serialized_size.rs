@@ -0,0 +1,77 @@
+extern crate serde;
+extern crate serde_dbor;
+#[macro_use]
+extern crate serde_derive;
+
+use std::collections::BTreeMap;
+
+use serde::ser::{Serialize, Serializer};
+
+use serde_dbor::{serialized_size, serialized_size_with_options, to_vec, to_vec_with_options, Error,
+    LeSlice, SerializerOptions};
+
+
+#[derive(Serialize)]
+struct Message<'a> {
+    id: u64,
+    name: &'a str,
+    scores: Vec<i32>,
+    weights: LeSlice<'a, f32>,
+    extra: BTreeMap<String, Option<f64>>,
+}
+
+fn message(weights: &[f32]) -> Message<'_> {
+    Message {
+        id: 0x1_0000_0000,
+        name: "message",
+        scores: vec![-1, 300, -70_000, 24],
+        weights: LeSlice::new(weights),
+        extra: vec![("message".to_string(), Some(0.5)), ("b".to_string(), None)]
+            .into_iter()
+            .collect(),
+    }
+}
+
+#[test]
+fn sizes_match_the_output() {
+    let weights = [1.0, 2.0, 3.5];
+    let message = message(&weights);
+
+    assert_eq!(serialized_size(&message).unwrap(), to_vec(&message).unwrap().len() as u64);
+    assert_eq!(serialized_size(&()).unwrap(), 1);
+    assert_eq!(serialized_size(&vec![0u8; 300]).unwrap(), 303);
+}
+
+#[test]
+fn sizes_match_the_output_with_options() {
+    let weights = [1.0, 2.0, 3.5];
+    let message = message(&weights);
+    let all = [
+        SerializerOptions::canonical(),
+        SerializerOptions { align_packed: true, ..SerializerOptions::default() },
+        SerializerOptions { intern_strings: true, ..SerializerOptions::default() },
+        SerializerOptions { shrink_floats: true, ..SerializerOptions::default() },
+        SerializerOptions { checksum: true, ..SerializerOptions::default() },
+    ];
+
+    for &options in &all {
+        assert_eq!(serialized_size_with_options(&message, options).unwrap(),
+            to_vec_with_options(&message, options).unwrap().len() as u64, "{:?}", options);
+    }
+}
+
+#[test]
+fn unknown_lengths() {
+    struct Evens;
+
+    impl Serialize for Evens {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq((0..10).filter(|n| n % 2 == 0))
+        }
+    }
+
+    match serialized_size(&Evens) {
+        Err(Error::MustKnowItemSize) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+}
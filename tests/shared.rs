@@ -0,0 +1,164 @@
+extern crate serde;
+extern crate serde_dbor;
+#[macro_use]
+extern crate serde_derive;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use serde::de::IgnoredAny;
+use serde::ser::{Serialize, SerializeSeq, Serializer};
+
+use serde_dbor::{from_reader, from_slice, to_vec, Error, Shared};
+
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Node {
+    name: String,
+    children: Vec<Shared<Rc<Node>>>,
+}
+
+fn leaf(name: &str) -> Shared<Rc<Node>> {
+    Shared(Rc::new(Node {
+        name: name.into(),
+        children: Vec::new(),
+    }))
+}
+
+#[test]
+fn sharing_survives_a_round_trip() {
+    let shared = leaf("shared");
+    let root = Node {
+        name: "root".into(),
+        children: vec![shared.clone(), leaf("other"), shared],
+    };
+    let bytes = to_vec(&root).unwrap();
+
+    for out in &[from_slice::<_, Node>(&bytes).unwrap(), from_reader(&bytes[..]).unwrap()] {
+        assert_eq!(out.children.len(), 3);
+        assert_eq!(out.children[0].name, "shared");
+        assert_eq!(out.children[1].name, "other");
+        assert!(Rc::ptr_eq(&out.children[0], &out.children[2]));
+        assert!(!Rc::ptr_eq(&out.children[0], &out.children[1]));
+    }
+}
+
+#[test]
+fn encoding() {
+    let value = Shared(Rc::new(5u8));
+    let bytes = to_vec(&(value.clone(), value.clone(), value)).unwrap();
+
+    assert_eq!(bytes, [0x83, 0xfc, 0x05, 0xfd, 0x00, 0xfd, 0x00]);
+}
+
+#[test]
+fn arcs_are_shared_too() {
+    let value = Shared(Arc::new(String::from("text")));
+    let bytes = to_vec(&vec![value.clone(), value]).unwrap();
+    let out: Vec<Shared<Arc<String>>> = from_slice(&bytes).unwrap();
+
+    assert!(Arc::ptr_eq(&out[0], &out[1]));
+}
+
+// Serializes every number behind its own pointer, which is dropped again right away
+struct Temporaries(Vec<u32>);
+
+impl Serialize for Temporaries {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.0.len()))?;
+
+        for &n in &self.0 {
+            seq.serialize_element(&Shared(Rc::new(n)))?;
+        }
+
+        seq.end()
+    }
+}
+
+#[test]
+fn dropped_pointers_are_not_mistaken_for_new_ones() {
+    // The allocator is free to hand out the same address for every number
+    let bytes = to_vec(&Temporaries(vec![1, 2, 3])).unwrap();
+    let out: Vec<Shared<Rc<u32>>> = from_slice(&bytes).unwrap();
+
+    assert_eq!(out.iter().map(|n| *n.0).collect::<Vec<_>>(), [1, 2, 3]);
+}
+
+#[test]
+fn references_to_skipped_values() {
+    let value = Shared(Rc::new(String::from("text")));
+    let bytes = to_vec(&(value.clone(), value.clone(), value)).unwrap();
+    let (_, first, second): (IgnoredAny, Shared<Rc<String>>, Shared<Rc<String>>) =
+        from_slice(&bytes).unwrap();
+
+    assert_eq!(**first, "text");
+    assert!(Rc::ptr_eq(&first, &second));
+
+    let (_, first, _): (serde_dbor::RawDbor, Shared<Rc<String>>, IgnoredAny) =
+        from_slice(&bytes).unwrap();
+
+    assert_eq!(**first, "text");
+}
+
+#[test]
+fn references_inside_skipped_values() {
+    let inner = leaf("inner");
+    let outer = Shared(Rc::new(Node {
+        name: "outer".into(),
+        children: vec![inner.clone()],
+    }));
+    let bytes = to_vec(&(outer.clone(), inner, outer)).unwrap();
+    let (_, inner, outer): (IgnoredAny, Shared<Rc<Node>>, Shared<Rc<Node>>) =
+        from_slice(&bytes).unwrap();
+
+    assert_eq!(inner.name, "inner");
+    assert_eq!(outer.name, "outer");
+    assert_eq!(outer.children[0].name, "inner");
+}
+
+#[test]
+fn cycles_cannot_be_deserialized() {
+    #[derive(Serialize, Deserialize)]
+    struct Cycle {
+        next: Option<Shared<Rc<RefCell<Cycle>>>>,
+    }
+
+    let cycle = Shared(Rc::new(RefCell::new(Cycle {
+        next: None,
+    })));
+
+    cycle.borrow_mut().next = Some(cycle.clone());
+
+    let bytes = to_vec(&cycle).unwrap();
+
+    // Break the cycle so that it doesn't leak
+    cycle.borrow_mut().next = None;
+
+    match from_slice::<_, Shared<Rc<RefCell<Cycle>>>>(&bytes) {
+        Err(Error::UnknownAnchor(0)) => {}
+        Err(error) => panic!("unexpected error {:?}", error),
+        Ok(_) => panic!("deserialized a cycle"),
+    }
+}
+
+#[test]
+fn unknown_anchor() {
+    match from_slice::<_, (Shared<Rc<u8>>, Shared<Rc<u8>>)>(&[0x82, 0xfc, 0x05, 0xfd, 0x01][..]) {
+        Err(Error::UnknownAnchor(1)) => {}
+        Err(error) => panic!("unexpected error {:?}", error),
+        Ok(_) => panic!("resolved an unknown anchor"),
+    }
+
+    match from_slice::<_, IgnoredAny>(&[0xfd, 0x00][..]) {
+        Err(Error::UnknownAnchor(0)) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn references_to_a_different_type() {
+    let bytes = [0x82, 0xfc, 0x05, 0xfd, 0x00];
+
+    assert!(from_slice::<_, (Shared<Rc<u8>>, Shared<Rc<u16>>)>(&bytes[..]).is_err());
+}
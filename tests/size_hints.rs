@@ -0,0 +1,105 @@
+extern crate serde;
+extern crate serde_dbor;
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::de::{Deserialize, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+
+use serde_dbor::{from_reader, from_slice, to_vec};
+
+
+// The size hint given before every item of a seq or map, and once more at the end
+#[derive(Debug)]
+struct Hints(Vec<Option<usize>>);
+
+impl<'de> Deserialize<'de> for Hints {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct HintVisitor;
+
+        impl<'de> Visitor<'de> for HintVisitor {
+            type Value = Hints;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a seq or map")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Hints, A::Error> {
+                let mut hints = vec![seq.size_hint()];
+
+                while seq.next_element::<IgnoredAny>()?.is_some() {
+                    hints.push(seq.size_hint());
+                }
+
+                Ok(Hints(hints))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Hints, A::Error> {
+                let mut hints = vec![map.size_hint()];
+
+                while map.next_entry::<IgnoredAny, IgnoredAny>()?.is_some() {
+                    hints.push(map.size_hint());
+                }
+
+                Ok(Hints(hints))
+            }
+        }
+
+        deserializer.deserialize_any(HintVisitor)
+    }
+}
+
+#[test]
+fn hints_count_down() {
+    let bytes = to_vec(&vec![1u8, 2, 3]).unwrap();
+
+    assert_eq!(from_slice::<_, Hints>(&bytes).unwrap().0, [Some(3), Some(2), Some(1), Some(0)]);
+
+    let map: BTreeMap<u8, u8> = vec![(1, 2), (3, 4)].into_iter().collect();
+    let bytes = to_vec(&map).unwrap();
+
+    assert_eq!(from_slice::<_, Hints>(&bytes).unwrap().0, [Some(2), Some(1), Some(0)]);
+    assert_eq!(from_reader::<_, Hints>(&bytes[..]).unwrap().0, [Some(2), Some(1), Some(0)]);
+}
+
+// Only the size hint given before the first item of a seq or map
+struct First(Option<usize>);
+
+impl<'de> Deserialize<'de> for First {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct FirstVisitor;
+
+        impl<'de> Visitor<'de> for FirstVisitor {
+            type Value = First;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a seq or map")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<First, A::Error> {
+                Ok(First(seq.size_hint()))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<First, A::Error> {
+                Ok(First(map.size_hint()))
+            }
+        }
+
+        deserializer.deserialize_any(FirstVisitor)
+    }
+}
+
+#[test]
+fn stream_hints_are_capped() {
+    // Streams don't know how much is left, so a seq that claims to have a million items only
+    //   gets a hint of 65536
+    let bytes = [0x9a, 0x40, 0x42, 0x0f, 0x00];
+    let mut deserializer = serde_dbor::Deserializer::from_reader(&bytes[..]);
+
+    assert_eq!(First::deserialize(&mut deserializer).unwrap().0, Some(1 << 16));
+
+    let bytes = to_vec(&vec![0u8; 1000]).unwrap();
+    let mut deserializer = serde_dbor::Deserializer::from_reader(&bytes[..]);
+
+    assert_eq!(First::deserialize(&mut deserializer).unwrap().0, Some(1000));
+}
@@ -0,0 +1,178 @@
+extern crate serde;
+extern crate serde_dbor;
+#[macro_use]
+extern crate serde_derive;
+
+use std::collections::BTreeMap;
+
+use serde::de::IgnoredAny;
+
+use serde_dbor::{from_slice, from_slice_with_options, to_vec, to_vec_with_options, DeserializerOptions,
+    Error, SerializerOptions, Type};
+
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+enum Choice {
+    A,
+    B(u8),
+}
+
+fn strict<T>(bytes: &[u8]) -> serde_dbor::Result<T>
+where
+    T: for<'de> serde::Deserialize<'de>
+{
+    from_slice_with_options(bytes, DeserializerOptions::strict())
+}
+
+fn canonical<T>(bytes: &[u8]) -> serde_dbor::Result<T>
+where
+    T: for<'de> serde::Deserialize<'de>
+{
+    from_slice_with_options(bytes, DeserializerOptions::canonical())
+}
+
+#[test]
+fn shortest_forms_are_accepted() {
+    let value = (5u64, -300i32, "text", vec![1u8; 30], Choice::B(7), Choice::A);
+    let bytes = to_vec(&value).unwrap();
+
+    assert_eq!(strict::<(u64, i32, String, Vec<u8>, Choice, Choice)>(&bytes).unwrap(),
+        (5, -300, "text".into(), vec![1; 30], Choice::B(7), Choice::A));
+    assert!(strict::<IgnoredAny>(&bytes).is_ok());
+}
+
+#[test]
+fn long_numbers() {
+    // 5 with a 1 byte and an 8 byte parameter
+    for bytes in &[&[0x18, 0x05][..], &[0x1b, 5, 0, 0, 0, 0, 0, 0, 0][..]] {
+        assert_eq!(from_slice::<_, u64>(bytes).unwrap(), 5);
+
+        match strict::<u64>(bytes) {
+            Err(Error::NonMinimalInt(Type::Uint, _)) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+
+        match strict::<IgnoredAny>(bytes) {
+            Err(Error::NonMinimalInt(Type::Uint, _)) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    // -1 with a 1 byte parameter
+    match strict::<i8>(&[0x38, 0xff]) {
+        Err(Error::NonMinimalInt(Type::Int, 24)) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    // Numbers that need their parameter are fine
+    assert_eq!(strict::<u8>(&[0x18, 24]).unwrap(), 24);
+    assert_eq!(strict::<i16>(&[0x39, 0x00, 0x80]).unwrap(), -0x8000);
+}
+
+#[test]
+fn long_lengths() {
+    match strict::<Vec<u8>>(&[0x98, 0x01, 0x00]) {
+        Err(Error::NonMinimalLength(Type::Seq, 24)) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    match strict::<String>(&[0xb9, 0x01, 0x00, b'a']) {
+        Err(Error::NonMinimalLength(Type::Bytes, 25)) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    // An interned back-reference with a 1 byte extended parameter
+    match strict::<(String, String)>(&[0x82, 0xbd, 0xa3, b'a', b'b', b'c', 0xbe, 0xf8, 0x00]) {
+        Err(Error::NonMinimalLength(Type::Bytes, 248)) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn long_variant_ids() {
+    let bytes = [0x78, 0x01, 0x05];
+
+    assert_eq!(from_slice::<_, Choice>(&bytes[..]).unwrap(), Choice::B(5));
+
+    match strict::<Choice>(&bytes) {
+        Err(Error::NonMinimalVariant(24)) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn duplicate_keys() {
+    let bytes = [0xc2, 0x01, 0x01, 0x01, 0x02];
+
+    // Without strict mode, the last value wins
+    assert_eq!(from_slice::<_, BTreeMap<u8, u8>>(&bytes[..]).unwrap()[&1], 2);
+
+    match strict::<BTreeMap<u8, u8>>(&bytes) {
+        Err(Error::DuplicateMapKey(ref key)) if *key == [0x01] => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    match strict::<IgnoredAny>(&bytes) {
+        Err(Error::DuplicateMapKey(..)) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn unsorted_keys_are_only_rejected_in_canonical_mode() {
+    let bytes = [0xc2, 0x02, 0x00, 0x01, 0x00];
+
+    assert_eq!(strict::<BTreeMap<u8, u8>>(&bytes).unwrap().len(), 2);
+
+    match canonical::<BTreeMap<u8, u8>>(&bytes) {
+        Err(Error::UnsortedMapKey(ref key)) if *key == [0x01] => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn canonical_floats() {
+    // 1.0 fits in an f16
+    match canonical::<f64>(&to_vec(&1.0f64).unwrap()) {
+        Err(Error::NonMinimalFloat(5)) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    // A NaN with a payload
+    match canonical::<f32>(&[0x46, 0x01, 0x7e]) {
+        Err(Error::NonMinimalFloat(6)) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    let bytes = to_vec_with_options(&(0.1f64, f32::NAN), SerializerOptions::canonical()).unwrap();
+    let (number, nan) = canonical::<(f64, f32)>(&bytes).unwrap();
+
+    assert_eq!(number, 0.1);
+    assert!(nan.is_nan());
+}
+
+#[test]
+fn encodings_that_canonical_data_never_contains() {
+    let interned = to_vec_with_options(&("interned", "interned"), SerializerOptions {
+        intern_strings: true,
+        ..SerializerOptions::default()
+    }).unwrap();
+
+    match canonical::<(String, String)>(&interned) {
+        Err(Error::NonCanonical(Type::Bytes, 29)) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    match canonical::<IgnoredAny>(&[0xfc, 0x05]) {
+        Err(Error::NonCanonical(Type::Reserved, 28)) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    // Unit variants written as just their id
+    assert_eq!(strict::<Choice>(&[0x00]).unwrap(), Choice::A);
+
+    match canonical::<Choice>(&[0x00]) {
+        Err(Error::NonCanonical(Type::Uint, 0)) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+}
@@ -0,0 +1,70 @@
+extern crate serde;
+extern crate serde_dbor;
+
+use serde::de::IgnoredAny;
+
+use serde_dbor::{from_slice, to_vec, tags, Error, Tagged};
+
+
+#[test]
+fn small_tags_fit_in_the_instruction_byte() {
+    let value = Tagged::new(tags::URI, String::from("a:b"));
+    let bytes = to_vec(&value).unwrap();
+
+    assert_eq!(bytes, [0xe0 | 24, 32, 0xa3, b'a', b':', b'b']);
+    assert_eq!(to_vec(&Tagged::new(tags::EPOCH_TIME, 5u8)).unwrap(), [0xe1, 0x05]);
+
+    let out: Tagged<String> = from_slice(&bytes).unwrap();
+
+    assert_eq!(out, value);
+}
+
+#[test]
+fn round_trip_large_tags() {
+    for &tag in &[0, 23, 24, 0xff, 0x100, 0xffff, 0x10000, 0xffffffff, 0x100000000, u64::MAX] {
+        let value = Tagged::new(tag, vec![1u8, 2, 3]);
+        let bytes = to_vec(&value).unwrap();
+
+        assert_eq!(from_slice::<_, Tagged<Vec<u8>>>(&bytes).unwrap(), value);
+    }
+}
+
+#[test]
+fn tags_can_be_nested_and_skipped() {
+    let value = (Tagged::new(40, Tagged::new(41, 'x')), 7u8);
+    let bytes = to_vec(&value).unwrap();
+
+    assert_eq!(from_slice::<_, (Tagged<Tagged<char>>, u8)>(&bytes).unwrap(), value);
+    assert_eq!(from_slice::<_, (IgnoredAny, u8)>(&bytes).unwrap().1, 7);
+}
+
+#[test]
+fn tags_can_be_read_as_pairs() {
+    let bytes = to_vec(&Tagged::new(tags::UUID, 3u8)).unwrap();
+
+    assert_eq!(from_slice::<_, (u64, u8)>(&bytes).unwrap(), (tags::UUID, 3));
+}
+
+#[test]
+fn untagged_values_are_not_tagged() {
+    match from_slice::<_, Tagged<u8>>(&[0x03][..]) {
+        Err(Error::ExpectedType(..)) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    // Anchors and references aren't tags
+    assert!(from_slice::<_, Tagged<u8>>(&[0xfc, 0x03][..]).is_err());
+}
+
+#[test]
+fn truncated_tag() {
+    match from_slice::<_, Tagged<u8>>(&[0xe0 | 25, 0x00][..]) {
+        Err(Error::Eof) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    match from_slice::<_, Tagged<u8>>(&[0xe0 | 3][..]) {
+        Err(Error::Eof) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+}
@@ -0,0 +1,90 @@
+extern crate serde;
+extern crate serde_dbor;
+#[macro_use]
+extern crate serde_derive;
+
+use std::fmt;
+
+use serde::de::{Deserialize, Deserializer, EnumAccess, IgnoredAny, VariantAccess, Visitor};
+
+use serde_dbor::{from_reader, from_slice, from_slice_with_options, to_vec, DeserializerOptions,
+    Error};
+
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+enum Light {
+    Off,
+    On(u8),
+    Blinking { every: u16 },
+}
+
+// Says whether `deserialize_any` found an enum or something else
+#[derive(PartialEq, Debug)]
+enum Kind {
+    Enum,
+    Other,
+}
+
+impl<'de> Deserialize<'de> for Kind {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct KindVisitor;
+
+        impl<'de> Visitor<'de> for KindVisitor {
+            type Value = Kind;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("anything")
+            }
+
+            fn visit_u64<E>(self, _: u64) -> Result<Kind, E> {
+                Ok(Kind::Other)
+            }
+
+            fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<Kind, A::Error> {
+                // There's no knowing what kind of variant it is, so its payload is just skipped
+                let (_, variant) = data.variant::<IgnoredAny>()?;
+
+                variant.unit_variant()?;
+
+                Ok(Kind::Enum)
+            }
+        }
+
+        deserializer.deserialize_any(KindVisitor)
+    }
+}
+
+#[test]
+fn unit_variants_are_variants() {
+    assert_eq!(to_vec(&Light::Off).unwrap(), [0x60, 0x42]);
+    assert_eq!(to_vec(&Light::On(7)).unwrap(), [0x61, 0x07]);
+
+    for light in &[Light::Off, Light::On(7), Light::Blinking { every: 500 }] {
+        let bytes = to_vec(light).unwrap();
+
+        assert_eq!(&from_slice::<_, Light>(&bytes).unwrap(), light);
+        assert_eq!(&from_reader::<_, Light>(&bytes[..]).unwrap(), light);
+    }
+
+    // They can't be mistaken for numbers any more
+    assert_eq!(from_slice::<_, Kind>(&to_vec(&Light::Off).unwrap()).unwrap(), Kind::Enum);
+    assert_eq!(from_slice::<_, Kind>(&to_vec(&0u32).unwrap()).unwrap(), Kind::Other);
+    assert!(from_slice::<_, u32>(&to_vec(&Light::Off).unwrap()).is_err());
+}
+
+#[test]
+fn old_unit_variants_can_still_be_read() {
+    assert_eq!(from_slice::<_, Light>(&[0x00][..]).unwrap(), Light::Off);
+    assert_eq!(from_reader::<_, Light>(&[0x00][..]).unwrap(), Light::Off);
+    assert_eq!(from_slice::<_, Vec<Light>>(&[0x82, 0x00, 0x61, 0x03][..]).unwrap(),
+        [Light::Off, Light::On(3)]);
+
+    // But not as a variant with a payload
+    assert!(from_slice::<_, Light>(&[0x01][..]).is_err());
+
+    // Or at all in canonical mode
+    match from_slice_with_options::<_, Light>(&[0x00][..], DeserializerOptions::canonical()) {
+        Err(Error::NonCanonical(..)) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+}